@@ -0,0 +1,484 @@
+//! `<action>` 中工具调用的语法：`工具名(参数, ...)`。参数可以是带引号的字符串、
+//! 不带引号的文本、`名称=值` 形式的命名参数、单个JSON对象，以及原样保留的
+//! heredoc（`<<EOF` ... `EOF`）和 CDATA（`<![CDATA[...]]>`）块
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use crate::tools::ToolParameter;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// 解析后的工具调用，参数在执行前通过 [`ActionCall::bind`] 对应到工具声明的参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionCall {
+    pub tool: String,
+    pub positional: Vec<String>,
+    /// 命名参数，按书写顺序
+    pub named: Vec<(String, String)>,
+    /// 唯一的参数是JSON对象时的解析结果
+    payload: Option<Map<String, Value>>,
+}
+
+impl ActionCall {
+    /// 按工具声明的参数顺序排列参数：位置参数在前，命名参数填入对应位置，
+    /// 中间未提供的可选参数为空字符串。唯一的JSON对象参数的键都是参数名时按命名参数处理
+    pub fn bind(&self, parameters: &[ToolParameter]) -> AgentResult<Vec<String>> {
+        let payload = self.payload.as_ref().filter(|payload| {
+            !parameters.is_empty()
+                && payload
+                    .keys()
+                    .all(|key| parameters.iter().any(|p| p.name == *key))
+        });
+        if let Some(payload) = payload {
+            let named = payload
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            return self.bind_named(parameters, &[], named);
+        }
+        if self.named.is_empty() {
+            return Ok(self.positional.clone());
+        }
+        let named = self
+            .named
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+            .collect();
+        self.bind_named(parameters, &self.positional, named)
+    }
+
+    fn bind_named(
+        &self,
+        parameters: &[ToolParameter],
+        positional: &[String],
+        named: Vec<(String, Value)>,
+    ) -> AgentResult<Vec<String>> {
+        let error = |message: String| AgentError::ToolExecutionError(message);
+        if parameters.is_empty() {
+            return Err(error(t!("action.no_declared_parameters", tool = self.tool)));
+        }
+
+        let mut values: Vec<Option<Vec<String>>> = vec![None; parameters.len()];
+        for (index, value) in positional.iter().enumerate() {
+            // 超出声明的位置参数归入最后一个可重复参数，否则交给工具自行检查
+            let slot = index.min(parameters.len() - 1);
+            values[slot]
+                .get_or_insert_with(Vec::new)
+                .push(value.clone());
+        }
+        for (name, value) in named {
+            let Some(index) = parameters.iter().position(|p| p.name == name) else {
+                let names: Vec<&str> = parameters.iter().map(|p| p.name.as_str()).collect();
+                return Err(error(t!(
+                    "action.unknown_parameter",
+                    tool = self.tool,
+                    name = name,
+                    parameters = names.join(", ")
+                )));
+            };
+            if values[index].is_some() {
+                return Err(error(t!("action.duplicate_parameter", name = name)));
+            }
+            values[index] = Some(match value {
+                Value::Array(items) if parameters[index].repeated => {
+                    items.into_iter().map(value_text).collect()
+                }
+                value => vec![value_text(value)],
+            });
+        }
+
+        if let Some(missing) = parameters
+            .iter()
+            .zip(&values)
+            .find(|(p, value)| p.required && value.is_none())
+        {
+            return Err(error(t!(
+                "action.missing_parameter",
+                tool = self.tool,
+                name = missing.0.name
+            )));
+        }
+        let provided = values
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        Ok(values
+            .into_iter()
+            .take(provided)
+            .flat_map(|value| value.unwrap_or_else(|| vec![String::new()]))
+            .collect())
+    }
+}
+
+impl fmt::Display for ActionCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self
+            .positional
+            .iter()
+            .cloned()
+            .chain(
+                self.named
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value)),
+            )
+            .collect();
+        write!(f, "{}({})", self.tool, args.join(", "))
+    }
+}
+
+/// JSON值作为参数文本：字符串取原值，其他类型使用JSON表示
+fn value_text(value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        other => other.to_string(),
+    }
+}
+
+/// 解析 `<action>` 标签中的工具调用
+pub fn parse_action(action: &str) -> AgentResult<ActionCall> {
+    Parser::new(action).parse().map_err(|reason| {
+        AgentError::ParseError(t!(
+            "action.invalid",
+            action = action.trim(),
+            reason = reason
+        ))
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+enum Argument {
+    Value(String),
+    Json(String, Map<String, Value>),
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.position += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.position += len;
+        Some(&rest[..len])
+    }
+
+    fn parse(mut self) -> Result<ActionCall, String> {
+        self.skip_whitespace();
+        let tool = self
+            .identifier()
+            .ok_or_else(|| t!("action.expected_tool_name"))?
+            .to_string();
+        self.skip_whitespace();
+        if !self.eat("(") {
+            return Err(t!("action.expected_open_paren"));
+        }
+
+        let mut call = ActionCall {
+            tool,
+            positional: Vec::new(),
+            named: Vec::new(),
+            payload: None,
+        };
+        let mut payload = None;
+        loop {
+            self.skip_whitespace();
+            if self.eat(")") {
+                break;
+            }
+
+            let name = self.argument_name();
+            match (name, self.argument()?) {
+                (Some(name), argument) => {
+                    let value = match argument {
+                        Argument::Value(value) | Argument::Json(value, _) => value,
+                    };
+                    call.named.push((name.to_string(), value));
+                }
+                (None, _) if !call.named.is_empty() => {
+                    return Err(t!("action.positional_after_named"));
+                }
+                (None, Argument::Value(value)) => call.positional.push(value),
+                (None, Argument::Json(value, object)) => {
+                    payload = Some(object);
+                    call.positional.push(value);
+                }
+            }
+
+            self.skip_whitespace();
+            if self.eat(")") {
+                break;
+            }
+            if !self.eat(",") {
+                return Err(t!("action.expected_separator", position = self.position));
+            }
+        }
+
+        if !self.rest().trim().is_empty() {
+            return Err(t!("action.trailing_content", content = self.rest().trim()));
+        }
+        if call.positional.len() == 1 && call.named.is_empty() {
+            call.payload = payload;
+        }
+        Ok(call)
+    }
+
+    /// `名称=` 形式的命名参数，不是命名参数时不消耗输入
+    fn argument_name(&mut self) -> Option<&'a str> {
+        let start = self.position;
+        if let Some(name) = self.identifier() {
+            self.skip_whitespace();
+            if self.rest().starts_with('=') && !self.rest().starts_with("==") {
+                self.position += 1;
+                self.skip_whitespace();
+                return Some(name);
+            }
+        }
+        self.position = start;
+        None
+    }
+
+    fn argument(&mut self) -> Result<Argument, String> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.quoted(quote).map(Argument::Value),
+            Some('{') => self.json(),
+            _ if self.rest().starts_with("<![CDATA[") => self.cdata().map(Argument::Value),
+            _ if self.rest().starts_with("<<") => self.heredoc().map(Argument::Value),
+            Some(_) => Ok(Argument::Value(self.bare())),
+            None => Err(t!("action.expected_close_paren")),
+        }
+    }
+
+    /// 带引号的字符串，支持 `\n`、`\t`、`\r`、`\0`、`\\` 和引号转义，其他反斜杠原样保留
+    fn quoted(&mut self, quote: char) -> Result<String, String> {
+        self.position += quote.len_utf8();
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.position += offset + c.len_utf8();
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, '0')) => value.push('\0'),
+                    Some((_, escaped @ ('\\' | '"' | '\''))) => value.push(escaped),
+                    Some((_, other)) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => value.push('\\'),
+                },
+                c => value.push(c),
+            }
+        }
+        Err(t!("action.unterminated_string"))
+    }
+
+    fn json(&mut self) -> Result<Argument, String> {
+        let rest = self.rest();
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        match stream.next() {
+            Some(Ok(Value::Object(object))) => {
+                let len = stream.byte_offset();
+                self.position += len;
+                Ok(Argument::Json(rest[..len].to_string(), object))
+            }
+            Some(Err(e)) => Err(t!("action.invalid_json", error = e)),
+            _ => Err(t!("action.invalid_json", error = "")),
+        }
+    }
+
+    fn cdata(&mut self) -> Result<String, String> {
+        self.position += "<![CDATA[".len();
+        let end = self
+            .rest()
+            .find("]]>")
+            .ok_or_else(|| t!("action.unterminated_block", delimiter = "]]>"))?;
+        let value = self.rest()[..end].to_string();
+        self.position += end + "]]>".len();
+        Ok(value)
+    }
+
+    /// `<<EOF` 换行后直到单独一行的 `EOF` 为止，内容原样保留并以换行结尾；
+    /// 结束标记后可以紧跟 `,` 或 `)`。标记可以加引号，如 `<<'EOF'`
+    fn heredoc(&mut self) -> Result<String, String> {
+        self.position += "<<".len();
+        let quote = self.peek().filter(|c| *c == '\'' || *c == '"');
+        if let Some(quote) = quote {
+            self.position += quote.len_utf8();
+        }
+        let delimiter = self
+            .identifier()
+            .ok_or_else(|| t!("action.expected_heredoc_delimiter"))?;
+        if let Some(quote) = quote {
+            if !self.eat(&quote.to_string()) {
+                return Err(t!("action.expected_heredoc_delimiter"));
+            }
+        }
+        let line_end = self.rest().find('\n').unwrap_or(self.rest().len());
+        if !self.rest()[..line_end].trim().is_empty() {
+            return Err(t!("action.expected_heredoc_delimiter"));
+        }
+        self.position += (line_end + 1).min(self.rest().len());
+
+        let mut value = String::new();
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+            let line_len = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            let line = &rest[..line_len];
+            if let Some(after) = line.trim_start().strip_prefix(delimiter) {
+                let after = after.trim();
+                if after.is_empty() || after.starts_with(',') || after.starts_with(')') {
+                    self.position += line.len() - line.trim_start().len() + delimiter.len();
+                    return Ok(value);
+                }
+            }
+            value.push_str(line.strip_suffix('\n').unwrap_or(line));
+            value.push('\n');
+            self.position += line_len;
+        }
+        Err(t!("action.unterminated_block", delimiter = delimiter))
+    }
+
+    /// 不带引号的参数，到顶层的 `,` 或 `)` 为止，括号可以嵌套
+    fn bare(&mut self) -> String {
+        let rest = self.rest();
+        let mut depth = 0usize;
+        let mut end = rest.len();
+        for (offset, c) in rest.char_indices() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' if depth == 0 => {
+                    end = offset;
+                    break;
+                }
+                ',' if depth == 0 => {
+                    end = offset;
+                    break;
+                }
+                ')' | ']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        self.position += end;
+        rest[..end].trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters() -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("path", "文件路径"),
+            ToolParameter::optional("staged", "已暂存"),
+            ToolParameter::optional("paths", "更多路径").repeated(),
+        ]
+    }
+
+    #[test]
+    fn test_parses_argument_forms() {
+        let call =
+            parse_action(r#"write_to_file("a.txt", 'it\'s "ok"\n', C:\dir\n, run(1, 2))"#).unwrap();
+        assert_eq!(call.tool, "write_to_file");
+        assert_eq!(
+            call.positional,
+            vec!["a.txt", "it's \"ok\"\n", "C:\\dir\\n", "run(1, 2)"]
+        );
+
+        let call = parse_action(
+            "write_to_file(\"src/main.rs\", <<'EOF'\nfn main() {\n    println!(\"<![CDATA[\");\n}\nEOF)",
+        )
+        .unwrap();
+        assert_eq!(
+            call.positional[1],
+            "fn main() {\n    println!(\"<![CDATA[\");\n}\n"
+        );
+
+        let call =
+            parse_action("write_to_file(path=\"a.md\", content=<![CDATA[# 标题\n\"原样\" \\n]]>)")
+                .unwrap();
+        assert!(call.positional.is_empty());
+        assert_eq!(
+            call.named,
+            vec![
+                ("path".to_string(), "a.md".to_string()),
+                ("content".to_string(), "# 标题\n\"原样\" \\n".to_string())
+            ]
+        );
+
+        assert_eq!(parse_action("git_status()").unwrap().positional.len(), 0);
+        assert!(parse_action("read_file(\"a.txt\"").is_err());
+        assert!(parse_action("read_file(\"a.txt\") extra").is_err());
+        assert!(parse_action("read_file(path=\"a\", \"b\")").is_err());
+        assert!(parse_action("write_to_file(\"a\", <<EOF\nno end)").is_err());
+    }
+
+    #[test]
+    fn test_binds_named_and_json_arguments() {
+        let bind = |action: &str| parse_action(action).unwrap().bind(&parameters());
+
+        assert_eq!(
+            bind(r#"git_diff("a.rs", staged="staged")"#).unwrap(),
+            vec!["a.rs", "staged"]
+        );
+        // 中间未提供的可选参数为空字符串
+        assert_eq!(
+            bind(r#"git_diff(paths="b.rs", path="a.rs")"#).unwrap(),
+            vec!["a.rs", "", "b.rs"]
+        );
+        assert_eq!(
+            bind(r#"git_diff({"path": "a.rs", "paths": ["b.rs", "c.rs"]})"#).unwrap(),
+            vec!["a.rs", "", "b.rs", "c.rs"]
+        );
+        // 键不是参数名的JSON对象按普通参数传递
+        assert_eq!(
+            bind(r#"git_diff({"other": 1})"#).unwrap(),
+            vec![r#"{"other": 1}"#]
+        );
+
+        assert!(bind(r#"git_diff(staged="staged")"#).is_err());
+        assert!(bind(r#"git_diff("a.rs", path="b.rs")"#).is_err());
+        assert!(bind(r#"git_diff(file="a.rs")"#).is_err());
+        assert!(parse_action(r#"find_recipe(dish="番茄炒蛋")"#)
+            .unwrap()
+            .bind(&[])
+            .is_err());
+    }
+}
@@ -1,60 +1,306 @@
+use crate::action::{parse_action, ActionCall};
+use crate::checkpoint::Checkpoint;
+use crate::config::AgentConfig;
 use crate::errors::{AgentError, AgentResult};
-use crate::prompt_template::PromptRenderer;
-use crate::tools::ToolRegistry;
+use crate::plan::{Plan, PlanTracker};
+use crate::project_context::ProjectContext;
+use crate::prompt_template::{today, PromptContext, PromptRenderer, ToolInfo};
+use crate::react_parser::{ParseEvent, ReactOutput, ReactParser, ReactTag};
+use crate::replay::RecordedModel;
+use crate::tools::{
+    current_branch, format_memory, tool_signature, DelegateSlots, DelegateTool, ToolRegistry,
+    DELEGATE_TOOL,
+};
+use crate::trajectory::{millis, Outcome, TrajectoryEvent, TrajectoryRecorder};
+use crate::{pt, t};
 // 这些类型在当前实现中未使用，但保留以备将来扩展
 use async_openai::{
     types::{ChatCompletionRequestMessage, CreateChatCompletionRequestArgs},
     Client,
 };
 use futures::StreamExt;
-use regex::Regex;
-use std::env;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 pub struct ReActAgent {
     tools: ToolRegistry,
-    model: String,
+    /// 未应用模型配置的原始配置，切换模型配置时以此为基础
+    base_config: AgentConfig,
+    config: AgentConfig,
     project_directory: String,
     client: Client<async_openai::config::OpenAIConfig>,
     prompt_renderer: PromptRenderer,
     // 添加对话历史存储
     conversation_history: Vec<ChatCompletionRequestMessage>,
+    /// 嵌套层数，顶层Agent为 0；子Agent不输出运行过程
+    depth: usize,
+    /// 所在委派树的子任务并发名额
+    delegate_slots: DelegateSlots,
+    /// 当前任务的运行轨迹，任务结束后关闭
+    trajectory: Option<TrajectoryRecorder>,
+    /// 最近一个任务的运行轨迹文件
+    last_trajectory: Option<PathBuf>,
+    /// 回放时代替API的录制输出
+    recorded_model: Option<RecordedModel>,
+}
+
+/// 一次工具调用的结果，记录到运行轨迹后再交给模型
+struct ToolRun {
+    arguments: Option<Vec<String>>,
+    result: AgentResult<String>,
+    duration: Duration,
 }
 
 impl ReActAgent {
-    pub fn new(tools: ToolRegistry, model: String, project_directory: String) -> AgentResult<Self> {
-        dotenv::dotenv().ok();
+    pub fn new(
+        tools: ToolRegistry,
+        config: AgentConfig,
+        project_directory: String,
+    ) -> AgentResult<Self> {
+        let slots = DelegateSlots::new(config.delegate.max_concurrent);
+        Self::with_depth(tools, config, project_directory, 0, slots)
+    }
+
+    /// 创建第 `depth` 层的Agent，子Agent由 delegate 工具创建并共用父Agent的并发名额
+    pub(crate) fn with_depth(
+        tools: ToolRegistry,
+        config: AgentConfig,
+        project_directory: String,
+        depth: usize,
+        delegate_slots: DelegateSlots,
+    ) -> AgentResult<Self> {
+        let active = config.resolved()?;
+        let client = Self::create_client(&active)?;
+        let prompt_renderer = PromptRenderer::load(Path::new(&project_directory), &active.prompt)?;
+
+        let mut agent = Self {
+            tools,
+            base_config: config,
+            config: active,
+            project_directory,
+            client,
+            prompt_renderer,
+            conversation_history: vec![],
+            depth,
+            delegate_slots,
+            trajectory: None,
+            last_trajectory: None,
+            recorded_model: None,
+        };
+        agent.install_delegate();
+        Ok(agent)
+    }
+
+    /// 未超过嵌套层数上限时提供 delegate 工具，子Agent使用当前生效的配置
+    fn install_delegate(&mut self) {
+        if self.depth >= self.config.delegate.max_depth
+            || !self.config.tools.is_enabled(DELEGATE_TOOL)
+        {
+            return;
+        }
+        let delegate = DelegateTool::new(
+            &self.tools,
+            self.config.clone(),
+            self.project_directory.clone(),
+            self.depth + 1,
+            self.delegate_slots.clone(),
+        );
+        self.tools.register(delegate);
+    }
+
+    /// 使用录制的模型输出代替API，用于回放运行轨迹
+    pub fn with_recorded_model(mut self, recorded: RecordedModel) -> Self {
+        self.recorded_model = Some(recorded);
+        self
+    }
 
-        let api_key = env::var("OPENROUTER_API_KEY")
-            .map_err(|_| AgentError::EnvVarError("OPENROUTER_API_KEY".to_string()))?;
+    /// 顶层Agent输出模型的流式内容、动作和观察结果，子Agent只输出警告
+    fn verbose(&self) -> bool {
+        self.depth == 0
+    }
 
-        let api_base = env::var("OPENAI_API_BASE")
-            .map_err(|_| AgentError::EnvVarError("OPENAI_API_BASE".to_string()))?;
+    fn create_client(
+        config: &AgentConfig,
+    ) -> AgentResult<Client<async_openai::config::OpenAIConfig>> {
+        let (api_key, api_base) = config.require_provider()?;
 
-        // 配置OpenAI客户端使用OpenRouter
-        let config = async_openai::config::OpenAIConfig::new()
+        // 配置OpenAI兼容客户端（默认使用OpenRouter）
+        let client_config = async_openai::config::OpenAIConfig::new()
             .with_api_key(api_key)
-            .with_api_base(&api_base);
+            .with_api_base(api_base);
 
-        let client = Client::with_config(config);
+        Ok(Client::with_config(client_config))
+    }
 
-        Ok(Self {
-            tools,
-            model,
-            project_directory,
-            client,
-            prompt_renderer: PromptRenderer::default(),
-            conversation_history: vec![],
-        })
+    /// 当前生效的配置（已应用模型配置）
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// 可切换的模型配置名称
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.base_config
+            .profiles
+            .keys()
+            .map(|k| k.as_str())
+            .collect()
+    }
+
+    /// 切换到指定的模型配置，对话历史保持不变
+    pub fn switch_profile(&mut self, name: &str) -> AgentResult<()> {
+        let config = self.base_config.with_profile(name)?;
+        self.client = Self::create_client(&config)?;
+        self.config = config;
+        self.install_delegate();
+        Ok(())
     }
 
     pub async fn run(&mut self, user_input: &str) -> AgentResult<String> {
+        // 为本次任务开启检查点，记录工具对文件的修改以便撤销
+        if let Some(checkpoints) = self.tools.checkpoints() {
+            checkpoints.begin_task(user_input);
+        }
+
+        self.start_trajectory(user_input);
+        let result = self.run_task(user_input).await;
+        match &result {
+            Ok(answer) => self.finish_trajectory(Outcome::Answer(answer.clone())),
+            Err(e) => self.finish_trajectory(Outcome::Failed(e.to_string())),
+        }
+
+        if let Some(checkpoint) = self.tools.checkpoints().and_then(|c| c.finish_task()) {
+            if !checkpoint.changes().is_empty() || !checkpoint.untracked_changes().is_empty() {
+                println!(
+                    "\n\n{}",
+                    t!("agent.checkpoint_created", summary = checkpoint.summary())
+                );
+                println!("{}", t!("agent.undo_hint"));
+            }
+        }
+
+        result
+    }
+
+    /// 为任务创建运行轨迹文件，创建失败时给出警告，任务照常运行
+    fn start_trajectory(&mut self, task: &str) {
+        self.trajectory = match TrajectoryRecorder::create(&self.project_directory) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("\n\n{}", t!("trajectory.write_failed", error = e));
+                None
+            }
+        };
+        self.record(TrajectoryEvent::Start {
+            task: task.to_string(),
+            model: self.config.model.name.clone(),
+            depth: self.depth,
+            date: today(),
+        });
+    }
+
+    fn record(&self, event: TrajectoryEvent) {
+        if let Some(trajectory) = &self.trajectory {
+            trajectory.record(event);
+        }
+    }
+
+    /// 记录任务结果并关闭运行轨迹；任务失败时提示轨迹文件的位置
+    fn finish_trajectory(&mut self, outcome: Outcome) {
+        let Some(trajectory) = self.trajectory.take() else {
+            return;
+        };
+        let failed = matches!(outcome, Outcome::Failed(_));
+        trajectory.record(TrajectoryEvent::Finish {
+            outcome,
+            duration_ms: millis(trajectory.elapsed()),
+        });
+        self.last_trajectory = Some(trajectory.path().to_path_buf());
+        if failed && self.verbose() {
+            eprintln!(
+                "\n\n{}",
+                t!(
+                    "trajectory.saved",
+                    path = trajectory
+                        .path()
+                        .strip_prefix(&self.project_directory)
+                        .unwrap_or(trajectory.path())
+                        .display()
+                )
+            );
+        }
+    }
+
+    /// 请模型为任务制定计划，返回计划中的步骤，由调用方确认后通过 `set_plan` 开始执行
+    ///
+    /// 计划确认前不应修改项目，制定计划时只提供只读工具
+    pub async fn draft_plan(&mut self, task: &str) -> AgentResult<Vec<String>> {
+        // 计划文件损坏时先报告，不必等模型制定完计划才发现无法保存
+        if let Some(tracker) = self.tools.plan() {
+            tracker.check_writable()?;
+        }
+        let read_only = self.tools.subset(|tool| tool.is_read_only());
+        let tools = std::mem::replace(&mut self.tools, read_only);
+        let answer = self.run(&pt!("plan.prompt.draft", task = task)).await;
+        self.tools = tools;
+
+        let answer = answer?;
+        let steps = Plan::parse_steps(&answer);
+        if steps.is_empty() {
+            return Err(AgentError::ParseError(t!("plan.no_steps")));
+        }
+        Ok(steps)
+    }
+
+    /// 当前计划，工具集未提供计划时返回 None
+    pub fn plan(&self) -> Option<Plan> {
+        self.tools.plan().and_then(|plan| plan.current())
+    }
+
+    /// 替换当前计划，所有步骤从未开始的状态执行
+    pub fn set_plan(&self, plan: Plan) -> AgentResult<()> {
+        self.plan_tracker()?.set(plan)
+    }
+
+    /// 放弃当前计划
+    pub fn clear_plan(&self) -> AgentResult<()> {
+        self.plan_tracker()?.clear()
+    }
+
+    fn plan_tracker(&self) -> AgentResult<&PlanTracker> {
+        self.tools
+            .plan()
+            .ok_or_else(|| AgentError::RuntimeError(t!("plan.disabled")))
+    }
+
+    /// 从第一个未完成的步骤开始执行当前计划，模型通过 update_plan 记录进度
+    pub async fn execute_plan(&mut self) -> AgentResult<String> {
+        let plan = self
+            .plan_tracker()?
+            .current()
+            .ok_or_else(|| AgentError::RuntimeError(t!("plan.no_plan")))?;
+        let Some(next) = plan.first_unfinished() else {
+            return Ok(t!("plan.all_done"));
+        };
+        let prompt = pt!(
+            "plan.prompt.execute",
+            task = plan.task,
+            plan = plan.render(),
+            step = next + 1
+        );
+        self.run(&prompt).await
+    }
+
+    async fn run_task(&mut self, user_input: &str) -> AgentResult<String> {
         // 创建当前任务的消息列表，包含系统提示词和用户输入
+        let system_prompt = self.render_system_prompt(user_input)?;
+        self.record(TrajectoryEvent::SystemPrompt {
+            content: system_prompt.clone(),
+        });
         let mut current_messages = vec![ChatCompletionRequestMessage::System(
             async_openai::types::ChatCompletionRequestSystemMessage {
                 content: async_openai::types::ChatCompletionRequestSystemMessageContent::Text(
-                    self.render_system_prompt()?,
+                    system_prompt,
                 ),
                 name: None,
             },
@@ -78,31 +324,53 @@ impl ReActAgent {
         ));
 
         let mut retry_count = 0;
-        const MAX_RETRIES: usize = 5;
+        let mut step_count = 0;
+        let mut turn = 0;
+        // 已记录到运行轨迹的消息数，系统提示词单独记录
+        let mut recorded_messages = 1;
+        let max_retries = self.config.limits.max_retries;
 
         loop {
             // 检查重试次数是否超过限制
-            if retry_count >= MAX_RETRIES {
-                return Err(AgentError::RuntimeError(format!(
-                    "同一问题调用大模型次数已达上限({}次)，请重新描述问题或检查网络连接",
-                    MAX_RETRIES
+            if retry_count >= max_retries {
+                return Err(AgentError::RuntimeError(t!(
+                    "agent.retry_limit",
+                    count = max_retries
                 )));
             }
 
             // 请求模型
-            let content = self.call_model_stream(&current_messages).await?;
-
-            // 检测 Thought
-            if let Some(thought) = self.extract_thought(&content) {
-                println!("\n\n💭 Thought: {}", thought);
-            }
+            turn += 1;
+            self.record(TrajectoryEvent::Request {
+                turn,
+                messages: current_messages[recorded_messages..].to_vec(),
+                total_messages: current_messages.len() - 1,
+            });
+            recorded_messages = current_messages.len();
+            let started = Instant::now();
+            let output = self.call_model_stream(&current_messages).await?;
+            self.record(TrajectoryEvent::Response {
+                turn,
+                raw: output.raw.clone(),
+                thought: output.thought.clone(),
+                actions: output.actions.clone(),
+                final_answer: output.final_answer.clone(),
+                duration_ms: millis(started.elapsed()),
+            });
+            let content = output.raw.clone();
 
             // 检测模型是否输出 Final Answer - 优先检查，如果找到立即返回
-            if let Some(final_answer) = self.extract_final_answer(&content) {
-                println!("\n\n✅ 检测到最终答案，任务完成！");
-                println!("📝 最终答案内容: {}", final_answer);
+            if let Some(final_answer) = output.final_answer {
+                if self.verbose() {
+                    println!("\n\n{}", t!("agent.final_answer_detected"));
+                    println!(
+                        "{}",
+                        t!("agent.final_answer_content", answer = final_answer)
+                    );
+                }
 
                 // 更新对话历史，包含当前对话
+                #[allow(deprecated)]
                 current_messages.push(ChatCompletionRequestMessage::Assistant(
                     async_openai::types::ChatCompletionRequestAssistantMessage {
                         content: Some(
@@ -123,61 +391,51 @@ impl ReActAgent {
             }
 
             // 调试信息：显示当前内容状态
-            eprintln!("\n\n🔍 内容分析:");
-            eprintln!("   - 内容长度: {} 字符", content.chars().count());
-            eprintln!(
-                "   - 是否包含 <final_answer>: {}",
-                content.contains("<final_answer>")
-            );
-            eprintln!(
-                "   - 是否包含 </final_answer>: {}",
-                content.contains("</final_answer>")
-            );
-            eprintln!("   - 是否包含 <action>: {}", content.contains("<action>"));
-            eprintln!("   - 是否包含 </action>: {}", content.contains("</action>"));
+            if self.verbose() {
+                eprintln!("\n\n{}", t!("agent.content_analysis"));
+                eprintln!(
+                    "{}",
+                    t!("agent.content_length", length = content.chars().count())
+                );
+                for tag in ["<final_answer>", "</final_answer>", "<action>", "</action>"] {
+                    eprintln!(
+                        "{}",
+                        t!(
+                            "agent.content_contains",
+                            tag = tag,
+                            value = content.contains(tag)
+                        )
+                    );
+                }
+            }
 
             // 检测 Action - 只有在没有final_answer的情况下才检查
-            let action_result = self.extract_action(&content);
-            let action = match action_result {
-                Ok(action) => action,
+            let actions = match output.require_actions() {
+                Ok(actions) => actions,
                 Err(e) => {
                     retry_count += 1;
                     eprintln!(
-                        "\n\n⚠️  模型输出不完整，尝试重新请求... (第{}次重试，最多{}次)",
-                        retry_count, MAX_RETRIES
+                        "\n\n{}",
+                        t!(
+                            "agent.incomplete_output_retry",
+                            attempt = retry_count,
+                            max = max_retries
+                        )
                     );
-                    eprintln!("错误详情: {}", e);
-
-                    // 检查是否真的没有final_answer（双重检查）
-                    if let Some(final_answer) = self.extract_final_answer(&content) {
-                        println!("\n\n✅ 重新检查发现最终答案，任务完成！");
-
-                        // 更新对话历史，包含当前对话
-                        current_messages.push(ChatCompletionRequestMessage::Assistant(
-                            async_openai::types::ChatCompletionRequestAssistantMessage {
-                                content: Some(
-                                    async_openai::types::ChatCompletionRequestAssistantMessageContent::Text(
-                                        content,
-                                    ),
-                                ),
-                                name: None,
-                                tool_calls: None,
-                                function_call: None,
-                                audio: None,
-                                refusal: None,
-                            },
-                        ));
-                        self.update_conversation_history(current_messages);
-
-                        return Ok(final_answer);
-                    }
+                    eprintln!("{}", t!("agent.error_detail", error = e));
+                    self.record(TrajectoryEvent::Retry {
+                        turn,
+                        attempt: retry_count,
+                        error: e.to_string(),
+                    });
 
                     // 添加一个提示消息，要求模型重新输出
                     current_messages.push(ChatCompletionRequestMessage::User(
                         async_openai::types::ChatCompletionRequestUserMessage {
-                            content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
-                                format!("请重新输出完整的action标签，格式为 <action>工具名(参数)</action>。这是第{}次重试。", retry_count),
-                            ),
+                            content:
+                                async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                                    pt!("agent.prompt.retry_action", attempt = retry_count),
+                                ),
                             name: None,
                         },
                     ));
@@ -187,28 +445,31 @@ impl ReActAgent {
                 }
             };
 
-            let (tool_name, args) = self.parse_action(&action)?;
-
-            println!("\n\n🔧 Action: {}({})", tool_name, args.join(", "));
+            let calls = actions
+                .iter()
+                .map(|action| parse_action(action))
+                .collect::<AgentResult<Vec<_>>>()?;
 
-            // 只有终端命令才需要询问用户
-            if tool_name == "run_terminal_command" {
-                let mut input = String::new();
-                println!("\n\n是否继续？（Y/N）");
-                std::io::stdin().read_line(&mut input)?;
-                if input.trim().to_lowercase() != "y" {
-                    println!("\n\n操作已取消。");
-                    return Ok("操作被用户取消".to_string());
-                }
+            step_count += calls.len();
+            if step_count > self.config.limits.max_steps {
+                return Err(AgentError::RuntimeError(t!(
+                    "agent.max_steps_exceeded",
+                    steps = self.config.limits.max_steps
+                )));
             }
 
-            // 执行工具
-            let observation = match self.tools.get_tool(&tool_name) {
-                Some(tool) => tool.execute(args).await?,
-                None => format!("工具 '{}' 不存在", tool_name),
+            let Some(observations) = self.execute_actions(&calls).await? else {
+                println!("\n\n{}", t!("agent.cancelled"));
+                self.finish_trajectory(Outcome::Cancelled);
+                return Ok(t!("agent.cancelled_observation"));
             };
 
-            println!("\n\n🔍 Observation：{}", observation);
+            if self.verbose() {
+                for observation in &observations {
+                    println!("\n\n🔍 Observation: {}", observation);
+                }
+            }
+            let observation = format_observations(&actions, &observations);
 
             // 添加观察结果到消息列表
             #[allow(deprecated)]
@@ -240,6 +501,101 @@ impl ReActAgent {
         }
     }
 
+    /// 按顺序执行一轮中的动作：连续的只读工具并行执行，其他工具逐个确认并执行。
+    /// 用户取消时返回 `None`
+    async fn execute_actions(&self, calls: &[ActionCall]) -> AgentResult<Option<Vec<String>>> {
+        let mut observations = Vec::with_capacity(calls.len());
+        let mut index = 0;
+        while index < calls.len() {
+            let parallel = calls[index..]
+                .iter()
+                .take_while(|call| self.runs_in_parallel(&call.tool))
+                .count();
+            if parallel > 0 {
+                let batch = &calls[index..index + parallel];
+                if self.verbose() {
+                    for call in batch {
+                        println!("\n\n🔧 Action: {}", call);
+                    }
+                }
+                let runs =
+                    futures::future::join_all(batch.iter().map(|call| self.execute_tool(call)))
+                        .await;
+                // 按动作的顺序记录，与完成的先后无关
+                for (call, run) in batch.iter().zip(runs) {
+                    observations.push(self.observe(call, run)?);
+                }
+                index += parallel;
+                continue;
+            }
+
+            let call = &calls[index];
+            let tool_name = call.tool.as_str();
+            if self.verbose() {
+                println!("\n\n🔧 Action: {}", call);
+            }
+
+            // 只有声明了需要确认的工具（如终端命令、git提交）才询问用户
+            let needs_approval = self
+                .tools
+                .get_tool(tool_name)
+                .is_some_and(|tool| tool.requires_approval());
+            if needs_approval
+                && !self
+                    .tools
+                    .approval_for(tool_name)
+                    .confirm(&format!("\n\n{}", t!("agent.confirm_continue")))?
+            {
+                return Ok(None);
+            }
+
+            let run = self.execute_tool(call).await;
+            observations.push(self.observe(call, run)?);
+            index += 1;
+        }
+        Ok(Some(observations))
+    }
+
+    /// 只读且无需确认的工具可以与相邻的同类调用并行执行
+    fn runs_in_parallel(&self, tool_name: &str) -> bool {
+        self.tools
+            .get_tool(tool_name)
+            .is_some_and(|tool| tool.is_read_only() && !tool.requires_approval())
+    }
+
+    /// 参数无法对应到工具声明的参数时，错误信息作为观察结果返回给模型
+    async fn execute_tool(&self, call: &ActionCall) -> ToolRun {
+        let started = Instant::now();
+        let (arguments, result) = match self.tools.get_tool(&call.tool) {
+            None => (None, Ok(t!("agent.tool_not_found", name = call.tool))),
+            Some(tool) => match call.bind(&tool.parameters()) {
+                Ok(args) => (Some(args.clone()), tool.execute(args).await),
+                Err(e) => (None, Ok(e.to_string())),
+            },
+        };
+        ToolRun {
+            arguments,
+            result,
+            duration: started.elapsed(),
+        }
+    }
+
+    /// 把工具调用记录到运行轨迹，返回观察结果
+    fn observe(&self, call: &ActionCall, run: ToolRun) -> AgentResult<String> {
+        self.record(TrajectoryEvent::Tool {
+            action: call.to_string(),
+            tool: call.tool.clone(),
+            arguments: run.arguments,
+            observation: match &run.result {
+                Ok(observation) => observation.clone(),
+                Err(e) => e.to_string(),
+            },
+            failed: run.result.is_err(),
+            duration_ms: millis(run.duration),
+        });
+        run.result
+    }
+
     // 添加一个方法来更新对话历史
     fn update_conversation_history(&mut self, messages: Vec<ChatCompletionRequestMessage>) {
         // 过滤掉系统提示词，只保留对话内容
@@ -254,311 +610,247 @@ impl ReActAgent {
         self.conversation_history.len()
     }
 
+    pub fn conversation_history(&self) -> &[ChatCompletionRequestMessage] {
+        &self.conversation_history
+    }
+
+    /// 最近一个任务的运行轨迹文件，没有运行过任务或未能创建轨迹文件时为 None
+    pub fn last_trajectory(&self) -> Option<&Path> {
+        self.last_trajectory.as_deref()
+    }
+
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// 将对话历史保存为JSON文件
+    pub fn save_conversation(&self, path: &Path) -> AgentResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.conversation_history)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// 从JSON文件加载对话历史，替换当前的对话
+    pub fn load_conversation(&mut self, path: &Path) -> AgentResult<()> {
+        let content = std::fs::read_to_string(path)?;
+        let messages: Vec<ChatCompletionRequestMessage> = serde_json::from_str(&content)?;
+        self.update_conversation_history(messages);
+        Ok(())
+    }
+
     // 添加一个方法来清除对话历史
     pub fn clear_conversation_history(&mut self) {
         self.conversation_history.clear();
     }
 
-    fn render_system_prompt(&self) -> AgentResult<String> {
+    /// 撤销最近一个任务对文件的修改
+    pub fn undo_last_task(&self) -> AgentResult<String> {
+        let checkpoints = self
+            .tools
+            .checkpoints()
+            .ok_or_else(|| AgentError::RuntimeError(t!("agent.checkpoints_disabled")))?;
+
+        let checkpoint = checkpoints.undo_last()?;
+        let mut message = t!(
+            "agent.undo_done",
+            id = checkpoint.id,
+            task = checkpoint.task,
+            count = checkpoint.changes().len()
+        );
+        for (path, kind) in checkpoint.changes() {
+            message.push('\n');
+            message.push_str(&t!(
+                "agent.undo_change",
+                kind = kind.label(),
+                path = path.display()
+            ));
+        }
+        if !checkpoint.untracked_changes().is_empty() {
+            message.push('\n');
+            message.push_str(&t!(
+                "agent.undo_untracked",
+                count = checkpoint.untracked_changes().len()
+            ));
+            for path in checkpoint.untracked_changes() {
+                message.push_str(&format!("\n  {}", path.display()));
+            }
+        }
+        Ok(message)
+    }
+
+    /// 已完成任务的检查点，从旧到新排列
+    pub fn list_checkpoints(&self) -> Vec<Checkpoint> {
+        self.tools
+            .checkpoints()
+            .map(|c| c.list())
+            .unwrap_or_default()
+    }
+
+    /// 渲染系统提示词，`task` 用于挑选相关的项目记忆
+    fn render_system_prompt(&self, task: &str) -> AgentResult<String> {
         let tool_list = self.tools.get_tool_list();
         let operating_system = self.get_operating_system_name();
         let file_list = self.get_file_list()?;
 
-        Ok(self
-            .prompt_renderer
-            .render(&tool_list, &operating_system, &file_list))
+        let tools = self
+            .tools
+            .tools()
+            .into_iter()
+            .map(|tool| ToolInfo {
+                name: tool.name().to_string(),
+                signature: tool_signature(tool),
+                description: tool.description().to_string(),
+            })
+            .collect();
+
+        let project_directory = Path::new(&self.project_directory);
+        let project = ProjectContext::collect(project_directory, &self.config.context);
+        let memories = self
+            .tools
+            .memory()
+            .map(|memory| memory.relevant(task, self.config.context.max_memories))
+            .unwrap_or_default()
+            .iter()
+            .map(format_memory)
+            .collect();
+
+        self.prompt_renderer.render(&PromptContext {
+            tools,
+            tool_list,
+            operating_system,
+            file_list,
+            file_tree: project.file_tree,
+            instructions: project.instructions,
+            project_types: project.project_types,
+            project_directory: self.project_directory.clone(),
+            date: today(),
+            git_branch: current_branch(project_directory),
+            max_actions: self.config.limits.max_actions_per_turn,
+            memories,
+        })
     }
 
     async fn call_model_stream(
         &self,
         messages: &[ChatCompletionRequestMessage],
-    ) -> AgentResult<String> {
-        println!("\n\n正在请求模型，请稍等...");
+    ) -> AgentResult<ReactOutput> {
+        if self.verbose() {
+            println!("\n\n{}", t!("agent.requesting_model"));
+        }
+
+        let mut parser = ReactParser::with_max_actions(self.config.limits.max_actions_per_turn);
+        match &self.recorded_model {
+            // 回放时一次输入录制的完整输出，不请求API
+            Some(recorded) => self.feed_parser(&mut parser, &recorded.next_response()?)?,
+            None => self.stream_model(messages, &mut parser).await?,
+        }
+
+        if self.verbose() {
+            println!(); // 换行
+        }
+
+        let output = parser.finish();
+        // 子Agent的原始输出不显示在父Agent的终端中
+        if self.verbose() && output.actions.is_empty() && output.final_answer.is_none() {
+            eprintln!(
+                "\n\n{}",
+                t!("agent.incomplete_output_warning", content = output.raw)
+            );
+        }
 
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(&self.model)
+        Ok(output)
+    }
+
+    /// 流式请求模型，把输出交给解析器，动作完整后不再等待后续输出
+    async fn stream_model(
+        &self,
+        messages: &[ChatCompletionRequestMessage],
+        parser: &mut ReactParser,
+    ) -> AgentResult<()> {
+        let model = &self.config.model;
+        let mut request = CreateChatCompletionRequestArgs::default();
+        request
+            .model(&model.name)
             .messages(messages.to_vec())
-            .stream(true)
+            .stream(true);
+        if let Some(temperature) = model.temperature {
+            request.temperature(temperature as f32);
+        }
+        if let Some(top_p) = model.top_p {
+            request.top_p(top_p as f32);
+        }
+        if let Some(max_tokens) = model.max_tokens {
+            request.max_completion_tokens(max_tokens);
+        }
+        let request = request
             .build()
-            .map_err(|e| AgentError::RuntimeError(format!("构建请求失败: {}", e)))?;
+            .map_err(|e| AgentError::RuntimeError(t!("agent.build_request_failed", error = e)))?;
 
         let mut stream = self
             .client
             .chat()
             .create_stream(request)
             .await
-            .map_err(|e| AgentError::RuntimeError(format!("API调用失败: {}", e)))?;
-
-        let mut content = String::new();
-        let mut buffer = String::new();
+            .map_err(|e| AgentError::ApiError(e.to_string()))?;
 
         while let Some(result) = stream.next().await {
             match result {
                 Ok(chunk) => {
-                    if let Some(choice) = chunk.choices.first() {
-                        let delta = &choice.delta;
-                        if let Some(text) = &delta.content {
-                            // 流式输出文本
-                            print!("{}", text);
-                            std::io::stdout().flush().map_err(|e| {
-                                AgentError::RuntimeError(format!("输出刷新失败: {}", e))
-                            })?;
-
-                            content.push_str(text);
-                            buffer.push_str(text);
-
-                            // 检测是否包含完整的标签
-                            if self.should_process_buffer(&buffer) {
-                                // 如果缓冲区包含完整的标签，处理它
-                                if let Some(thought) = self.extract_thought(&buffer) {
-                                    println!("\n\n💭 Thought: {}", thought);
-                                    buffer.clear();
-                                }
-
-                                // 如果检测到完整的 action 标签，等待更多内容确保完整性
-                                if buffer.contains("</action>") {
-                                    // 等待一小段时间，确保内容完整
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(100))
-                                        .await;
-
-                                    // 检查是否已经获得了完整的action内容
-                                    if self.has_complete_action(&content) {
-                                        break;
-                                    }
-                                }
-                            }
+                    if let Some(text) = chunk
+                        .choices
+                        .first()
+                        .and_then(|choice| choice.delta.content.as_ref())
+                    {
+                        self.feed_parser(parser, text)?;
+                        // 动作已完整，不再等待后续输出
+                        if parser.is_finished() {
+                            break;
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("\n\n流式输出错误: {}", e);
+                    eprintln!("\n\n{}", t!("agent.stream_error", error = e));
                     break;
                 }
             }
         }
-
-        println!(); // 换行
-
-        // 调试信息：显示最终内容
-        if !self.has_complete_action(&content) {
-            eprintln!("\n\n⚠️  警告：模型输出可能不完整，内容：{}", content);
-        }
-
-        Ok(content)
-    }
-
-    fn should_process_buffer(&self, buffer: &str) -> bool {
-        // 检查缓冲区是否包含完整的标签
-        // 使用更智能的检测逻辑
-        let has_complete_thought = buffer.contains("<thought>") && buffer.contains("</thought>");
-        let has_complete_action = buffer.contains("<action>") && buffer.contains("</action>");
-        let has_complete_final_answer =
-            buffer.contains("<final_answer>") && buffer.contains("</final_answer>");
-
-        has_complete_thought || has_complete_action || has_complete_final_answer
-    }
-
-    fn extract_thought(&self, content: &str) -> Option<String> {
-        let re = Regex::new(r"<thought>(.*?)</thought>").ok()?;
-        re.captures(content).map(|cap| cap[1].trim().to_string())
-    }
-
-    fn extract_final_answer(&self, content: &str) -> Option<String> {
-        let re = Regex::new(r"<final_answer>(.*?)</final_answer>").ok()?;
-        re.captures(content).map(|cap| cap[1].trim().to_string())
-    }
-
-    fn extract_action(&self, content: &str) -> AgentResult<String> {
-        // 调试信息：显示正在解析的内容
-        eprintln!(
-            "\n\n🔍 正在解析内容中的action标签，内容长度: {}",
-            content.len()
-        );
-
-        // 使用chars()来正确处理UTF-8字符边界
-        let char_count = content.chars().count();
-        let preview_length = if char_count > 100 { 100 } else { char_count };
-        let preview: String = content.chars().take(preview_length).collect();
-
-        eprintln!("🔍 内容预览 (前{}个字符): {}", preview_length, preview);
-
-        let re = Regex::new(r"<action>(.*?)</action>")
-            .map_err(|_| AgentError::ParseError("无法编译正则表达式".to_string()))?;
-
-        match re.captures(content) {
-            Some(cap) => {
-                let action = cap[1].trim().to_string();
-                eprintln!("✅ 成功提取action: {}", action);
-                Ok(action)
-            }
-            None => {
-                // 尝试查找部分匹配的标签
-                let action_start = content.find("<action>");
-                let action_end = content.find("</action>");
-
-                eprintln!("❌ 未找到完整的action标签");
-                eprintln!("   <action> 位置: {:?}", action_start);
-                eprintln!("   </action> 位置: {:?}", action_end);
-
-                if let Some(start) = action_start {
-                    // 安全地处理字符串切片，避免在UTF-8字符中间截断
-                    let remaining = if start < content.len() {
-                        // 将字节索引转换为字符索引
-                        let char_indices: Vec<(usize, char)> = content.char_indices().collect();
-                        let char_start = char_indices
-                            .iter()
-                            .position(|(byte_pos, _)| *byte_pos >= start)
-                            .unwrap_or(0);
-
-                        if char_start < char_indices.len() {
-                            let (byte_pos, _) = char_indices[char_start];
-                            // 安全地截取字符串，避免在UTF-8字符中间截断
-                            if byte_pos < content.len() {
-                                let chars: Vec<char> = content.chars().collect();
-                                let char_start = content
-                                    .char_indices()
-                                    .position(|(pos, _)| pos >= byte_pos)
-                                    .unwrap_or(0);
-                                if char_start < chars.len() {
-                                    // 安全地截取字符串，避免在UTF-8字符中间截断
-                                    let remaining_chars: String =
-                                        chars.into_iter().skip(char_start).collect();
-                                    remaining_chars
-                                } else {
-                                    "".to_string()
-                                }
-                            } else {
-                                "".to_string()
-                            }
-                        } else {
-                            "".to_string()
-                        }
-                    } else {
-                        "".to_string()
-                    };
-
-                    // 安全地截取前100个字符
-                    let remaining_chars: Vec<char> = remaining.chars().collect();
-                    let safe_length = if remaining_chars.len() > 100 {
-                        100
-                    } else {
-                        remaining_chars.len()
-                    };
-                    let safe_preview: String =
-                        remaining_chars.into_iter().take(safe_length).collect();
-                    eprintln!("   从 <action> 开始的内容: {}", safe_preview);
-                }
-
-                Err(AgentError::ParseError(format!(
-                    "模型未输出 <action> 标签。内容长度: {} 字符, 是否包含开始标签: {}, 是否包含结束标签: {}",
-                    char_count,
-                    action_start.is_some(),
-                    action_end.is_some()
-                )))
-            }
-        }
+        Ok(())
     }
 
-    fn parse_action(&self, action_str: &str) -> AgentResult<(String, Vec<String>)> {
-        let re = Regex::new(r"(\w+)\((.*)\)")
-            .map_err(|_| AgentError::ParseError("无法编译函数调用正则表达式".to_string()))?;
-
-        let captures = re
-            .captures(action_str)
-            .ok_or_else(|| AgentError::ParseError("无效的函数调用语法".to_string()))?;
-
-        let func_name = captures[1].to_string();
-        let args_str = captures[2].trim();
-
-        let args = self.parse_arguments(args_str)?;
-
-        Ok((func_name, args))
-    }
-
-    fn parse_arguments(&self, args_str: &str) -> AgentResult<Vec<String>> {
-        let mut args = Vec::new();
-        let mut current_arg = String::new();
-        let mut in_string = false;
-        let mut string_char = None;
-        let mut paren_depth = 0;
-        let mut i = 0;
-        let chars: Vec<char> = args_str.chars().collect();
-
-        while i < chars.len() {
-            let char = chars[i];
-
-            if !in_string {
-                if char == '"' || char == '\'' {
-                    in_string = true;
-                    string_char = Some(char);
-                    current_arg.push(char);
-                } else if char == '(' {
-                    paren_depth += 1;
-                    current_arg.push(char);
-                } else if char == ')' {
-                    paren_depth -= 1;
-                    current_arg.push(char);
-                } else if char == ',' && paren_depth == 0 {
-                    // 遇到顶层逗号，结束当前参数
-                    args.push(self.parse_single_arg(&current_arg.trim())?);
-                    current_arg.clear();
-                } else {
-                    current_arg.push(char);
+    /// 把一段模型输出交给解析器，顶层Agent同时输出文本和闭合的思考
+    fn feed_parser(&self, parser: &mut ReactParser, text: &str) -> AgentResult<()> {
+        let accepted = parser.raw().len();
+        let events = parser.feed(text);
+
+        if self.verbose() {
+            // 流式输出文本，`</action>` 之后的内容已被丢弃
+            print!("{}", &parser.raw()[accepted..]);
+            std::io::stdout()
+                .flush()
+                .map_err(|e| AgentError::RuntimeError(t!("agent.flush_failed", error = e)))?;
+
+            for event in events {
+                if let ParseEvent::Close {
+                    tag: ReactTag::Thought,
+                    content,
+                } = event
+                {
+                    println!("\n\n💭 Thought: {}", content.trim());
                 }
-            } else {
-                current_arg.push(char);
-                if char == string_char.unwrap() && (i == 0 || chars[i - 1] != '\\') {
-                    in_string = false;
-                    string_char = None;
-                }
-            }
-
-            i += 1;
-        }
-
-        // 添加最后一个参数
-        if !current_arg.trim().is_empty() {
-            args.push(self.parse_single_arg(&current_arg.trim())?);
-        }
-
-        Ok(args)
-    }
-
-    fn parse_single_arg(&self, arg_str: &str) -> AgentResult<String> {
-        let arg_str = arg_str.trim();
-
-        // 如果是字符串字面量
-        if (arg_str.starts_with('"') && arg_str.ends_with('"'))
-            || (arg_str.starts_with('\'') && arg_str.ends_with('\''))
-        {
-            // 安全地处理字符串切片，避免在UTF-8字符中间截断
-            let chars: Vec<char> = arg_str.chars().collect();
-            if chars.len() >= 2 {
-                let inner_chars: String = chars[1..chars.len() - 1].iter().collect();
-                // 处理常见的转义字符
-                let processed = inner_chars
-                    .replace("\\\"", "\"")
-                    .replace("\\'", "'")
-                    .replace("\\n", "\n")
-                    .replace("\\t", "\t")
-                    .replace("\\r", "\r")
-                    .replace("\\\\", "\\");
-                Ok(processed)
-            } else {
-                Ok("".to_string())
             }
-        } else {
-            // 如果不是字符串，直接返回
-            Ok(arg_str.to_string())
         }
+        Ok(())
     }
 
     fn get_file_list(&self) -> AgentResult<String> {
         let path = Path::new(&self.project_directory);
         if !path.exists() || !path.is_dir() {
-            return Err(AgentError::RuntimeError(
-                "项目目录不存在或不是目录".to_string(),
-            ));
+            return Err(AgentError::RuntimeError(t!(
+                "agent.project_directory_invalid"
+            )));
         }
 
         let entries = std::fs::read_dir(path)?;
@@ -585,62 +877,430 @@ impl ReActAgent {
             _ => "Unknown".to_string(),
         }
     }
+}
 
-    fn has_complete_action(&self, content: &str) -> bool {
-        // 检查是否有完整的 <action>...</action> 标签对
-        let action_start = content.find("<action>");
-        let action_end = content.find("</action>");
-
-        if let (Some(start), Some(end)) = (action_start, action_end) {
-            // 确保结束标签在开始标签之后
-            end > start
-        } else {
-            false
-        }
+/// 单个动作的观察结果原样返回；多个动作的结果按顺序编号，并附上对应的动作
+fn format_observations(actions: &[String], observations: &[String]) -> String {
+    if let [observation] = observations {
+        return observation.clone();
     }
+    actions
+        .iter()
+        .zip(observations)
+        .enumerate()
+        .map(|(index, (action, observation))| {
+            format!("[{}] {}\n{}", index + 1, action, observation)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::i18n::{text_in, with_prompt_lang, Lang};
+    use crate::replay::ReplayReport;
+    use crate::tools::create_configured_tools;
+    use crate::tools::Tool;
+    use crate::trajectory::Trajectory;
+    use std::sync::{Arc, Mutex};
+
+    fn test_agent() -> ReActAgent {
+        test_agent_with(ToolRegistry::new())
+    }
+
+    fn test_agent_with(tools: ToolRegistry) -> ReActAgent {
+        ReActAgent::new(tools, test_config(), "/tmp".to_string()).unwrap()
+    }
+
+    fn test_config() -> AgentConfig {
+        // 测试不访问网络，只需要占位的连接配置让构造通过
+        let mut config = AgentConfig::default();
+        config.provider.api_key = Some("test-key".to_string());
+        config.provider.api_base = Some("http://localhost".to_string());
+        config.model.name = "test-model".to_string();
+        config
+    }
+
+    /// 记录开始和结束顺序的工具
+    struct RecordingTool {
+        name: &'static str,
+        read_only: bool,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for RecordingTool {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            ""
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+
+        async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+            let arg = args.join(",");
+            self.log.lock().unwrap().push(format!("start {}", arg));
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.log.lock().unwrap().push(format!("end {}", arg));
+            Ok(format!("{}({})", self.name, arg))
+        }
+    }
 
     #[test]
-    fn test_has_complete_action() {
-        let agent = ReActAgent::new(
-            ToolRegistry::new(),
-            "test-model".to_string(),
-            "/tmp".to_string(),
-        )
-        .unwrap();
+    fn test_switch_profile_keeps_history() {
+        let mut config = AgentConfig::default();
+        config.provider.api_key = Some("test-key".to_string());
+        config.provider.api_base = Some("http://localhost".to_string());
+        config.profiles.insert(
+            "strong".to_string(),
+            toml::from_str("model = \"strong-model\"\nmax_tokens = 4096").unwrap(),
+        );
+        let mut agent = ReActAgent::new(ToolRegistry::new(), config, "/tmp".to_string()).unwrap();
+        agent.update_conversation_history(vec![ChatCompletionRequestMessage::User(
+            async_openai::types::ChatCompletionRequestUserMessage {
+                content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                    "<question>你好</question>".to_string(),
+                ),
+                name: None,
+            },
+        )]);
 
-        // 测试完整的action标签
-        assert!(agent.has_complete_action("<action>read_file(\"test.txt\")</action>"));
+        agent.switch_profile("strong").unwrap();
+        assert_eq!(agent.config().model.name, "strong-model");
+        assert_eq!(agent.config().profile.as_deref(), Some("strong"));
+        assert_eq!(agent.get_conversation_length(), 1);
 
-        // 测试不完整的action标签
-        assert!(!agent.has_complete_action("<action>read_file(\"test.txt\")"));
-        assert!(!agent.has_complete_action("read_file(\"test.txt\")</action>"));
-        assert!(!agent.has_complete_action(""));
+        assert!(agent.switch_profile("missing").is_err());
+        assert_eq!(agent.config().model.name, "strong-model");
+    }
 
-        // 测试标签顺序错误
-        assert!(!agent.has_complete_action("</action>read_file(\"test.txt\")<action>"));
+    #[test]
+    fn test_save_and_load_conversation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions/default.json");
+        let mut agent = test_agent();
+        agent.update_conversation_history(vec![ChatCompletionRequestMessage::User(
+            async_openai::types::ChatCompletionRequestUserMessage {
+                content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                    "<question>你好</question>".to_string(),
+                ),
+                name: None,
+            },
+        )]);
+        agent.save_conversation(&path).unwrap();
+
+        let mut restored = test_agent();
+        restored.load_conversation(&path).unwrap();
+        assert_eq!(
+            restored.conversation_history(),
+            agent.conversation_history()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only_actions_run_concurrently_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut tools = ToolRegistry::new();
+        for (name, read_only) in [("read", true), ("write", false)] {
+            tools.register(RecordingTool {
+                name,
+                read_only,
+                log: Arc::clone(&log),
+            });
+        }
+        let agent = test_agent_with(tools);
+
+        let call = |name: &str, arg: &str| parse_action(&format!("{}({})", name, arg)).unwrap();
+        let calls = vec![
+            call("read", "a"),
+            call("read", "b"),
+            call("write", "c"),
+            call("read", "d"),
+        ];
+        let observations = agent.execute_actions(&calls).await.unwrap().unwrap();
+        assert_eq!(
+            observations,
+            vec!["read(a)", "read(b)", "write(c)", "read(d)"]
+        );
+        // a 和 b 同时开始；写操作在它们结束后才开始，d 在写操作结束后才开始
+        let log = log.lock().unwrap();
+        assert_eq!(&log[..2], ["start a", "start b"]);
+        assert_eq!(&log[4..], ["start c", "end c", "start d", "end d"]);
+
+        let actions = vec!["read(\"a\")".to_string(), "read(\"b\")".to_string()];
+        let batch = format_observations(&actions, &observations[..2]);
+        assert_eq!(
+            batch,
+            "[1] read(\"a\")\nread(a)\n\n[2] read(\"b\")\nread(b)"
+        );
+        assert_eq!(
+            format_observations(&actions[..1], &observations[..1]),
+            "read(a)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_recorded_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut tools = ToolRegistry::new();
+        tools.register(RecordingTool {
+            name: "read",
+            read_only: true,
+            log,
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let mut agent = test_agent_with(tools);
+        agent.trajectory = Some(TrajectoryRecorder::create(dir.path()).unwrap());
+
+        let calls = vec![
+            parse_action("read(\"a\")").unwrap(),
+            parse_action("read(\"b\")").unwrap(),
+            parse_action("missing()").unwrap(),
+        ];
+        agent.execute_actions(&calls).await.unwrap().unwrap();
+        agent.finish_trajectory(Outcome::Cancelled);
+        assert!(agent.trajectory.is_none());
+
+        let path = crate::trajectory::Trajectory::list(dir.path()).remove(0);
+        let trajectory = crate::trajectory::Trajectory::load(&path).unwrap();
+        let tools: Vec<(String, Option<Vec<String>>)> = trajectory
+            .entries
+            .iter()
+            .filter_map(|entry| match &entry.event {
+                TrajectoryEvent::Tool {
+                    action, arguments, ..
+                } => Some((action.clone(), arguments.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            tools,
+            vec![
+                ("read(a)".to_string(), Some(vec!["a".to_string()])),
+                ("read(b)".to_string(), Some(vec!["b".to_string()])),
+                ("missing()".to_string(), None),
+            ]
+        );
+        assert_eq!(trajectory.outcome(), Some(&Outcome::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_replay_recorded_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().to_string_lossy().to_string();
+        let replay_agent = |recorded: RecordedModel| {
+            let mut tools = ToolRegistry::new();
+            tools.register(RecordingTool {
+                name: "read",
+                read_only: true,
+                log: Arc::new(Mutex::new(Vec::new())),
+            });
+            ReActAgent::new(tools, test_config(), project.clone())
+                .unwrap()
+                .with_recorded_model(recorded)
+        };
+
+        let mut agent = replay_agent(RecordedModel::new(vec![
+            "<thought>先读文件</thought><action>read(\"a\")</action>".to_string(),
+            "<final_answer>完成</final_answer>".to_string(),
+        ]));
+        assert_eq!(agent.run("读取 a").await.unwrap(), "完成");
+        let recorded = Trajectory::load(agent.last_trajectory().unwrap()).unwrap();
+        assert_eq!(recorded.task(), Some("读取 a"));
+
+        let mut replay = replay_agent(RecordedModel::from_trajectory(&recorded));
+        replay.run("读取 a").await.unwrap();
+        assert_ne!(replay.last_trajectory(), agent.last_trajectory());
+        let replayed = Trajectory::load(replay.last_trajectory().unwrap()).unwrap();
+        assert!(ReplayReport::compare(&recorded, &replayed, str::to_string).passed());
+
+        // 录制的回复用完后任务失败
+        assert!(replay.run("再读一次").await.is_err());
+        let failed = Trajectory::load(replay.last_trajectory().unwrap()).unwrap();
+        assert!(matches!(failed.outcome(), Some(Outcome::Failed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_replay_git_tools_in_fixture() {
+        let source = tempfile::tempdir().unwrap();
+        let fixture = tempfile::tempdir().unwrap();
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(source.path())
+            .args(["init", "-q"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::write(source.path().join("a.txt"), "a").unwrap();
+
+        let run = |directory: &Path, recorded: RecordedModel| {
+            let directory = directory.to_string_lossy().to_string();
+            let mut tools = ToolRegistry::new();
+            tools.register(crate::tools::GitStatusTool::new(directory.clone()));
+            ReActAgent::new(tools, test_config(), directory)
+                .unwrap()
+                .with_recorded_model(recorded)
+        };
+
+        let mut agent = run(
+            source.path(),
+            RecordedModel::new(vec![
+                "<action>git_status()</action>".to_string(),
+                "<final_answer>完成</final_answer>".to_string(),
+            ]),
+        );
+        agent.run("查看状态").await.unwrap();
+        let recorded = Trajectory::load(agent.last_trajectory().unwrap()).unwrap();
+
+        // 副本带有版本库，git工具的观察结果与录制时相同
+        crate::replay::copy_fixture(source.path(), fixture.path()).unwrap();
+        let mut replay = run(fixture.path(), RecordedModel::from_trajectory(&recorded));
+        replay.run("查看状态").await.unwrap();
+        let replayed = Trajectory::load(replay.last_trajectory().unwrap()).unwrap();
+        let (from, to) = (
+            fixture.path().to_string_lossy().to_string(),
+            source.path().to_string_lossy().to_string(),
+        );
+        let report = ReplayReport::compare(&recorded, &replayed, |text| text.replace(&from, &to));
+        assert!(report.passed(), "{}", report.render());
+    }
+
+    #[tokio::test]
+    async fn test_draft_plan_only_offers_read_only_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut tools = ToolRegistry::new();
+        for (name, read_only) in [("read", true), ("write", false)] {
+            tools.register(RecordingTool {
+                name,
+                read_only,
+                log: Arc::clone(&log),
+            });
+        }
+        let mut agent = ReActAgent::new(tools, test_config(), dir.path().display().to_string())
+            .unwrap()
+            .with_recorded_model(RecordedModel::new(vec![
+                "<thought>先改文件</thought><action>write(\"a\")</action>".to_string(),
+                "<thought>再读文件</thought><action>read(\"a\")</action>".to_string(),
+                "<final_answer>1. 阅读代码\n2. 修改代码</final_answer>".to_string(),
+            ]));
+
+        let steps = agent.draft_plan("修改 a").await.unwrap();
+        assert_eq!(steps, vec!["阅读代码", "修改代码"]);
+        assert_eq!(*log.lock().unwrap(), vec!["start a", "end a"]);
+
+        let trajectory = Trajectory::load(agent.last_trajectory().unwrap()).unwrap();
+        let observations: Vec<&str> = trajectory
+            .entries
+            .iter()
+            .filter_map(|entry| match &entry.event {
+                TrajectoryEvent::Tool { observation, .. } => Some(observation.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            observations,
+            vec![
+                t!("agent.tool_not_found", name = "write"),
+                "read(a)".to_string()
+            ]
+        );
+        // 计划制定完成后恢复完整的工具集
+        assert!(agent.tools().get_tool("write").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_git_commit_is_not_executed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(path)
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        std::fs::write(path.join("a.txt"), "a").unwrap();
+
+        let mut tools = ToolRegistry::new();
+        tools.register(crate::tools::GitCommitTool::new(
+            path.to_string_lossy().to_string(),
+        ));
+        tools.set_approval(crate::ApprovalPolicy::Reject.into());
+        let agent = test_agent_with(tools);
+
+        let calls = vec![parse_action("git_commit(\"提交\", \"a.txt\")").unwrap()];
+        assert!(agent.execute_actions(&calls).await.unwrap().is_none());
+        // 用户拒绝后文件没有被暂存或提交
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["status", "--porcelain"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "?? a.txt\n");
     }
 
     #[test]
-    fn test_extract_action() {
-        let agent = ReActAgent::new(
+    fn test_delegate_respects_depth_limit() {
+        let agent = test_agent();
+        assert!(agent.tools().get_tool(DELEGATE_TOOL).is_some());
+        assert!(agent
+            .tools()
+            .get_tool(DELEGATE_TOOL)
+            .unwrap()
+            .is_read_only());
+
+        // 已达到最大嵌套层数的子Agent不能继续委派
+        let config = agent.config().clone();
+        let child = ReActAgent::with_depth(
             ToolRegistry::new(),
-            "test-model".to_string(),
+            config.clone(),
             "/tmp".to_string(),
+            1,
+            DelegateSlots::new(1),
         )
         .unwrap();
+        assert!(child.tools().get_tool(DELEGATE_TOOL).is_none());
 
-        // 测试成功提取
-        let result = agent.extract_action("<action>read_file(\"test.txt\")</action>");
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "read_file(\"test.txt\")");
+        let mut disabled = config;
+        disabled.tools.disabled = vec![DELEGATE_TOOL.to_string()];
+        let agent = ReActAgent::new(ToolRegistry::new(), disabled, "/tmp".to_string()).unwrap();
+        assert!(agent.tools().get_tool(DELEGATE_TOOL).is_none());
+    }
 
-        // 测试失败提取
-        let result = agent.extract_action("没有action标签");
-        assert!(result.is_err());
+    #[test]
+    fn test_english_system_prompt_contains_no_chinese() {
+        let project = tempfile::tempdir().unwrap();
+        let directory = project.path().display().to_string();
+        let mut config = test_config();
+        config.prompt.language = Some(Lang::En);
+
+        let prompt = with_prompt_lang(Lang::En, || {
+            let tools = create_configured_tools(directory.clone(), &config);
+            let agent = ReActAgent::new(tools, config, directory).unwrap();
+            assert!(agent.tools().get_tool(DELEGATE_TOOL).is_some());
+            agent.render_system_prompt("task").unwrap()
+        });
+
+        assert!(prompt.contains(text_in(Lang::En, "tools.prompt.git_status")));
+        // 中日韩统一表意文字、中文标点和全角字符
+        let is_cjk = |c: &char| {
+            ('\u{4e00}'..='\u{9fff}').contains(c)
+                || ('\u{3000}'..='\u{303f}').contains(c)
+                || ('\u{ff00}'..='\u{ffef}').contains(c)
+        };
+        let cjk: String = prompt.chars().filter(is_cjk).collect();
+        assert!(cjk.is_empty(), "{}\n{}", cjk, prompt);
     }
 }
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// 需要用户确认的操作（执行命令、创建父目录、写入修改等）如何处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ApprovalPolicy {
+    /// 在终端询问用户
+    #[default]
+    #[serde(rename = "ask")]
+    Interactive,
+    /// 全部自动同意
+    #[serde(rename = "auto")]
+    AutoApprove,
+    /// 全部自动拒绝
+    #[serde(rename = "reject")]
+    Reject,
+}
+
+impl ApprovalPolicy {
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Self::Interactive)
+    }
+
+    /// 询问用户是否同意，非交互模式下直接按策略返回，不读取stdin
+    pub fn confirm(&self, question: &str) -> io::Result<bool> {
+        match self {
+            Self::AutoApprove => Ok(true),
+            Self::Reject => Ok(false),
+            Self::Interactive => {
+                print!("{}(Y/N): ", question);
+                io::stdout().flush()?;
+
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                Ok(input.trim().to_lowercase() == "y")
+            }
+        }
+    }
+
+    /// 向用户显示提示信息；非交互模式下stdout可能被协议占用，改写到stderr
+    pub fn notify(&self, message: &str) {
+        if self.is_interactive() {
+            println!("{}", message);
+        } else {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+/// 审批规则：默认策略加上按工具名单独指定的策略
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApprovalRules {
+    pub default: ApprovalPolicy,
+    pub tools: BTreeMap<String, ApprovalPolicy>,
+}
+
+impl ApprovalRules {
+    pub fn policy_for(&self, tool_name: &str) -> ApprovalPolicy {
+        self.tools.get(tool_name).copied().unwrap_or(self.default)
+    }
+
+    /// 是否有任何需要在终端询问的规则
+    pub fn is_interactive(&self) -> bool {
+        self.default.is_interactive() || self.tools.values().any(|p| p.is_interactive())
+    }
+
+    /// 将所有需要询问的规则替换为给定策略，用于无法交互的运行方式
+    pub fn without_prompts(mut self, fallback: ApprovalPolicy) -> Self {
+        for policy in std::iter::once(&mut self.default).chain(self.tools.values_mut()) {
+            if policy.is_interactive() {
+                *policy = fallback;
+            }
+        }
+        self
+    }
+}
+
+impl From<ApprovalPolicy> for ApprovalRules {
+    fn from(default: ApprovalPolicy) -> Self {
+        Self {
+            default,
+            tools: BTreeMap::new(),
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use crate::errors::{AgentError, AgentResult};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// 扫描项目时跳过的目录，这些目录通常很大且与任务修改无关
+const IGNORED_DIRECTORIES: &[&str] = &[".git", "target", "node_modules", ".rust-agent"];
+/// 扫描文件数上限，超过后不再检测终端命令造成的修改
+const MAX_FINGERPRINT_FILES: usize = 20_000;
+
+/// 文件在任务中的变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn label(&self) -> &'static str {
+        crate::i18n::text(match self {
+            ChangeKind::Created => "checkpoint.created",
+            ChangeKind::Modified => "checkpoint.modified",
+            ChangeKind::Deleted => "checkpoint.deleted",
+        })
+    }
+}
+
+/// 路径在任务首次修改它之前的状态
+#[derive(Debug, Clone)]
+enum OriginalState {
+    Missing,
+    Directory,
+    File(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+struct FileSnapshot {
+    path: PathBuf,
+    original: OriginalState,
+}
+
+impl FileSnapshot {
+    /// 对比当前磁盘状态，返回变更类型；没有变化时返回 None
+    fn change_kind(&self) -> Option<ChangeKind> {
+        let exists = self.path.exists();
+        match &self.original {
+            OriginalState::Missing if exists => Some(ChangeKind::Created),
+            OriginalState::Missing => None,
+            OriginalState::Directory if !exists => Some(ChangeKind::Deleted),
+            OriginalState::Directory => None,
+            OriginalState::File(_) if !exists => Some(ChangeKind::Deleted),
+            OriginalState::File(content) => match fs::read(&self.path) {
+                Ok(current) if &current == content => None,
+                _ => Some(ChangeKind::Modified),
+            },
+        }
+    }
+
+    fn restore(&self) -> std::io::Result<()> {
+        match &self.original {
+            OriginalState::Missing => {
+                if self.path.is_dir() {
+                    // 只删除空目录，避免误删用户在目录中放入的其他文件
+                    if fs::read_dir(&self.path)?.next().is_none() {
+                        fs::remove_dir(&self.path)?;
+                    }
+                } else if self.path.exists() {
+                    fs::remove_file(&self.path)?;
+                }
+            }
+            OriginalState::Directory => fs::create_dir_all(&self.path)?,
+            OriginalState::File(content) => {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&self.path, content)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 文件的轻量指纹，用于发现工具之外的修改
+#[derive(Debug, Clone, PartialEq)]
+struct FileFingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// 一次任务的检查点
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub id: usize,
+    pub task: String,
+    pub created_at: SystemTime,
+    snapshots: Vec<FileSnapshot>,
+    fingerprint: Option<BTreeMap<PathBuf, FileFingerprint>>,
+    changes: Vec<(PathBuf, ChangeKind)>,
+    untracked_changes: Vec<PathBuf>,
+}
+
+impl Checkpoint {
+    /// 任务结束时通过工具产生的文件变更
+    pub fn changes(&self) -> &[(PathBuf, ChangeKind)] {
+        &self.changes
+    }
+
+    /// 工具之外（例如终端命令）产生的、无法撤销的变更
+    pub fn untracked_changes(&self) -> &[PathBuf] {
+        &self.untracked_changes
+    }
+
+    pub fn summary(&self) -> String {
+        let mut lines = vec![crate::t!(
+            "checkpoint.summary",
+            id = self.id,
+            task = self.task,
+            count = self.changes.len()
+        )];
+        lines.extend(
+            self.changes
+                .iter()
+                .map(|(path, kind)| format!("  {}  {}", kind.label(), path.display())),
+        );
+        if !self.untracked_changes.is_empty() {
+            lines.push(crate::t!(
+                "checkpoint.untracked",
+                count = self.untracked_changes.len()
+            ));
+            lines.extend(
+                self.untracked_changes
+                    .iter()
+                    .map(|path| format!("    {}", path.display())),
+            );
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug)]
+struct CheckpointState {
+    project_directory: PathBuf,
+    next_id: usize,
+    active: Option<Checkpoint>,
+    history: Vec<Checkpoint>,
+}
+
+/// 任务检查点管理器，可在工具和Agent之间共享
+#[derive(Debug, Clone)]
+pub struct CheckpointManager {
+    state: Arc<Mutex<CheckpointState>>,
+}
+
+impl CheckpointManager {
+    pub fn new(project_directory: impl Into<PathBuf>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CheckpointState {
+                project_directory: project_directory.into(),
+                next_id: 1,
+                active: None,
+                history: Vec::new(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CheckpointState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 开始一个新任务，之后的修改都会记录到该任务的检查点中
+    pub fn begin_task(&self, task: &str) {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        let fingerprint = fingerprint_project(&state.project_directory);
+        state.active = Some(Checkpoint {
+            id,
+            task: task.to_string(),
+            created_at: SystemTime::now(),
+            snapshots: Vec::new(),
+            fingerprint,
+            changes: Vec::new(),
+            untracked_changes: Vec::new(),
+        });
+    }
+
+    /// 在任务首次修改某个路径之前保存它的原始状态，
+    /// 不存在的上级目录也会一并记录，便于撤销时清理
+    pub fn record(&self, path: &Path) -> std::io::Result<()> {
+        let mut state = self.lock();
+        let Some(active) = state.active.as_mut() else {
+            return Ok(());
+        };
+
+        let mut missing_ancestors: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .collect();
+        missing_ancestors.reverse();
+
+        for target in missing_ancestors.into_iter().chain(std::iter::once(path)) {
+            if active.snapshots.iter().any(|s| s.path == target) {
+                continue;
+            }
+            let original = if target.is_dir() {
+                OriginalState::Directory
+            } else if target.exists() {
+                OriginalState::File(fs::read(target)?)
+            } else {
+                OriginalState::Missing
+            };
+            active.snapshots.push(FileSnapshot {
+                path: target.to_path_buf(),
+                original,
+            });
+        }
+        Ok(())
+    }
+
+    /// 结束当前任务，检测工具之外的修改并保存检查点
+    pub fn finish_task(&self) -> Option<Checkpoint> {
+        let mut state = self.lock();
+        let mut checkpoint = state.active.take()?;
+        checkpoint.changes = checkpoint
+            .snapshots
+            .iter()
+            .filter_map(|snapshot| {
+                snapshot
+                    .change_kind()
+                    .map(|kind| (snapshot.path.clone(), kind))
+            })
+            .collect();
+
+        if let Some(before) = checkpoint.fingerprint.take() {
+            if let Some(after) = fingerprint_project(&state.project_directory) {
+                let tracked: HashSet<&PathBuf> =
+                    checkpoint.snapshots.iter().map(|s| &s.path).collect();
+                let mut changed: Vec<PathBuf> = before
+                    .keys()
+                    .chain(after.keys())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .filter(|path| before.get(*path) != after.get(*path))
+                    .filter(|path| !tracked.contains(path))
+                    .cloned()
+                    .collect();
+                changed.sort();
+                checkpoint.untracked_changes = changed;
+            }
+        }
+
+        state.history.push(checkpoint.clone());
+        Some(checkpoint)
+    }
+
+    /// 撤销最近一个任务的所有文件修改
+    pub fn undo_last(&self) -> AgentResult<Checkpoint> {
+        let mut state = self.lock();
+        if state.active.is_some() {
+            return Err(AgentError::RuntimeError(crate::t!(
+                "checkpoint.task_running"
+            )));
+        }
+        let checkpoint = state
+            .history
+            .pop()
+            .ok_or_else(|| AgentError::RuntimeError(crate::t!("checkpoint.nothing_to_undo")))?;
+
+        // 按记录的逆序恢复，保证先处理文件再处理其所在的新建目录
+        for snapshot in checkpoint.snapshots.iter().rev() {
+            snapshot.restore().map_err(|e| {
+                AgentError::RuntimeError(crate::t!(
+                    "checkpoint.restore_failed",
+                    path = snapshot.path.display(),
+                    error = e
+                ))
+            })?;
+        }
+        Ok(checkpoint)
+    }
+
+    /// 已完成任务的检查点，从旧到新排列
+    pub fn list(&self) -> Vec<Checkpoint> {
+        self.lock().history.clone()
+    }
+}
+
+/// 记录项目中所有文件的大小和修改时间；文件过多时返回 None
+fn fingerprint_project(root: &Path) -> Option<BTreeMap<PathBuf, FileFingerprint>> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                let ignored = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| IGNORED_DIRECTORIES.contains(&name));
+                if !ignored {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            files.insert(
+                path,
+                FileFingerprint {
+                    len: metadata.len(),
+                    modified: metadata.modified().ok(),
+                },
+            );
+            if files.len() > MAX_FINGERPRINT_FILES {
+                return None;
+            }
+        }
+    }
+
+    Some(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_restores_created_modified_and_deleted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing.txt");
+        let removed = dir.path().join("removed.txt");
+        let created = dir.path().join("nested/dir/created.txt");
+        fs::write(&existing, "old").unwrap();
+        fs::write(&removed, "keep me").unwrap();
+
+        let manager = CheckpointManager::new(dir.path());
+        manager.begin_task("修改文件");
+
+        manager.record(&existing).unwrap();
+        fs::write(&existing, "new").unwrap();
+        manager.record(&removed).unwrap();
+        fs::remove_file(&removed).unwrap();
+        manager.record(&created).unwrap();
+        fs::create_dir_all(created.parent().unwrap()).unwrap();
+        fs::write(&created, "hello").unwrap();
+
+        let checkpoint = manager.finish_task().unwrap();
+        let changes = checkpoint.changes();
+        assert!(changes.contains(&(existing.clone(), ChangeKind::Modified)));
+        assert!(changes.contains(&(removed.clone(), ChangeKind::Deleted)));
+        assert!(changes.contains(&(created.clone(), ChangeKind::Created)));
+        assert!(checkpoint.untracked_changes().is_empty());
+
+        manager.undo_last().unwrap();
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&removed).unwrap(), "keep me");
+        assert!(!created.exists());
+        assert!(!dir.path().join("nested").exists());
+        assert!(manager.list().is_empty());
+    }
+
+    #[test]
+    fn test_detects_changes_outside_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CheckpointManager::new(dir.path());
+        manager.begin_task("运行命令");
+        fs::write(dir.path().join("from_shell.txt"), "x").unwrap();
+
+        let checkpoint = manager.finish_task().unwrap();
+        assert_eq!(
+            checkpoint.untracked_changes(),
+            &[dir.path().join("from_shell.txt")]
+        );
+    }
+}
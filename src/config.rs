@@ -0,0 +1,628 @@
+//! 分层配置：内置默认值 < 全局配置文件 < 项目配置文件 < 环境变量 < 命令行参数
+
+use crate::approval::ApprovalRules;
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::Lang;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// 项目级配置文件
+pub const PROJECT_CONFIG_FILE: &str = ".rust-agent/config.toml";
+
+/// 模型服务的连接信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    /// 服务名称，仅用于展示
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            name: "openrouter".to_string(),
+            api_base: None,
+            api_key: None,
+        }
+    }
+}
+
+/// 模型名称和采样参数，未设置的采样参数使用服务端默认值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            name: "kimi-k2-250711".to_string(),
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// 模型输出不完整时同一问题最多重新请求的次数
+    pub max_retries: usize,
+    /// 单个任务最多执行的工具调用次数
+    pub max_steps: usize,
+    /// 模型一次回答中最多执行的动作数，超出的部分不会执行
+    pub max_actions_per_turn: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            max_steps: 50,
+            max_actions_per_turn: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// 启用的工具，为空表示全部启用
+    pub enabled: Vec<String>,
+    /// 禁用的工具，优先于 enabled
+    pub disabled: Vec<String>,
+    /// http_request 允许访问的主机，未设置时仅允许本机
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_allowed_hosts: Option<Vec<String>>,
+}
+
+impl ToolsConfig {
+    pub fn is_enabled(&self, tool_name: &str) -> bool {
+        let name = tool_name.to_string();
+        (self.enabled.is_empty() || self.enabled.contains(&name)) && !self.disabled.contains(&name)
+    }
+}
+
+/// 系统提示词中的项目上下文
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContextConfig {
+    /// 项目说明文件，相对于项目目录；不含目录的文件名也会在子目录中查找
+    pub instruction_files: Vec<String>,
+    /// 说明文件总共最多加载的字节数
+    pub max_instruction_bytes: usize,
+    /// 目录树的最大深度
+    pub max_depth: usize,
+    /// 目录树最多显示的条目数
+    pub max_entries: usize,
+    /// 系统提示词中最多加入的项目记忆条数，与任务相关的优先
+    pub max_memories: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            instruction_files: vec![
+                "AGENTS.md".to_string(),
+                ".rust-agent/instructions.md".to_string(),
+            ],
+            max_instruction_bytes: 16 * 1024,
+            max_depth: 3,
+            max_entries: 200,
+            max_memories: 20,
+        }
+    }
+}
+
+/// delegate 工具创建的子Agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DelegateConfig {
+    /// 子Agent最多嵌套的层数，为 0 时不提供 delegate 工具
+    pub max_depth: usize,
+    /// 整个委派树同时运行的子Agent数量上限
+    pub max_concurrent: usize,
+    /// 子Agent单个任务最多执行的工具调用次数
+    pub max_steps: usize,
+    /// 允许子Agent使用修改文件或执行命令的工具，默认只提供只读工具
+    pub allow_mutating: bool,
+}
+
+impl Default for DelegateConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 1,
+            max_concurrent: 2,
+            max_steps: 20,
+            allow_mutating: false,
+        }
+    }
+}
+
+/// 系统提示词模板及模板中可用的附加内容
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptConfig {
+    /// 内置模板和工具说明的语言，未设置时与界面语言相同
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<Lang>,
+    /// 模板文件，相对路径基于项目目录；未设置时使用 `.rust-agent/prompt.j2` 或内置模板
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+    /// 要求模型遵守的项目规则，对应模板中的 `rules`
+    pub rules: Vec<String>,
+    /// 自定义模板变量
+    pub variables: BTreeMap<String, String>,
+}
+
+/// 命名的模型配置，可通过 `--profile` 或 `/model` 切换，未设置的项沿用 `[provider]` 和 `[model]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base: Option<String>,
+    /// 读取API密钥的环境变量名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    /// 启动时使用的模型配置名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// 界面语言，未设置时根据系统区域设置判断
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<Lang>,
+    pub provider: ProviderConfig,
+    pub model: ModelConfig,
+    pub limits: LimitsConfig,
+    pub tools: ToolsConfig,
+    pub approval: ApprovalRules,
+    pub prompt: PromptConfig,
+    pub context: ContextConfig,
+    pub delegate: DelegateConfig,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// 环境变量与配置项的对应关系
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("OPENROUTER_API_KEY", "provider.api_key"),
+    ("OPENAI_API_BASE", "provider.api_base"),
+    ("OPENAI_MODEL_NAME", "model.name"),
+    ("HTTP_ALLOWED_HOSTS", "tools.http_allowed_hosts"),
+];
+
+/// 全局配置文件路径：`$RUST_AGENT_CONFIG`，否则为 `~/.config/rust-agent/config.toml`
+pub fn global_config_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("RUST_AGENT_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok()?;
+    Some(config_home.join("rust-agent").join("config.toml"))
+}
+
+/// 将 `overlay` 递归合并到 `base`，同名的表逐项合并，其他值直接覆盖
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 按点分隔的路径设置值，如 `model.temperature`
+fn set_path(table: &mut Table, path: &str, value: Value) -> AgentResult<()> {
+    let mut keys: Vec<&str> = path.split('.').map(|k| k.trim()).collect();
+    let last = keys.pop().filter(|k| !k.is_empty());
+    let Some(last) = last else {
+        return Err(AgentError::ConfigError(t!(
+            "config.invalid_key",
+            key = path
+        )));
+    };
+
+    let mut current = table;
+    for key in keys {
+        let entry = current
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        current = entry
+            .as_table_mut()
+            .ok_or_else(|| AgentError::ConfigError(t!("config.not_a_table", key = key)))?;
+    }
+    current.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// 命令行中的值按TOML解析（数字、布尔、数组等），解析失败时视为字符串
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn env_value(path: &str, raw: &str) -> Value {
+    if path == "tools.http_allowed_hosts" {
+        return Value::Array(
+            raw.split(',')
+                .map(|h| h.trim())
+                .filter(|h| !h.is_empty())
+                .map(|h| Value::String(h.to_string()))
+                .collect(),
+        );
+    }
+    Value::String(raw.to_string())
+}
+
+/// 配置的一个来源层
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    /// 来源说明，如文件路径或 "环境变量"
+    pub source: String,
+    table: Table,
+}
+
+impl ConfigLayer {
+    /// 读取TOML配置文件，文件不存在时返回 None
+    pub fn from_file(path: &Path) -> AgentResult<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        let table = toml::from_str(&content).map_err(|e| {
+            AgentError::ConfigError(t!("config.invalid_file", path = path.display(), error = e))
+        })?;
+        Ok(Some(Self {
+            source: path.display().to_string(),
+            table,
+        }))
+    }
+
+    /// 从环境变量生成配置层，`lookup` 用于读取变量值
+    pub fn from_env(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let mut table = Table::new();
+        for (name, path) in ENV_OVERRIDES {
+            if let Some(raw) = lookup(name).filter(|v| !v.is_empty()) {
+                // 路径都是固定的合法值，不会失败
+                let _ = set_path(&mut table, path, env_value(path, &raw));
+            }
+        }
+        Self {
+            source: crate::i18n::text("config.source_env").to_string(),
+            table,
+        }
+    }
+
+    /// 从命令行的 `key=value` 覆盖项生成配置层
+    pub fn from_overrides(overrides: &[String]) -> AgentResult<Self> {
+        let mut table = Table::new();
+        for item in overrides {
+            let (path, raw) = item.split_once('=').ok_or_else(|| {
+                AgentError::ConfigError(t!("config.invalid_override", item = item))
+            })?;
+            set_path(&mut table, path, parse_value(raw.trim()))?;
+        }
+        Ok(Self {
+            source: crate::i18n::text("config.source_cli").to_string(),
+            table,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+/// 合并后的配置及其来源
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: AgentConfig,
+    /// 实际生效的来源，按优先级从低到高排列
+    pub sources: Vec<String>,
+}
+
+impl AgentConfig {
+    /// 按顺序合并各层，后面的层覆盖前面的层
+    pub fn from_layers(layers: Vec<ConfigLayer>) -> AgentResult<LoadedConfig> {
+        let mut merged = Table::new();
+        let mut sources = Vec::new();
+        for layer in layers.into_iter().filter(|l| !l.is_empty()) {
+            sources.push(layer.source);
+            merge_tables(&mut merged, layer.table);
+        }
+
+        let config = Value::Table(merged)
+            .try_into()
+            .map_err(|e| AgentError::ConfigError(t!("config.invalid", error = e)))?;
+        Ok(LoadedConfig { config, sources })
+    }
+
+    /// 加载全局配置、项目配置、环境变量（包括 `.env`）和命令行覆盖项
+    pub fn load(
+        project_directory: Option<&Path>,
+        overrides: &[String],
+    ) -> AgentResult<LoadedConfig> {
+        dotenv::dotenv().ok();
+
+        let mut layers = Vec::new();
+        if let Some(path) = global_config_path() {
+            layers.extend(ConfigLayer::from_file(&path)?);
+        }
+        if let Some(project_directory) = project_directory {
+            layers.extend(ConfigLayer::from_file(
+                &project_directory.join(PROJECT_CONFIG_FILE),
+            )?);
+        }
+        layers.push(ConfigLayer::from_env(|name| std::env::var(name).ok()));
+        layers.push(ConfigLayer::from_overrides(overrides)?);
+        Self::from_layers(layers)
+    }
+
+    /// 应用 `profile` 指定的模型配置，未指定时原样返回
+    pub fn resolved(&self) -> AgentResult<AgentConfig> {
+        match &self.profile {
+            Some(name) => self.with_profile(name),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// 用名为 `name` 的模型配置覆盖连接信息和模型参数
+    pub fn with_profile(&self, name: &str) -> AgentResult<AgentConfig> {
+        self.with_profile_from_env(name, |var| std::env::var(var).ok())
+    }
+
+    fn with_profile_from_env(
+        &self,
+        name: &str,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> AgentResult<AgentConfig> {
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(|k| k.as_str()).collect();
+            AgentError::ConfigError(t!(
+                "config.unknown_profile",
+                name = name,
+                available = if available.is_empty() {
+                    t!("config.no_profiles")
+                } else {
+                    available.join(", ")
+                }
+            ))
+        })?;
+
+        let mut config = self.clone();
+        config.profile = Some(name.to_string());
+        if let Some(provider) = &profile.provider {
+            config.provider.name = provider.clone();
+        }
+        if let Some(api_base) = &profile.api_base {
+            config.provider.api_base = Some(api_base.clone());
+        }
+        if let Some(var) = &profile.api_key_env {
+            let api_key = lookup(var).filter(|v| !v.is_empty()).ok_or_else(|| {
+                AgentError::ConfigError(t!("config.profile_env_missing", name = name, var = var))
+            })?;
+            config.provider.api_key = Some(api_key);
+        }
+        config.model = ModelConfig {
+            name: profile.model.clone(),
+            temperature: profile.temperature.or(self.model.temperature),
+            top_p: profile.top_p.or(self.model.top_p),
+            max_tokens: profile.max_tokens.or(self.model.max_tokens),
+        };
+        Ok(config)
+    }
+
+    /// 界面语言：配置中的 `language`，否则按系统区域设置判断
+    pub fn ui_lang(&self) -> Lang {
+        self.language.unwrap_or_else(Lang::detect)
+    }
+
+    /// 提示词语言：`prompt.language`，否则与界面语言相同
+    pub fn prompt_lang(&self) -> Lang {
+        self.prompt.language.unwrap_or_else(|| self.ui_lang())
+    }
+
+    /// 按配置设置全局的界面语言和提示词语言
+    pub fn apply_languages(&self) {
+        crate::i18n::set_ui_lang(self.ui_lang());
+        crate::i18n::set_prompt_lang(self.prompt_lang());
+    }
+
+    /// 检查连接模型服务所需的配置是否齐全
+    pub fn require_provider(&self) -> AgentResult<(&str, &str)> {
+        let api_key = self
+            .provider
+            .api_key
+            .as_deref()
+            .ok_or_else(|| AgentError::ConfigError(t!("config.missing_api_key")))?;
+        let api_base = self
+            .provider
+            .api_base
+            .as_deref()
+            .ok_or_else(|| AgentError::ConfigError(t!("config.missing_api_base")))?;
+        Ok((api_key, api_base))
+    }
+
+    /// 以TOML格式输出，API密钥只显示前几位
+    pub fn to_display_toml(&self) -> AgentResult<String> {
+        let mut masked = self.clone();
+        if let Some(key) = &masked.provider.api_key {
+            let visible: String = key.chars().take(4).collect();
+            masked.provider.api_key = Some(format!("{}****", visible));
+        }
+        toml::to_string_pretty(&masked)
+            .map_err(|e| AgentError::ConfigError(t!("config.serialize_failed", error = e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::ApprovalPolicy;
+
+    fn layer(source: &str, content: &str) -> ConfigLayer {
+        ConfigLayer {
+            source: source.to_string(),
+            table: toml::from_str(content).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_later_layers_take_precedence() {
+        let global = layer(
+            "global",
+            r#"
+            [provider]
+            api_base = "https://global.example/v1"
+            [model]
+            name = "global-model"
+            temperature = 0.2
+            [approval]
+            default = "reject"
+            "#,
+        );
+        let project = layer(
+            "project",
+            r#"
+            [model]
+            name = "project-model"
+            [limits]
+            max_steps = 10
+            [approval.tools]
+            git_commit = "auto"
+            "#,
+        );
+        let env = ConfigLayer::from_env(|name| match name {
+            "OPENAI_MODEL_NAME" => Some("env-model".to_string()),
+            "HTTP_ALLOWED_HOSTS" => Some("localhost, *.internal".to_string()),
+            _ => None,
+        });
+        let cli = ConfigLayer::from_overrides(&["model.max_tokens=1024".to_string()]).unwrap();
+
+        let loaded = AgentConfig::from_layers(vec![global, project, env, cli]).unwrap();
+        let config = loaded.config;
+        assert_eq!(
+            config.provider.api_base.as_deref(),
+            Some("https://global.example/v1")
+        );
+        assert_eq!(config.model.name, "env-model");
+        assert_eq!(config.model.temperature, Some(0.2));
+        assert_eq!(config.model.max_tokens, Some(1024));
+        assert_eq!(config.limits.max_steps, 10);
+        assert_eq!(config.limits.max_retries, 5);
+        assert_eq!(
+            config.tools.http_allowed_hosts,
+            Some(vec!["localhost".to_string(), "*.internal".to_string()])
+        );
+        assert_eq!(
+            config.approval.policy_for("git_commit"),
+            ApprovalPolicy::AutoApprove
+        );
+        assert_eq!(
+            config.approval.policy_for("run_terminal_command"),
+            ApprovalPolicy::Reject
+        );
+        assert_eq!(
+            loaded.sources,
+            vec![
+                "global".to_string(),
+                "project".to_string(),
+                t!("config.source_env"),
+                t!("config.source_cli")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_overrides_provider_and_model() {
+        let loaded = AgentConfig::from_layers(vec![layer(
+            "project",
+            r#"
+            profile = "cheap"
+            [provider]
+            api_base = "https://default.example/v1"
+            api_key = "default-key"
+            [model]
+            name = "default-model"
+            max_tokens = 2048
+            [profiles.cheap]
+            model = "small-model"
+            temperature = 0.7
+            [profiles.strong]
+            provider = "openai"
+            api_base = "https://api.openai.com/v1"
+            api_key_env = "OPENAI_API_KEY"
+            model = "big-model"
+            "#,
+        )])
+        .unwrap();
+        let config = loaded.config;
+
+        let cheap = config.resolved().unwrap();
+        assert_eq!(cheap.model.name, "small-model");
+        assert_eq!(cheap.model.temperature, Some(0.7));
+        assert_eq!(cheap.model.max_tokens, Some(2048));
+        assert_eq!(cheap.provider.api_key.as_deref(), Some("default-key"));
+
+        let lookup = |var: &str| (var == "OPENAI_API_KEY").then(|| "sk-strong".to_string());
+        let strong = config.with_profile_from_env("strong", lookup).unwrap();
+        assert_eq!(strong.profile.as_deref(), Some("strong"));
+        assert_eq!(strong.provider.name, "openai");
+        assert_eq!(strong.provider.api_key.as_deref(), Some("sk-strong"));
+        assert_eq!(strong.model.name, "big-model");
+        // 从基础配置切换，不会带上其他模型配置的参数
+        assert_eq!(strong.model.temperature, None);
+
+        assert!(config.with_profile_from_env("strong", |_| None).is_err());
+        let err = config.with_profile("missing").unwrap_err().to_string();
+        assert!(err.contains("cheap, strong"));
+    }
+
+    #[test]
+    fn test_reports_invalid_values() {
+        let result = AgentConfig::from_layers(vec![layer("project", "[model]\nnmae = \"x\"")]);
+        assert!(result.unwrap_err().to_string().contains("nmae"));
+
+        let result = ConfigLayer::from_overrides(&["model.name".to_string()]);
+        assert!(result.is_err());
+
+        let config = AgentConfig::default();
+        assert!(config.require_provider().is_err());
+        assert!(config.tools.is_enabled("read_file"));
+    }
+}
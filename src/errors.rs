@@ -1,30 +1,39 @@
+use std::fmt::Display;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum AgentError {
-    #[error("API调用失败: {0}")]
+    #[error("{}", message("error.api", .0))]
     ApiError(String),
 
-    #[error("JSON序列化/反序列化错误: {0}")]
+    #[error("{}", message("error.json", .0))]
     JsonError(#[from] serde_json::Error),
 
-    #[error("环境变量未设置: {0}")]
+    #[error("{}", message("error.env_var", .0))]
     EnvVarError(String),
 
-    #[error("工具执行错误: {0}")]
+    #[error("{}", message("error.tool_execution", .0))]
     ToolExecutionError(String),
 
-    #[error("解析错误: {0}")]
+    #[error("{}", message("error.parse", .0))]
     ParseError(String),
 
-    #[error("运行时错误: {0}")]
+    #[error("{}", message("error.runtime", .0))]
     RuntimeError(String),
 
-    #[error("IO错误: {0}")]
+    #[error("{}", message("error.io", .0))]
     IoError(#[from] std::io::Error),
 
-    #[error("命令执行错误: {0}")]
+    #[error("{}", message("error.command_execution", .0))]
     CommandExecutionError(String),
+
+    #[error("{}", message("error.config", .0))]
+    ConfigError(String),
+}
+
+/// 按界面语言生成错误信息
+fn message(key: &str, detail: &impl Display) -> String {
+    crate::i18n::translate(key, &[("detail", detail.to_string())])
 }
 
 pub type AgentResult<T> = Result<T, AgentError>;
@@ -0,0 +1,245 @@
+//! 多语言支持。消息目录位于 `locales/<语言>.toml`，文本中的 `{name}` 为占位符。
+//! 界面语言和提示词语言分开设置：界面文本用 [`t!`]，提供给模型的文本用 [`pt!`]
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Lang {
+    /// 解析语言标签，如 `en`、`zh-CN`、`en_US.UTF-8`
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_lowercase();
+        match tag.split(['-', '_', '.', '@']).next() {
+            Some("zh") => Some(Self::Zh),
+            Some("en") => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    /// 按 LC_ALL、LC_MESSAGES、LANG 的顺序判断系统语言，无法识别时使用中文
+    pub fn detect() -> Self {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    fn detect_from(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .find_map(|name| lookup(name).filter(|v| !v.is_empty()))
+            .and_then(|tag| Self::parse(&tag))
+            .unwrap_or_default()
+    }
+
+    fn catalog(self) -> &'static HashMap<String, String> {
+        static ZH: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static EN: OnceLock<HashMap<String, String>> = OnceLock::new();
+        match self {
+            Self::Zh => ZH.get_or_init(|| load_catalog(include_str!("../locales/zh.toml"))),
+            Self::En => EN.get_or_init(|| load_catalog(include_str!("../locales/en.toml"))),
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Zh => 0,
+            Self::En => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::En,
+            _ => Self::Zh,
+        }
+    }
+}
+
+static UI_LANG: AtomicU8 = AtomicU8::new(0);
+static PROMPT_LANG: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_ui_lang(lang: Lang) {
+    UI_LANG.store(lang.to_u8(), Ordering::Relaxed);
+}
+
+pub fn ui_lang() -> Lang {
+    Lang::from_u8(UI_LANG.load(Ordering::Relaxed))
+}
+
+pub fn set_prompt_lang(lang: Lang) {
+    PROMPT_LANG.store(lang.to_u8(), Ordering::Relaxed);
+}
+
+pub fn prompt_lang() -> Lang {
+    #[cfg(test)]
+    if let Some(lang) = THREAD_PROMPT_LANG.with(std::cell::Cell::get) {
+        return lang;
+    }
+    Lang::from_u8(PROMPT_LANG.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+thread_local! {
+    /// 只对当前线程生效的提示词语言，测试修改语言时不影响并行的其他测试
+    static THREAD_PROMPT_LANG: std::cell::Cell<Option<Lang>> = const { std::cell::Cell::new(None) };
+}
+
+/// 在当前线程中以指定的提示词语言执行 `f`
+#[cfg(test)]
+pub(crate) fn with_prompt_lang<R>(lang: Lang, f: impl FnOnce() -> R) -> R {
+    let previous = THREAD_PROMPT_LANG.with(|current| current.replace(Some(lang)));
+    let result = f();
+    THREAD_PROMPT_LANG.with(|current| current.set(previous));
+    result
+}
+
+/// 将嵌套的表展开为 `section.key` 形式的键
+fn load_catalog(source: &str) -> HashMap<String, String> {
+    fn flatten(prefix: &str, table: toml::Table, catalog: &mut HashMap<String, String>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            match value {
+                toml::Value::Table(table) => flatten(&key, table, catalog),
+                toml::Value::String(text) => {
+                    catalog.insert(key, text);
+                }
+                other => panic!("消息目录中的 '{}' 不是字符串: {}", key, other),
+            }
+        }
+    }
+
+    let table: toml::Table = toml::from_str(source).expect("消息目录格式错误");
+    let mut catalog = HashMap::new();
+    flatten("", table, &mut catalog);
+    catalog
+}
+
+/// 查找消息文本，缺失时依次回退到中文和键本身
+pub fn text_in(lang: Lang, key: &str) -> &str {
+    lang.catalog()
+        .get(key)
+        .or_else(|| Lang::Zh.catalog().get(key))
+        .map(|text| text.as_str())
+        .unwrap_or(key)
+}
+
+/// 界面语言的消息文本
+pub fn text(key: &str) -> &str {
+    text_in(ui_lang(), key)
+}
+
+/// 提示词语言的消息文本
+pub fn prompt_text(key: &str) -> &str {
+    text_in(prompt_lang(), key)
+}
+
+/// 查找消息并替换占位符；参数值中的 `{...}` 不会被再次替换
+pub fn translate_in(lang: Lang, key: &str, args: &[(&str, String)]) -> String {
+    let template = text_in(lang, key);
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                output.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+pub fn translate(key: &str, args: &[(&str, String)]) -> String {
+    translate_in(ui_lang(), key, args)
+}
+
+/// 按界面语言翻译：`t!("repl.goodbye")`、`t!("repl.session_saved", path = path.display())`
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::translate($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($key, &[$((stringify!($name), ($value).to_string())),+])
+    };
+}
+
+/// 按提示词语言翻译，用于发送给模型的文本
+#[macro_export]
+macro_rules! pt {
+    ($key:expr) => {
+        $crate::i18n::translate_in($crate::i18n::prompt_lang(), $key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate_in(
+            $crate::i18n::prompt_lang(),
+            $key,
+            &[$((stringify!($name), ($value).to_string())),+],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_catalogs_have_the_same_keys() {
+        let zh: BTreeSet<&String> = Lang::Zh.catalog().keys().collect();
+        let en: BTreeSet<&String> = Lang::En.catalog().keys().collect();
+        assert_eq!(
+            zh.symmetric_difference(&en).collect::<Vec<_>>(),
+            Vec::<&&String>::new()
+        );
+    }
+
+    #[test]
+    fn test_translate_and_detect() {
+        let args = [("path", "{path}.rs".to_string())];
+        assert_eq!(
+            translate_in(Lang::En, "tools.write_succeeded", &args),
+            "Wrote {path}.rs"
+        );
+        assert_eq!(
+            translate_in(Lang::Zh, "tools.write_succeeded", &args),
+            "写入成功: {path}.rs"
+        );
+        assert_eq!(text_in(Lang::En, "missing.key"), "missing.key");
+
+        assert_eq!(Lang::parse("en_US.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::parse("zh-Hans"), Some(Lang::Zh));
+        assert_eq!(Lang::parse("C"), None);
+        let env = |name: &str| match name {
+            "LC_ALL" => Some(String::new()),
+            "LANG" => Some("en_GB.UTF-8".to_string()),
+            _ => None,
+        };
+        assert_eq!(Lang::detect_from(env), Lang::En);
+        assert_eq!(Lang::detect_from(|_| None), Lang::Zh);
+    }
+}
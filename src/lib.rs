@@ -1,10 +1,31 @@
+pub mod action;
 pub mod agent;
+pub mod approval;
+pub mod checkpoint;
+pub mod config;
 pub mod errors;
+pub mod i18n;
+pub mod mcp;
+pub mod memory;
+pub mod plan;
+pub mod project_context;
 pub mod prompt_template;
+pub mod react_parser;
+pub mod replay;
 pub mod tools;
+pub mod trajectory;
 pub mod types;
 
 pub use agent::ReActAgent;
+pub use approval::{ApprovalPolicy, ApprovalRules};
+pub use checkpoint::{ChangeKind, Checkpoint, CheckpointManager};
+pub use config::AgentConfig;
 pub use errors::AgentError;
-pub use tools::{create_default_tools, Tool, ToolRegistry};
+pub use memory::{Memory, MemoryStore};
+pub use plan::{Plan, PlanTracker};
+pub use tools::{
+    create_configured_tools, create_default_tools, load_custom_tools, load_plugin_tools, Tool,
+    ToolParameter, ToolRegistry,
+};
+pub use trajectory::{Trajectory, TrajectoryRecorder};
 pub use types::*;
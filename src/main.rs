@@ -1,101 +1,826 @@
-use clap::Parser;
-use rust_agent::{create_default_tools, ReActAgent};
-use std::io::{self, Write};
-use std::path::PathBuf;
+mod repl;
+
+use clap::{Parser, Subcommand};
+use repl::{print_help, Input, Repl};
+use rust_agent::config::LoadedConfig;
+use rust_agent::i18n::{self, Lang};
+use rust_agent::replay::{copy_fixture, RecordedModel, ReplayReport};
+use rust_agent::tools::format_memory;
+use rust_agent::tools::DELEGATE_TOOL;
+use rust_agent::trajectory::Outcome;
+use rust_agent::{
+    create_configured_tools, load_custom_tools, load_plugin_tools, mcp, t, AgentConfig,
+    ApprovalPolicy, Plan, ReActAgent, Tool, ToolRegistry, Trajectory,
+};
+use std::io;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "rust-agent")]
 #[command(about = "A Rust implementation of ReAct Agent")]
 #[command(version)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// 项目目录路径
     #[arg(value_name = "PROJECT_DIRECTORY")]
-    project_directory: PathBuf,
+    project_directory: Option<PathBuf>,
+
+    /// 使用的模型名称，覆盖配置文件和环境变量
+    #[arg(long, global = true)]
+    model: Option<String>,
+
+    /// 使用的模型配置（配置文件中 [profiles.<名称>] 定义）
+    #[arg(long, global = true, conflicts_with = "model")]
+    profile: Option<String>,
+
+    /// 覆盖任意配置项，可重复使用，如 --set model.temperature=0.2
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    overrides: Vec<String>,
+
+    /// 界面语言（zh 或 en），默认根据系统区域设置判断
+    #[arg(long, value_name = "LANG", global = true, value_parser = parse_lang)]
+    lang: Option<Lang>,
+
+    /// 提示词和工具说明的语言（zh 或 en），默认与界面语言相同
+    #[arg(long, value_name = "LANG", global = true, value_parser = parse_lang)]
+    prompt_lang: Option<Lang>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 初始化日志
-    tracing_subscriber::fmt::init();
+fn parse_lang(value: &str) -> Result<Lang, String> {
+    Lang::parse(value).ok_or_else(|| format!("unsupported language '{}', expected zh or en", value))
+}
 
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum Commands {
+    /// 以MCP服务的形式通过stdio提供项目的工具
+    McpServe {
+        /// 项目目录路径
+        #[arg(value_name = "PROJECT_DIRECTORY")]
+        project_directory: PathBuf,
+
+        /// 自动批准需要确认的操作（默认全部拒绝）
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 查看运行轨迹：不指定名称时列出全部轨迹，新的在前
+    Trajectory {
+        /// 项目目录路径
+        #[arg(value_name = "PROJECT_DIRECTORY")]
+        project_directory: PathBuf,
+
+        /// 轨迹文件名（可省略 .jsonl）或路径，latest 表示最近的一次运行
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+
+        /// 显示系统提示词以及完整的消息和观察结果
+        #[arg(long)]
+        full: bool,
+    },
+    /// 回放录制的运行：用轨迹中的模型输出代替API，在项目副本中重新执行工具，报告与录制不同的地方
+    Replay {
+        /// 项目目录路径
+        #[arg(value_name = "PROJECT_DIRECTORY")]
+        project_directory: PathBuf,
+
+        /// 要回放的轨迹文件名（可省略 .jsonl）或路径，可指定多个，latest 表示最近的一次运行
+        #[arg(value_name = "NAME", required = true)]
+        names: Vec<String>,
+
+        /// 复制这个目录而不是项目目录作为回放的工作目录，如与轨迹一起保存的项目快照
+        #[arg(long, value_name = "DIR")]
+        fixture: Option<PathBuf>,
+
+        /// 保留回放使用的项目副本
+        #[arg(long)]
+        keep_fixture: bool,
+    },
+    /// 查看配置
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
 
-    // 检查项目目录是否存在
-    if !cli.project_directory.exists() {
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// 打印合并后的配置及其来源
+    Show {
+        /// 项目目录路径，指定时包含项目配置文件
+        #[arg(value_name = "PROJECT_DIRECTORY")]
+        project_directory: Option<PathBuf>,
+    },
+}
+
+/// 检查项目目录是否存在，返回规范化后的路径
+fn resolve_project_directory(project_directory: &Path) -> PathBuf {
+    if !project_directory.exists() {
         eprintln!(
-            "错误：项目目录 '{}' 不存在",
-            cli.project_directory.display()
+            "{}",
+            t!(
+                "cli.directory_not_found",
+                path = project_directory.display()
+            )
         );
         std::process::exit(1);
     }
 
-    if !cli.project_directory.is_dir() {
-        eprintln!("错误：'{}' 不是一个目录", cli.project_directory.display());
+    if !project_directory.is_dir() {
+        eprintln!(
+            "{}",
+            t!("cli.not_a_directory", path = project_directory.display())
+        );
         std::process::exit(1);
     }
 
-    let project_dir = cli.project_directory.canonicalize()?;
-    println!("项目目录: {}", project_dir.display());
+    project_directory
+        .canonicalize()
+        .unwrap_or_else(|_| project_directory.to_path_buf())
+}
+
+/// 注册项目中声明的命令工具（.rust-agent/tools.toml）和WebAssembly插件（.rust-agent/plugins.toml），
+/// 同名时覆盖内置工具
+fn register_project_tools(tools: &mut ToolRegistry, project_dir: &Path) {
+    let project_dir = project_dir.to_string_lossy();
+
+    let mut register = |tool: Box<dyn Tool>| {
+        if tools.get_tool(tool.name()).is_some() {
+            eprintln!("{}", t!("cli.tool_overrides_builtin", name = tool.name()));
+        }
+        tools.register_boxed(tool);
+    };
+
+    match load_custom_tools(&project_dir) {
+        Ok(custom_tools) => custom_tools
+            .into_iter()
+            .for_each(|tool| register(Box::new(tool))),
+        Err(e) => eprintln!("{}", t!("cli.custom_tools_failed", error = e)),
+    }
+    match load_plugin_tools(&project_dir) {
+        Ok(plugins) => plugins
+            .into_iter()
+            .for_each(|tool| register(Box::new(tool))),
+        Err(e) => eprintln!("{}", t!("cli.plugins_failed", error = e)),
+    }
+}
+
+/// 加载分层配置，命令行的 --model、--profile、--lang、--prompt-lang 和 --set 优先级最高。
+/// 加载完成后按配置设置界面语言和提示词语言
+fn load_config(cli: &Cli, project_dir: Option<&Path>) -> LoadedConfig {
+    let mut overrides = cli.overrides.clone();
+    let lang_value = |lang: Lang| toml::Value::try_from(lang).expect("语言可以序列化");
+    if let Some(lang) = cli.lang {
+        overrides.push(format!("language={}", lang_value(lang)));
+    }
+    if let Some(lang) = cli.prompt_lang {
+        overrides.push(format!("prompt.language={}", lang_value(lang)));
+    }
+    if let Some(model) = &cli.model {
+        overrides.push(format!("model.name={}", toml::Value::String(model.clone())));
+    }
+    if let Some(profile) = &cli.profile {
+        overrides.push(format!("profile={}", toml::Value::String(profile.clone())));
+    }
+    match AgentConfig::load(project_dir, &overrides) {
+        Ok(loaded) => {
+            loaded.config.apply_languages();
+            loaded
+        }
+        Err(e) => {
+            eprintln!("{}", t!("cli.error", message = e));
+            std::process::exit(1);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    // 配置加载前的消息（如配置错误）使用命令行或系统区域设置的语言
+    i18n::set_ui_lang(cli.lang.unwrap_or_else(Lang::detect));
+
+    match (&cli.command, &cli.project_directory) {
+        (
+            Some(Commands::McpServe {
+                project_directory,
+                yes,
+            }),
+            _,
+        ) => {
+            // stdout用于协议通信，日志只能写到stderr
+            tracing_subscriber::fmt().with_writer(io::stderr).init();
+            let project_dir = resolve_project_directory(project_directory);
+            let mut config = load_config(&cli, Some(&project_dir)).config;
+            // 服务模式下无法询问用户，需要询问的操作默认拒绝，--yes 时自动批准
+            let fallback = if *yes {
+                ApprovalPolicy::AutoApprove
+            } else {
+                ApprovalPolicy::Reject
+            };
+            config.approval = config.approval.without_prompts(fallback);
+
+            let mut tools =
+                create_configured_tools(project_dir.to_string_lossy().to_string(), &config);
+            register_project_tools(&mut tools, &project_dir);
+            tools.retain(|name| config.tools.is_enabled(name));
+            eprintln!(
+                "{}",
+                t!("cli.mcp_server_started", path = project_dir.display())
+            );
+            mcp::serve(&tools, tokio::io::stdin(), tokio::io::stdout()).await?;
+            Ok(())
+        }
+        (
+            Some(Commands::Config {
+                action: ConfigCommand::Show { project_directory },
+            }),
+            _,
+        ) => {
+            let project_dir = project_directory.as_deref().map(resolve_project_directory);
+            let loaded = load_config(&cli, project_dir.as_deref());
+            let config = loaded.config.resolved().unwrap_or_else(|e| {
+                eprintln!("{}", t!("cli.error", message = e));
+                std::process::exit(1);
+            });
+            println!("{}", t!("cli.config_sources"));
+            for source in &loaded.sources {
+                println!("#   < {}", source);
+            }
+            println!();
+            print!("{}", config.to_display_toml()?);
+            Ok(())
+        }
+        (
+            Some(Commands::Trajectory {
+                project_directory,
+                name,
+                full,
+            }),
+            _,
+        ) => {
+            let project_dir = resolve_project_directory(project_directory);
+            load_config(&cli, Some(&project_dir));
+            match name {
+                Some(name) => show_trajectory(&project_dir, name, *full),
+                None => list_trajectories(&project_dir),
+            }
+            Ok(())
+        }
+        (
+            Some(Commands::Replay {
+                project_directory,
+                names,
+                fixture,
+                keep_fixture,
+            }),
+            _,
+        ) => {
+            let project_dir = resolve_project_directory(project_directory);
+            let source = fixture
+                .as_deref()
+                .map(resolve_project_directory)
+                .unwrap_or_else(|| project_dir.clone());
+            let config = load_config(&cli, Some(&project_dir)).config;
+            let mut failed = 0;
+            for (index, name) in names.iter().enumerate() {
+                match replay(&project_dir, &source, name, index, &config, *keep_fixture).await {
+                    Ok(true) => {}
+                    Ok(false) => failed += 1,
+                    Err(e) => {
+                        eprintln!("{}", t!("cli.error", message = e));
+                        failed += 1;
+                    }
+                }
+            }
+            println!(
+                "\n{}",
+                t!("replay.summary", total = names.len(), failed = failed)
+            );
+            if failed > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        (None, Some(project_directory)) => {
+            // 初始化日志
+            tracing_subscriber::fmt::init();
+            let project_dir = resolve_project_directory(project_directory);
+            let loaded = load_config(&cli, Some(&project_dir));
+            run_interactive(project_dir, loaded.config).await
+        }
+        (None, None) => {
+            eprintln!("{}", t!("cli.missing_project_directory"));
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run_interactive(
+    project_dir: PathBuf,
+    config: AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "{}",
+        t!("cli.project_directory", path = project_dir.display())
+    );
 
     // 创建工具注册表
-    let tools = create_default_tools(project_dir.to_string_lossy().to_string());
+    let mut tools = create_configured_tools(project_dir.to_string_lossy().to_string(), &config);
+    register_project_tools(&mut tools, &project_dir);
 
-    // 从环境变量获取模型名称
-    let model_name =
-        std::env::var("OPENAI_MODEL_NAME").unwrap_or_else(|_| "kimi-k2-250711".to_string());
+    // 注册 .rust-agent/mcp.json 中配置的MCP服务提供的工具
+    match mcp::load_mcp_tools(&project_dir).await {
+        Ok(mcp_tools) => {
+            if !mcp_tools.is_empty() {
+                println!("{}", t!("cli.mcp_tools_loaded", count = mcp_tools.len()));
+            }
+            for tool in mcp_tools {
+                tools.register(tool);
+            }
+        }
+        Err(e) => eprintln!("{}", t!("cli.mcp_config_failed", error = e)),
+    }
+
+    tools.retain(|name| config.tools.is_enabled(name));
 
     // 创建Agent
-    let mut agent = ReActAgent::new(tools, model_name, project_dir.to_string_lossy().to_string())?;
+    let mut agent = ReActAgent::new(tools, config, project_dir.to_string_lossy().to_string())?;
 
-    println!("🤖 Rust Agent 已启动！输入 'quit' 或 'exit' 退出程序。");
-    println!("💡 你可以继续输入新的任务，Agent会记住之前的对话上下文。");
-    println!("💡 输入 'clear' 可以清除对话历史。\n");
+    let mut repl = Repl::new(
+        &project_dir,
+        agent
+            .profile_names()
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+    )?;
+
+    println!("{}", t!("repl.started"));
+    println!("{}", t!("repl.hint_context"));
+    println!("{}", t!("repl.hint_help"));
+    println!("{}\n", t!("repl.hint_multiline"));
+    print_current_model(&agent);
+    // 上次中断的计划可以继续执行
+    if let Some(plan) = agent
+        .plan()
+        .filter(|plan| plan.first_unfinished().is_some())
+    {
+        println!(
+            "{}",
+            t!(
+                "plan.unfinished",
+                task = plan.task,
+                done = plan.completed_count(),
+                total = plan.steps.len()
+            )
+        );
+    }
 
     // 持续对话循环
     loop {
-        // 获取用户输入
-        print!("请输入任务：");
-        io::stdout().flush()?;
+        let Some(input) = repl.read_input(&t!("repl.prompt"))? else {
+            println!("{}", t!("repl.goodbye"));
+            break;
+        };
 
-        let mut task = String::new();
-        io::stdin().read_line(&mut task)?;
-        let task = task.trim();
+        let (name, args) = match input {
+            Input::Task(task) => {
+                if task.is_empty() {
+                    println!("{}", t!("repl.empty_task"));
+                    continue;
+                }
+                run_task(&mut agent, &task).await;
+                continue;
+            }
+            Input::Command { name, args } => (name, args),
+        };
 
-        // 检查退出命令
-        if task.is_empty() {
-            println!("任务不能为空，请重新输入");
-            continue;
+        match name.as_str() {
+            "quit" | "exit" => {
+                println!("{}", t!("repl.goodbye"));
+                break;
+            }
+            "help" => print_help(),
+            "tools" => {
+                for tool in agent.tools().tools() {
+                    println!("  {:<24} {}", tool.name(), tool.description());
+                }
+            }
+            "history" => print_conversation(&agent),
+            "save" | "load" => {
+                let Some(path) = repl::session_path(&project_dir, &args) else {
+                    eprintln!("{}", t!("repl.invalid_session_name", name = args));
+                    continue;
+                };
+                if name == "save" {
+                    match agent.save_conversation(&path) {
+                        Ok(()) => {
+                            println!("{}", t!("repl.session_saved", path = path.display()))
+                        }
+                        Err(e) => eprintln!("{}", t!("repl.save_failed", error = e)),
+                    }
+                } else {
+                    match agent.load_conversation(&path) {
+                        Ok(()) => println!(
+                            "{}",
+                            t!(
+                                "repl.session_loaded",
+                                path = path.display(),
+                                length = agent.get_conversation_length()
+                            )
+                        ),
+                        Err(e) => eprintln!("{}", t!("repl.load_failed", error = e)),
+                    }
+                }
+            }
+            "model" => {
+                if args.is_empty() {
+                    print_current_model(&agent);
+                    let profiles = agent.profile_names();
+                    if profiles.is_empty() {
+                        println!("{}", t!("repl.no_profiles"));
+                    } else {
+                        println!(
+                            "{}",
+                            t!("repl.available_profiles", profiles = profiles.join(", "))
+                        );
+                    }
+                } else {
+                    match agent.switch_profile(&args) {
+                        Ok(()) => print_current_model(&agent),
+                        Err(e) => eprintln!("{}", t!("repl.switch_profile_failed", error = e)),
+                    }
+                }
+            }
+            "clear" => {
+                agent.clear_conversation_history();
+                println!("{}", t!("repl.history_cleared"));
+                println!(
+                    "{}",
+                    t!(
+                        "repl.history_length",
+                        length = agent.get_conversation_length()
+                    )
+                );
+            }
+            "undo" => match agent.undo_last_task() {
+                Ok(message) => println!("↩️  {}", message),
+                Err(e) => eprintln!("{}", t!("repl.undo_failed", error = e)),
+            },
+            "plan" => match args.as_str() {
+                "" => match agent.plan() {
+                    Some(plan) => print_plan(&plan),
+                    None => println!("{}", t!("plan.no_plan")),
+                },
+                "resume" => run_plan(&mut agent).await,
+                "clear" => match agent.clear_plan() {
+                    Ok(()) => println!("{}", t!("plan.cleared")),
+                    Err(e) => eprintln!("{}", t!("cli.error", message = e)),
+                },
+                task => {
+                    if plan_task(&mut agent, &mut repl, task).await? {
+                        run_plan(&mut agent).await;
+                    }
+                }
+            },
+            "memory" => manage_memory(&agent, &args),
+            "checkpoints" => {
+                let checkpoints = agent.list_checkpoints();
+                if checkpoints.is_empty() {
+                    println!("{}", t!("repl.no_checkpoints"));
+                }
+                for checkpoint in checkpoints.iter().rev() {
+                    println!("{}", checkpoint.summary());
+                }
+            }
+            _ => {
+                eprintln!("{}", t!("repl.unknown_command", name = name));
+                continue;
+            }
         }
+        println!("\n{}", "=".repeat(50));
+    }
 
-        if task.to_lowercase() == "quit" || task.to_lowercase() == "exit" {
-            println!("👋 再见！");
-            break;
-        }
+    Ok(())
+}
+
+async fn run_task(agent: &mut ReActAgent, task: &str) {
+    println!("{}", t!("repl.task_started", task = task));
+    println!(
+        "{}",
+        t!(
+            "repl.history_length",
+            length = agent.get_conversation_length()
+        )
+    );
 
-        if task.to_lowercase() == "clear" {
-            agent.clear_conversation_history();
-            println!("🗑️  对话历史已清除！");
-            println!("📊 当前对话历史长度: {}", agent.get_conversation_length());
+    // 运行Agent
+    match agent.run(task).await {
+        Ok(final_answer) => {
+            println!("\n\n{}", t!("repl.final_answer", answer = final_answer));
+            println!("\n{}", "=".repeat(50));
+        }
+        Err(e) => {
+            eprintln!("{}", t!("repl.task_failed", error = e));
+            println!("{}", t!("repl.retry_hint"));
             println!("\n{}", "=".repeat(50));
-            continue;
         }
+    }
+}
 
-        println!("开始执行任务: {}", task);
-        println!("📊 当前对话历史长度: {}", agent.get_conversation_length());
+/// 请模型制定计划，由用户确认或修改后保存为当前计划；用户取消时返回 false
+async fn plan_task(
+    agent: &mut ReActAgent,
+    repl: &mut Repl,
+    task: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("{}", t!("repl.task_started", task = task));
+    let mut steps = match agent.draft_plan(task).await {
+        Ok(steps) => steps,
+        Err(e) => {
+            eprintln!("{}", t!("repl.task_failed", error = e));
+            return Ok(false);
+        }
+    };
 
-        // 运行Agent
-        match agent.run(task).await {
-            Ok(final_answer) => {
-                println!("\n\n✅ Final Answer：{}", final_answer);
-                println!("\n{}", "=".repeat(50));
+    loop {
+        let plan = Plan::new(task, steps.clone());
+        println!("\n{}", t!("plan.proposed", count = plan.steps.len()));
+        println!("{}", plan.render());
+        let Some(choice) = repl.read_text(&t!("plan.confirm"))? else {
+            return Ok(false);
+        };
+        match choice.to_lowercase().as_str() {
+            "y" => {
+                if let Err(e) = agent.set_plan(plan) {
+                    eprintln!("{}", t!("cli.error", message = e));
+                    return Ok(false);
+                }
+                return Ok(true);
             }
-            Err(e) => {
-                eprintln!("Agent执行错误: {}", e);
-                println!("请重新输入任务或输入 'quit' 退出程序");
-                println!("\n{}", "=".repeat(50));
+            "n" => {
+                println!("{}", t!("plan.cancelled"));
+                return Ok(false);
             }
+            "e" => {
+                println!("{}", t!("plan.edit_hint"));
+                let Some(text) = repl.read_text(&t!("repl.prompt"))? else {
+                    return Ok(false);
+                };
+                let edited = Plan::parse_steps(&text);
+                if edited.is_empty() {
+                    println!("{}", t!("plan.edit_empty"));
+                } else {
+                    steps = edited;
+                }
+            }
+            _ => println!("{}", t!("plan.invalid_choice")),
         }
     }
+}
 
-    Ok(())
+/// 从第一个未完成的步骤执行当前计划，结束后显示进度
+async fn run_plan(agent: &mut ReActAgent) {
+    match agent.execute_plan().await {
+        Ok(final_answer) => println!("\n\n{}", t!("repl.final_answer", answer = final_answer)),
+        Err(e) => {
+            eprintln!("{}", t!("repl.task_failed", error = e));
+            println!("{}", t!("repl.retry_hint"));
+        }
+    }
+    if let Some(plan) = agent.plan() {
+        println!();
+        print_plan(&plan);
+    }
+}
+
+fn print_plan(plan: &Plan) {
+    println!(
+        "{}",
+        t!(
+            "plan.progress",
+            done = plan.completed_count(),
+            total = plan.steps.len(),
+            plan = plan.render()
+        )
+    );
+}
+
+/// 查看或删除项目记忆
+fn manage_memory(agent: &ReActAgent, args: &str) {
+    let Some(memory) = agent.tools().memory() else {
+        println!("{}", t!("memory.none"));
+        return;
+    };
+    let (action, id) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let result = match action {
+        "" => {
+            let memories = memory.list();
+            if memories.is_empty() {
+                println!("{}", t!("memory.none"));
+            }
+            for entry in &memories {
+                println!("{}", format_memory(entry));
+            }
+            return;
+        }
+        "forget" => match id.trim().trim_start_matches('#').parse::<usize>() {
+            Ok(id) => memory
+                .forget(id)
+                .map(|entry| t!("memory.forgotten", memory = format_memory(&entry))),
+            Err(_) => Ok(t!("memory.invalid_id", value = id.trim())),
+        },
+        "clear" => memory.clear().map(|()| t!("memory.cleared")),
+        _ => Ok(t!("memory.usage")),
+    };
+    match result {
+        Ok(message) => println!("{}", message),
+        Err(e) => eprintln!("{}", t!("cli.error", message = e)),
+    }
+}
+
+/// 列出项目中的运行轨迹：文件名、结果和任务
+fn list_trajectories(project_dir: &Path) {
+    let paths = Trajectory::list(project_dir);
+    if paths.is_empty() {
+        println!("{}", t!("trajectory.none"));
+        return;
+    }
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let Ok(trajectory) = Trajectory::load(&path) else {
+            continue;
+        };
+        let status = match trajectory.outcome() {
+            Some(Outcome::Answer(_)) => t!("trajectory.status_answered"),
+            Some(Outcome::Cancelled) => t!("trajectory.status_cancelled"),
+            Some(Outcome::Failed(_)) => t!("trajectory.status_failed"),
+            None => t!("trajectory.status_unfinished"),
+        };
+        let task = trajectory.task().unwrap_or_default();
+        let first_line = task.lines().next().unwrap_or_default();
+        let preview: String = first_line.chars().take(60).collect();
+        println!("  {:<24} {:<10} {}", name, status, preview);
+    }
+}
+
+fn show_trajectory(project_dir: &Path, name: &str, full: bool) {
+    let Some(path) = Trajectory::find(project_dir, name) else {
+        eprintln!("{}", t!("trajectory.not_found", name = name));
+        std::process::exit(1);
+    };
+    match Trajectory::load(&path) {
+        Ok(trajectory) => println!("{}", trajectory.render(full)),
+        Err(e) => {
+            eprintln!("{}", t!("cli.error", message = e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 在 `source` 的副本中回放一个运行轨迹并输出比较结果，一致时返回 true
+async fn replay(
+    project_dir: &Path,
+    source: &Path,
+    name: &str,
+    index: usize,
+    config: &AgentConfig,
+    keep_fixture: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let path = Trajectory::find(project_dir, name)
+        .ok_or_else(|| t!("trajectory.not_found", name = name))?;
+    let recorded = Trajectory::load(&path)?;
+    let task = recorded
+        .task()
+        .ok_or_else(|| t!("trajectory.not_found", name = name))?
+        .to_string();
+    println!("\n{}", t!("replay.replaying", name = name, task = task));
+
+    let fixture = std::env::temp_dir().join(format!(
+        "rust-agent-replay-{}-{}",
+        std::process::id(),
+        index
+    ));
+    if fixture.exists() {
+        std::fs::remove_dir_all(&fixture)?;
+    }
+    std::fs::create_dir_all(&fixture)?;
+    let fixture = FixtureGuard {
+        path: fixture.canonicalize()?,
+        keep: keep_fixture,
+    };
+    let count = copy_fixture(source, &fixture.path)?;
+    println!(
+        "{}",
+        t!(
+            "replay.fixture_copied",
+            count = count,
+            path = fixture.path.display()
+        )
+    );
+
+    // 回放不请求API，也无法询问用户：需要确认的操作在副本中自动批准，子Agent没有录制的输出可用
+    let mut config = config.clone();
+    config.approval = config.approval.without_prompts(ApprovalPolicy::AutoApprove);
+    config.tools.disabled.push(DELEGATE_TOOL.to_string());
+    config
+        .provider
+        .api_key
+        .get_or_insert_with(|| "replay".to_string());
+    config
+        .provider
+        .api_base
+        .get_or_insert_with(|| "http://localhost".to_string());
+
+    // 工具都在传入的项目目录中执行，回放不会改变进程的当前目录
+    let fixture_directory = fixture.path.to_string_lossy().to_string();
+    let mut tools = create_configured_tools(fixture_directory.clone(), &config);
+    register_project_tools(&mut tools, &fixture.path);
+    tools.retain(|name| config.tools.is_enabled(name));
+    let mut agent = ReActAgent::new(tools, config, fixture_directory.clone())?
+        .with_recorded_model(RecordedModel::from_trajectory(&recorded));
+
+    let _ = agent.run(&task).await;
+
+    let replayed = Trajectory::load(
+        agent
+            .last_trajectory()
+            .ok_or_else(|| t!("trajectory.not_found", name = name))?,
+    )?;
+    let project_directory = project_dir.to_string_lossy();
+    let report = ReplayReport::compare(&recorded, &replayed, |text| {
+        text.replace(&fixture_directory, &project_directory)
+    });
+    println!("\n{}", report.render());
+
+    if keep_fixture {
+        println!(
+            "{}",
+            t!("replay.fixture_kept", path = fixture.path.display())
+        );
+    }
+    Ok(report.passed())
+}
+
+/// 回放用的项目副本，离开作用域时删除（包括出错返回的情况），除非要求保留
+struct FixtureGuard {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl Drop for FixtureGuard {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// 每条消息显示角色和内容的第一行
+fn print_conversation(agent: &ReActAgent) {
+    let history = agent.conversation_history();
+    if history.is_empty() {
+        println!("{}", t!("repl.no_history"));
+        return;
+    }
+    for (index, message) in history.iter().enumerate() {
+        let value = serde_json::to_value(message).unwrap_or_default();
+        let role = value["role"].as_str().unwrap_or("?");
+        let content = match &value["content"] {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        let first_line = content.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        let preview: String = first_line.chars().take(80).collect();
+        let ellipsis = if content.chars().count() > preview.chars().count() {
+            "…"
+        } else {
+            ""
+        };
+        println!("{:>3}. [{}] {}{}", index + 1, role, preview, ellipsis);
+    }
+}
+
+fn print_current_model(agent: &ReActAgent) {
+    let config = agent.config();
+    match &config.profile {
+        Some(profile) => println!(
+            "{}",
+            t!(
+                "repl.current_model_profile",
+                model = config.model.name,
+                profile = profile,
+                provider = config.provider.name
+            )
+        ),
+        None => println!(
+            "{}",
+            t!(
+                "repl.current_model",
+                model = config.model.name,
+                provider = config.provider.name
+            )
+        ),
+    }
 }
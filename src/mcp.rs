@@ -0,0 +1,97 @@
+//! Model Context Protocol（MCP）支持：基于stdio、按行分隔的JSON-RPC 2.0
+//!
+//! 客户端用于导入外部MCP服务的工具，服务端将本项目的工具注册表提供给其他Agent和编辑器
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+mod client;
+mod server;
+
+pub use client::{load_mcp_tools, McpClient, McpTool, McpToolInfo};
+pub use server::serve;
+
+/// 与服务端协商的协议版本
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+/// 项目级MCP配置文件，格式与常见编辑器的 `mcpServers` 配置一致
+pub const MCP_CONFIG_FILE: &str = ".rust-agent/mcp.json";
+
+/// 单个MCP服务的启动配置
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct McpServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// 工作目录，默认为项目目录
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub disabled: bool,
+    /// 调用该服务的工具前是否需要用户确认，默认只有未声明 `readOnlyHint` 的工具需要
+    #[serde(rename = "requiresApproval", default)]
+    pub requires_approval: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct McpConfigFile {
+    #[serde(rename = "mcpServers", default)]
+    servers: BTreeMap<String, McpServerConfig>,
+}
+
+/// 读取项目中配置的MCP服务，配置文件不存在时返回空列表
+pub fn load_mcp_config(project_directory: &Path) -> AgentResult<BTreeMap<String, McpServerConfig>> {
+    let path = project_directory.join(MCP_CONFIG_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    let config: McpConfigFile = serde_json::from_str(&content).map_err(|e| {
+        AgentError::ParseError(t!("mcp.invalid_config", path = path.display(), error = e))
+    })?;
+    Ok(config
+        .servers
+        .into_iter()
+        .filter(|(_, server)| !server.disabled)
+        .collect())
+}
+
+fn request_message(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification_message(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn result_message(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_message(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// JSON-RPC 标准错误码
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// 写入一条消息，每条消息占一行
+async fn write_message<W>(writer: &mut W, message: &Value) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await
+}
@@ -0,0 +1,899 @@
+use super::{
+    error_message, load_mcp_config, notification_message, request_message, result_message,
+    write_message, McpServerConfig, METHOD_NOT_FOUND, PROTOCOL_VERSION,
+};
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use crate::tools::{Tool, ToolParameter};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// 服务进程意外退出后最多自动重启的次数
+const MAX_RESTARTS: usize = 3;
+
+type PendingRequests = Arc<StdMutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+type SharedWriter = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// 与一个MCP服务之间的连接
+struct Connection {
+    server_name: String,
+    writer: SharedWriter,
+    pending: PendingRequests,
+    next_id: AtomicU64,
+    closed: Arc<AtomicBool>,
+    reader_task: JoinHandle<()>,
+    // 持有子进程，连接释放时随之结束（kill_on_drop）
+    _child: Option<Child>,
+}
+
+impl Connection {
+    fn start<R, W>(server_name: &str, reader: R, writer: W, child: Option<Child>) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(writer)));
+        let pending = PendingRequests::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader_task = tokio::spawn(read_loop(
+            server_name.to_string(),
+            reader,
+            Arc::clone(&writer),
+            Arc::clone(&pending),
+            Arc::clone(&closed),
+        ));
+
+        Self {
+            server_name: server_name.to_string(),
+            writer,
+            pending,
+            next_id: AtomicU64::new(1),
+            closed,
+            reader_task,
+            _child: child,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn closed_error(&self) -> AgentError {
+        AgentError::ToolExecutionError(t!("mcp.connection_closed", server = self.server_name))
+    }
+
+    async fn send(&self, message: &Value) -> AgentResult<()> {
+        let mut writer = self.writer.lock().await;
+        write_message(&mut **writer, message).await.map_err(|e| {
+            self.closed.store(true, Ordering::SeqCst);
+            AgentError::ToolExecutionError(t!(
+                "mcp.send_failed",
+                server = self.server_name,
+                error = e
+            ))
+        })
+    }
+
+    async fn request(&self, method: &str, params: Value) -> AgentResult<Value> {
+        if self.is_closed() {
+            return Err(self.closed_error());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        lock(&self.pending).insert(id, sender);
+
+        if let Err(e) = self.send(&request_message(id, method, params)).await {
+            lock(&self.pending).remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Err(_) => {
+                lock(&self.pending).remove(&id);
+                Err(AgentError::ToolExecutionError(t!(
+                    "mcp.request_timeout",
+                    server = self.server_name,
+                    method = method,
+                    seconds = REQUEST_TIMEOUT.as_secs()
+                )))
+            }
+            Ok(Err(_)) => Err(self.closed_error()),
+            Ok(Ok(Err(message))) => Err(AgentError::ToolExecutionError(t!(
+                "mcp.server_error",
+                server = self.server_name,
+                error = message
+            ))),
+            Ok(Ok(Ok(result))) => Ok(result),
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> AgentResult<()> {
+        self.send(&notification_message(method, params)).await
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+fn lock<T>(mutex: &StdMutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 读取服务端消息：响应交给等待中的请求，服务端发起的请求直接回复
+async fn read_loop<R>(
+    server_name: String,
+    reader: R,
+    writer: SharedWriter,
+    pending: PendingRequests,
+    closed: Arc<AtomicBool>,
+) where
+    R: AsyncRead + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!(
+                    "{}",
+                    t!("mcp.invalid_message", server = server_name, error = e)
+                );
+                continue;
+            }
+        };
+
+        let id = message.get("id").filter(|id| !id.is_null()).cloned();
+        if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+            match id {
+                Some(id) => {
+                    let response = if method == "ping" {
+                        result_message(id, json!({}))
+                    } else {
+                        error_message(id, METHOD_NOT_FOUND, &t!("mcp.unsupported_client_method"))
+                    };
+                    let mut writer = writer.lock().await;
+                    let _ = write_message(&mut **writer, &response).await;
+                }
+                None => tracing::debug!(
+                    "{}",
+                    t!("mcp.notification", server = server_name, method = method)
+                ),
+            }
+            continue;
+        }
+
+        let Some(id) = id.and_then(|id| id.as_u64()) else {
+            continue;
+        };
+        if let Some(sender) = lock(&pending).remove(&id) {
+            let result = match message.get("error") {
+                Some(error) => Err(error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| t!("mcp.unknown_error"))),
+                None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+            };
+            let _ = sender.send(result);
+        }
+    }
+
+    // 连接结束后让所有等待中的请求立即失败
+    closed.store(true, Ordering::SeqCst);
+    lock(&pending).clear();
+}
+
+async fn initialize(connection: &Connection) -> AgentResult<()> {
+    connection
+        .request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "rust-agent", "version": env!("CARGO_PKG_VERSION") },
+            }),
+        )
+        .await?;
+    connection
+        .notify("notifications/initialized", json!({}))
+        .await
+}
+
+/// 启动服务进程并完成初始化握手，服务的stderr输出写入日志
+async fn spawn_connection(
+    server_name: &str,
+    config: &McpServerConfig,
+    project_directory: &Path,
+) -> AgentResult<Connection> {
+    let cwd = match &config.cwd {
+        Some(cwd) => project_directory.join(cwd),
+        None => project_directory.to_path_buf(),
+    };
+    let mut child = Command::new(&config.command)
+        .args(&config.args)
+        .envs(&config.env)
+        .current_dir(cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            AgentError::CommandExecutionError(t!(
+                "mcp.spawn_failed",
+                server = server_name,
+                command = config.command,
+                error = e
+            ))
+        })?;
+
+    let (Some(stdin), Some(stdout), Some(stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        return Err(AgentError::CommandExecutionError(t!(
+            "mcp.stdio_unavailable",
+            server = server_name
+        )));
+    };
+
+    let name = server_name.to_string();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            tracing::info!("[mcp:{}] {}", name, line);
+        }
+    });
+
+    let connection = Connection::start(server_name, stdout, stdin, Some(child));
+    initialize(&connection).await?;
+    Ok(connection)
+}
+
+/// `tools/list` 返回的工具描述
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+    #[serde(default)]
+    pub annotations: McpToolAnnotations,
+}
+
+/// 工具的行为提示，只使用其中的 `readOnlyHint`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct McpToolAnnotations {
+    #[serde(rename = "readOnlyHint", default)]
+    pub read_only_hint: bool,
+}
+
+/// MCP客户端，服务进程意外退出时会在下次调用前自动重启
+pub struct McpClient {
+    name: String,
+    launch: Option<(McpServerConfig, PathBuf)>,
+    connection: Mutex<Arc<Connection>>,
+    restarts: AtomicUsize,
+}
+
+impl McpClient {
+    /// 按配置启动服务进程并建立连接
+    pub async fn spawn(
+        name: &str,
+        config: McpServerConfig,
+        project_directory: &Path,
+    ) -> AgentResult<Arc<Self>> {
+        let connection = spawn_connection(name, &config, project_directory).await?;
+        Ok(Arc::new(Self {
+            name: name.to_string(),
+            launch: Some((config, project_directory.to_path_buf())),
+            connection: Mutex::new(Arc::new(connection)),
+            restarts: AtomicUsize::new(0),
+        }))
+    }
+
+    /// 在已有的读写流上建立连接（不支持重启），用于进程内服务或测试
+    pub async fn connect<R, W>(name: &str, reader: R, writer: W) -> AgentResult<Arc<Self>>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let connection = Connection::start(name, reader, writer, None);
+        initialize(&connection).await?;
+        Ok(Arc::new(Self {
+            name: name.to_string(),
+            launch: None,
+            connection: Mutex::new(Arc::new(connection)),
+            restarts: AtomicUsize::new(0),
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 服务配置中的 `requiresApproval`，未配置时为 `None`
+    fn requires_approval(&self) -> Option<bool> {
+        self.launch
+            .as_ref()
+            .and_then(|(config, _)| config.requires_approval)
+    }
+
+    async fn connection(&self) -> AgentResult<Arc<Connection>> {
+        let mut current = self.connection.lock().await;
+        if !current.is_closed() {
+            return Ok(Arc::clone(&current));
+        }
+
+        let Some((config, project_directory)) = &self.launch else {
+            return Err(current.closed_error());
+        };
+        let restarts = self.restarts.fetch_add(1, Ordering::SeqCst);
+        if restarts >= MAX_RESTARTS {
+            return Err(AgentError::ToolExecutionError(t!(
+                "mcp.restart_limit",
+                server = self.name,
+                count = MAX_RESTARTS
+            )));
+        }
+
+        tracing::warn!(
+            "{}",
+            t!("mcp.restarting", server = self.name, count = restarts + 1)
+        );
+        let connection = Arc::new(spawn_connection(&self.name, config, project_directory).await?);
+        *current = Arc::clone(&connection);
+        Ok(connection)
+    }
+
+    pub async fn list_tools(&self) -> AgentResult<Vec<McpToolInfo>> {
+        let connection = self.connection().await?;
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = connection.request("tools/list", params).await?;
+            let page: Vec<McpToolInfo> =
+                serde_json::from_value(result.get("tools").cloned().unwrap_or(json!([])))?;
+            tools.extend(page);
+
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(|c| c.to_string());
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> AgentResult<Value> {
+        self.connection()
+            .await?
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await
+    }
+}
+
+/// 从JSON Schema中提取参数列表：必填参数在前，其余按声明顺序
+fn schema_parameters(schema: &Value) -> Vec<ToolParameter> {
+    let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) else {
+        return Vec::new();
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    let mut parameters: Vec<ToolParameter> = properties
+        .iter()
+        .map(|(name, property)| {
+            let description = property
+                .get("description")
+                .and_then(|d| d.as_str())
+                .unwrap_or_default();
+            if required.contains(&name.as_str()) {
+                ToolParameter::required(name, description)
+            } else {
+                ToolParameter::optional(name, description)
+            }
+        })
+        .collect();
+    parameters.sort_by_key(|p| !p.required);
+    parameters
+}
+
+/// 按schema中声明的类型转换字符串参数
+fn coerce_argument(value: &str, property: Option<&Value>) -> Value {
+    let declared_type = property
+        .and_then(|p| p.get("type"))
+        .and_then(|t| t.as_str())
+        .unwrap_or("string");
+    match declared_type {
+        "integer" | "number" | "boolean" | "object" | "array" | "null" => {
+            serde_json::from_str(value.trim()).unwrap_or_else(|_| Value::String(value.to_string()))
+        }
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// 将 `tools/call` 的结果整理成观察文本
+fn format_call_result(result: &Value) -> String {
+    let mut parts: Vec<String> = result
+        .get("content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .map(|item| match item.get("type").and_then(|t| t.as_str()) {
+            Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+            Some("image") | Some("audio") => format!(
+                "[{}: {}]",
+                item["type"].as_str().unwrap_or_default(),
+                item["mimeType"]
+                    .as_str()
+                    .map(|t| t.to_string())
+                    .unwrap_or_else(|| t!("mcp.unknown_type"))
+            ),
+            Some("resource") => item["resource"]["text"]
+                .as_str()
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| {
+                    t!(
+                        "mcp.resource",
+                        uri = item["resource"]["uri"].as_str().unwrap_or("")
+                    )
+                }),
+            _ => item.to_string(),
+        })
+        .collect();
+
+    if parts.is_empty() {
+        if let Some(structured) = result.get("structuredContent") {
+            parts.push(serde_json::to_string_pretty(structured).unwrap_or_default());
+        }
+    }
+
+    let text = parts.join("\n");
+    if result.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+        t!("mcp.tool_error", error = text)
+    } else {
+        text
+    }
+}
+
+/// 只保留字母、数字和下划线，保证工具名能被action解析
+fn sanitize_tool_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// 将MCP服务提供的工具包装成 `Tool`，调用转发为 `tools/call` 请求
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    description: String,
+    info: McpToolInfo,
+    parameters: Vec<ToolParameter>,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, info: McpToolInfo) -> Self {
+        let name = sanitize_tool_name(&format!("{}_{}", client.name(), info.name));
+        let description = format!("[MCP:{}] {}", client.name(), info.description);
+        let parameters = schema_parameters(&info.input_schema);
+        Self {
+            client,
+            name,
+            description,
+            info,
+            parameters,
+        }
+    }
+
+    /// 位置参数按参数声明顺序映射；单个JSON对象参数直接作为arguments
+    fn build_arguments(&self, args: Vec<String>) -> AgentResult<Value> {
+        if let [single] = args.as_slice() {
+            if let Ok(Value::Object(object)) = serde_json::from_str::<Value>(single.trim()) {
+                return Ok(Value::Object(object));
+            }
+        }
+
+        if args.len() > self.parameters.len() {
+            return Err(AgentError::ToolExecutionError(t!(
+                "tools.too_many_arguments",
+                tool = self.name,
+                max = self.parameters.len(),
+                actual = args.len()
+            )));
+        }
+        if let Some(missing) = self.parameters.iter().skip(args.len()).find(|p| p.required) {
+            return Err(AgentError::ToolExecutionError(t!(
+                "tools.missing_argument",
+                tool = self.name,
+                name = missing.name
+            )));
+        }
+
+        let properties = self.info.input_schema.get("properties");
+        let arguments = self
+            .parameters
+            .iter()
+            .zip(args.iter())
+            .map(|(parameter, value)| {
+                let property = properties.and_then(|p| p.get(&parameter.name));
+                (parameter.name.clone(), coerce_argument(value, property))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        Ok(Value::Object(arguments))
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        self.parameters.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.info.annotations.read_only_hint
+    }
+
+    /// 未声明只读的工具可能修改状态，默认需要确认；服务配置可以统一覆盖
+    fn requires_approval(&self) -> bool {
+        self.client
+            .requires_approval()
+            .unwrap_or(!self.info.annotations.read_only_hint)
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let arguments = self.build_arguments(args)?;
+        let result = self.client.call_tool(&self.info.name, arguments).await?;
+        Ok(format_call_result(&result))
+    }
+}
+
+/// 启动项目中配置的所有MCP服务并收集它们的工具；单个服务失败不影响其他服务
+pub async fn load_mcp_tools(project_directory: &Path) -> AgentResult<Vec<McpTool>> {
+    let mut tools = Vec::new();
+    for (name, config) in load_mcp_config(project_directory)? {
+        let client = match McpClient::spawn(&name, config, project_directory).await {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("⚠️  {}", t!("mcp.skipped", server = name, error = e));
+                continue;
+            }
+        };
+        match client.list_tools().await {
+            Ok(infos) => tools.extend(
+                infos
+                    .into_iter()
+                    .map(|info| McpTool::new(Arc::clone(&client), info)),
+            ),
+            Err(e) => eprintln!(
+                "⚠️  {}",
+                t!("mcp.list_tools_failed", server = name, error = e)
+            ),
+        }
+    }
+    Ok(tools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolRegistry;
+    use tokio::io::{duplex, split, AsyncWriteExt};
+
+    /// 设置后测试程序重新运行 `fixture_process`，作为子进程形式的MCP服务
+    const FIXTURE_ENV: &str = "RUST_AGENT_MCP_FIXTURE";
+
+    /// 极简的MCP服务：提供 echo、fail 和 exit 三个工具，exit 会直接结束服务
+    async fn fixture_server<R, W>(reader: R, mut writer: W)
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = request.get("id").cloned() else {
+                continue;
+            };
+            let result = match request["method"].as_str().unwrap() {
+                "initialize" => json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "fixture", "version": "0.1.0" },
+                }),
+                "tools/list" => json!({ "tools": [
+                    {
+                        "name": "echo",
+                        "description": "重复文本",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "text": { "type": "string", "description": "要重复的文本" },
+                                "times": { "type": "integer" },
+                            },
+                            "required": ["text"],
+                        },
+                        "annotations": { "readOnlyHint": true },
+                    },
+                    { "name": "fail", "inputSchema": { "type": "object" } },
+                    { "name": "exit", "inputSchema": { "type": "object" } },
+                ]}),
+                "tools/call" => match request["params"]["name"].as_str().unwrap() {
+                    "echo" => {
+                        let arguments = &request["params"]["arguments"];
+                        let times = arguments["times"].as_u64().unwrap_or(1) as usize;
+                        let text = arguments["text"].as_str().unwrap().repeat(times);
+                        json!({ "content": [{ "type": "text", "text": text }] })
+                    }
+                    "fail" => {
+                        json!({ "content": [{ "type": "text", "text": "boom" }], "isError": true })
+                    }
+                    _ => return,
+                },
+                _ => {
+                    let response = error_message(id, METHOD_NOT_FOUND, "unknown");
+                    write_message(&mut writer, &response).await.unwrap();
+                    continue;
+                }
+            };
+            write_message(&mut writer, &result_message(id, result))
+                .await
+                .unwrap();
+        }
+        writer.shutdown().await.ok();
+    }
+
+    /// 只在设置了 `FIXTURE_ENV` 的子进程中运行，通过标准输入输出提供fixture服务
+    #[test]
+    fn fixture_process() {
+        if std::env::var_os(FIXTURE_ENV).is_none() {
+            return;
+        }
+        eprintln!("fixture started");
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fixture_server(tokio::io::stdin(), tokio::io::stdout()));
+        std::process::exit(0);
+    }
+
+    /// 以子进程启动fixture服务的配置；测试框架输出的非JSON行会被客户端忽略
+    fn fixture_config() -> McpServerConfig {
+        McpServerConfig {
+            command: std::env::current_exe().unwrap().display().to_string(),
+            args: [
+                "mcp::client::tests::fixture_process",
+                "--exact",
+                "--nocapture",
+                "--quiet",
+                "--test-threads=1",
+            ]
+            .map(String::from)
+            .to_vec(),
+            env: HashMap::from([(FIXTURE_ENV.to_string(), "1".to_string())]),
+            cwd: None,
+            disabled: false,
+            requires_approval: None,
+        }
+    }
+
+    async fn spawn_fixture(config: McpServerConfig) -> Arc<McpClient> {
+        McpClient::spawn("fixture", config, &std::env::temp_dir())
+            .await
+            .unwrap()
+    }
+
+    fn tool(client: &Arc<McpClient>, name: &str) -> McpTool {
+        McpTool::new(
+            Arc::clone(client),
+            McpToolInfo {
+                name: name.to_string(),
+                description: String::new(),
+                input_schema: json!({}),
+                annotations: Default::default(),
+            },
+        )
+    }
+
+    /// 收集日志输出，用于检查服务的stderr是否写入日志
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<StdMutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            lock(&self.0).extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn connect_fixture() -> Arc<McpClient> {
+        let (client_stream, server_stream) = duplex(64 * 1024);
+        let (server_reader, server_writer) = split(server_stream);
+        tokio::spawn(fixture_server(server_reader, server_writer));
+        let (reader, writer) = split(client_stream);
+        McpClient::connect("fixture", reader, writer).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_registers_and_calls_mcp_tools() {
+        let client = connect_fixture().await;
+        let infos = client.list_tools().await.unwrap();
+        assert_eq!(infos.len(), 3);
+
+        let mut registry = ToolRegistry::new();
+        for info in infos {
+            registry.register(McpTool::new(Arc::clone(&client), info));
+        }
+
+        let echo = registry.get_tool("fixture_echo").unwrap();
+        assert_eq!(echo.description(), "[MCP:fixture] 重复文本");
+        let parameters = echo.parameters();
+        assert_eq!(parameters[0].name, "text");
+        assert!(parameters[0].required);
+        assert!(!parameters[1].required);
+        assert!(echo.is_read_only());
+        assert!(!echo.requires_approval());
+        let fail = registry.get_tool("fixture_fail").unwrap();
+        assert!(!fail.is_read_only());
+        assert!(fail.requires_approval());
+
+        let output = echo
+            .execute(vec!["ab".to_string(), "3".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(output, "ababab");
+
+        let output = echo
+            .execute(vec![r#"{"text": "x", "times": 2}"#.to_string()])
+            .await
+            .unwrap();
+        assert_eq!(output, "xx");
+
+        assert!(echo.execute(vec![]).await.is_err());
+
+        let fail = registry.get_tool("fixture_fail").unwrap();
+        assert_eq!(
+            fail.execute(vec![]).await.unwrap(),
+            t!("mcp.tool_error", error = "boom")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_closed_connection() {
+        let client = connect_fixture().await;
+        let exit = tool(&client, "exit");
+
+        let error = exit.execute(vec![]).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains(&t!("mcp.connection_closed", server = "fixture")));
+        assert!(client.list_tools().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spawned_server_calls_tools_and_logs_stderr() {
+        let logs = LogBuffer::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = spawn_fixture(fixture_config()).await;
+        assert_eq!(client.list_tools().await.unwrap().len(), 3);
+        let echo = tool(&client, "echo");
+        assert_eq!(
+            echo.execute(vec![r#"{"text": "ab", "times": 2}"#.to_string()])
+                .await
+                .unwrap(),
+            "abab"
+        );
+        assert!(tool(&client, "fail").requires_approval());
+
+        let expected = "[mcp:fixture] fixture started";
+        let logged = async {
+            while !String::from_utf8_lossy(&lock(&logs.0)).contains(expected) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        assert!(tokio::time::timeout(Duration::from_secs(5), logged)
+            .await
+            .is_ok());
+
+        // 服务配置可以覆盖工具默认的确认要求
+        let mut config = fixture_config();
+        config.requires_approval = Some(false);
+        let client = spawn_fixture(config).await;
+        assert!(!tool(&client, "fail").requires_approval());
+    }
+
+    #[tokio::test]
+    async fn test_restarts_crashed_server() {
+        let client = spawn_fixture(fixture_config()).await;
+        let exit = tool(&client, "exit");
+        let echo = tool(&client, "echo");
+
+        let error = exit.execute(vec![]).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains(&t!("mcp.connection_closed", server = "fixture")));
+        assert_eq!(
+            echo.execute(vec![r#"{"text": "again"}"#.to_string()])
+                .await
+                .unwrap(),
+            "again"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stops_restarting_after_limit() {
+        let client = spawn_fixture(fixture_config()).await;
+        let exit = tool(&client, "exit");
+        let echo = tool(&client, "echo");
+        let arguments = vec![r#"{"text": "x"}"#.to_string()];
+
+        for _ in 0..MAX_RESTARTS {
+            assert!(exit.execute(vec![]).await.is_err());
+            assert_eq!(echo.execute(arguments.clone()).await.unwrap(), "x");
+        }
+        assert!(exit.execute(vec![]).await.is_err());
+        let error = echo.execute(arguments).await.unwrap_err();
+        assert!(error.to_string().contains(&t!(
+            "mcp.restart_limit",
+            server = "fixture",
+            count = MAX_RESTARTS
+        )));
+    }
+}
@@ -0,0 +1,351 @@
+use super::{
+    error_message, result_message, write_message, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_NOT_FOUND, PARSE_ERROR, PROTOCOL_VERSION,
+};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::t;
+use crate::tools::{Tool, ToolParameter, ToolRegistry};
+use serde_json::{json, Map, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+
+/// 根据工具声明的参数生成输入schema；未声明参数的工具接受一个字符串数组 `args`
+fn input_schema(tool: &dyn Tool) -> Value {
+    let parameters = tool.parameters();
+    if parameters.is_empty() {
+        return json!({
+            "type": "object",
+            "properties": {
+                "args": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": prompt_text("tools.prompt.positional_args"),
+                },
+            },
+        });
+    }
+
+    let mut properties = Map::new();
+    for parameter in &parameters {
+        let property = if parameter.repeated {
+            json!({
+                "type": "array",
+                "items": { "type": "string" },
+                "description": parameter.description,
+            })
+        } else {
+            json!({ "type": "string", "description": parameter.description })
+        };
+        properties.insert(parameter.name.clone(), property);
+    }
+    let required: Vec<&str> = parameters
+        .iter()
+        .filter(|p| p.required)
+        .map(|p| p.name.as_str())
+        .collect();
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn argument_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// 将命名参数转换为工具的位置参数；中间缺省的可选参数以空字符串占位
+fn positional_arguments(
+    parameters: &[ToolParameter],
+    arguments: &Map<String, Value>,
+) -> Result<Vec<String>, String> {
+    if parameters.is_empty() {
+        return match arguments.get("args") {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(values)) => Ok(values.iter().map(argument_to_string).collect()),
+            Some(other) => Ok(vec![argument_to_string(other)]),
+        };
+    }
+
+    if let Some(unknown) = arguments
+        .keys()
+        .find(|key| !parameters.iter().any(|p| &p.name == *key))
+    {
+        return Err(t!("mcp.unknown_argument", name = unknown));
+    }
+
+    let mut args = Vec::new();
+    let mut provided_len = 0;
+    for parameter in parameters {
+        match arguments.get(&parameter.name) {
+            None | Some(Value::Null) if parameter.required => {
+                return Err(t!("mcp.missing_argument", name = parameter.name));
+            }
+            None | Some(Value::Null) => args.push(String::new()),
+            Some(Value::Array(values)) if parameter.repeated => {
+                if parameter.required && values.is_empty() {
+                    return Err(t!("mcp.empty_argument", name = parameter.name));
+                }
+                args.extend(values.iter().map(argument_to_string));
+                provided_len = args.len();
+            }
+            Some(value) => {
+                args.push(argument_to_string(value));
+                provided_len = args.len();
+            }
+        }
+    }
+    args.truncate(provided_len);
+    Ok(args)
+}
+
+fn tool_result(text: String, is_error: bool) -> Value {
+    json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+}
+
+async fn call_tool(registry: &ToolRegistry, params: &Value) -> Result<Value, (i64, String)> {
+    let name = params
+        .get("name")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| (INVALID_PARAMS, t!("mcp.missing_tool_name")))?;
+    let tool = registry
+        .get_tool(name)
+        .ok_or_else(|| (INVALID_PARAMS, t!("mcp.unknown_tool", name = name)))?;
+
+    let empty = Map::new();
+    let arguments = match params.get("arguments") {
+        None | Some(Value::Null) => &empty,
+        Some(Value::Object(arguments)) => arguments,
+        Some(_) => return Err((INVALID_PARAMS, t!("mcp.arguments_not_object"))),
+    };
+    let args = positional_arguments(&tool.parameters(), arguments)
+        .map_err(|message| (INVALID_PARAMS, format!("{}: {}", name, message)))?;
+
+    // 需要确认的工具按审批策略处理，服务模式下不会读取stdin
+    if tool.requires_approval() {
+        let question = t!("mcp.confirm_call", tool = name, args = args.join(", "));
+        if !registry
+            .approval_for(name)
+            .confirm(&question)
+            .unwrap_or(false)
+        {
+            return Ok(tool_result(t!("mcp.call_rejected", tool = name), true));
+        }
+    }
+
+    Ok(match tool.execute(args).await {
+        Ok(output) => tool_result(output, false),
+        Err(e) => tool_result(e.to_string(), true),
+    })
+}
+
+async fn handle_request(
+    registry: &ToolRegistry,
+    method: &str,
+    params: &Value,
+) -> Result<Value, (i64, String)> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": { "listChanged": false } },
+            "serverInfo": { "name": "rust-agent", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => {
+            let tools: Vec<Value> = registry
+                .tools()
+                .into_iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name(),
+                        "description": tool.description(),
+                        "inputSchema": input_schema(tool),
+                        "annotations": { "readOnlyHint": tool.is_read_only() },
+                    })
+                })
+                .collect();
+            Ok(json!({ "tools": tools }))
+        }
+        "tools/call" => call_tool(registry, params).await,
+        _ => Err((
+            METHOD_NOT_FOUND,
+            t!("mcp.unsupported_method", method = method),
+        )),
+    }
+}
+
+/// 以MCP服务的形式提供注册表中的工具，直到输入流结束
+///
+/// stdin/stdout 用于协议通信，因此注册表的审批策略不能是交互式的
+pub async fn serve<R, W>(registry: &ToolRegistry, reader: R, mut writer: W) -> AgentResult<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    if registry.approval().is_interactive() {
+        return Err(AgentError::RuntimeError(t!("mcp.interactive_approval")));
+    }
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                let response = error_message(Value::Null, PARSE_ERROR, &e.to_string());
+                write_message(&mut writer, &response).await?;
+                continue;
+            }
+        };
+
+        let id = message.get("id").filter(|id| !id.is_null()).cloned();
+        let method = message.get("method").and_then(|m| m.as_str());
+        let response = match (id, method) {
+            (Some(id), Some(method)) => {
+                let params = message.get("params").cloned().unwrap_or(Value::Null);
+                match handle_request(registry, method, &params).await {
+                    Ok(result) => result_message(id, result),
+                    Err((code, error)) => error_message(id, code, &error),
+                }
+            }
+            // 通知（如 notifications/initialized）不需要回复
+            (None, Some(method)) => {
+                tracing::debug!("{}", t!("mcp.received_notification", method = method));
+                continue;
+            }
+            (Some(id), None)
+                if message.get("result").is_none() && message.get("error").is_none() =>
+            {
+                error_message(id, INVALID_REQUEST, &t!("mcp.missing_method"))
+            }
+            _ => continue,
+        };
+        write_message(&mut writer, &response).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::ApprovalPolicy;
+    use crate::config::AgentConfig;
+    use crate::mcp::{McpClient, McpTool};
+    use crate::tools::create_configured_tools;
+    use std::sync::Arc;
+    use tokio::io::{duplex, split};
+
+    async fn serve_project(
+        project: &tempfile::TempDir,
+        approval: ApprovalPolicy,
+    ) -> Arc<McpClient> {
+        let config = AgentConfig {
+            approval: approval.into(),
+            ..AgentConfig::default()
+        };
+        let registry = create_configured_tools(project.path().display().to_string(), &config);
+        let (client_stream, server_stream) = duplex(64 * 1024);
+        tokio::spawn(async move {
+            let (reader, writer) = split(server_stream);
+            serve(&registry, reader, writer).await.unwrap();
+        });
+        let (reader, writer) = split(client_stream);
+        McpClient::connect("agent", reader, writer).await.unwrap()
+    }
+
+    async fn call(client: &Arc<McpClient>, name: &str, arguments: Value) -> Value {
+        client.call_tool(name, arguments).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serves_registry_tools() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("hello.txt"), "hi there").unwrap();
+        let client = serve_project(&project, ApprovalPolicy::Reject).await;
+
+        let infos = client.list_tools().await.unwrap();
+        let read_file = infos.iter().find(|t| t.name == "read_file").unwrap();
+        assert_eq!(read_file.input_schema["required"], json!(["path"]));
+
+        // 通过MCP客户端包装后与本地调用方式一致
+        let tool = McpTool::new(Arc::clone(&client), read_file.clone());
+        let output = tool.execute(vec!["hello.txt".to_string()]).await.unwrap();
+        assert!(output.contains("hi there"));
+
+        // 路径限制同样生效
+        let result = call(&client, "read_file", json!({ "path": "/etc/hostname" })).await;
+        assert_eq!(result["isError"], json!(true));
+
+        // 缺少必填参数和未知工具属于协议错误
+        assert!(client.call_tool("read_file", json!({})).await.is_err());
+        assert!(client.call_tool("missing", json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_applies_approval_policy() {
+        let project = tempfile::tempdir().unwrap();
+        let rejecting = serve_project(&project, ApprovalPolicy::Reject).await;
+
+        let result = call(
+            &rejecting,
+            "run_terminal_command",
+            json!({ "command": "touch x" }),
+        )
+        .await;
+        assert_eq!(result["isError"], json!(true));
+        let result = call(
+            &rejecting,
+            "write_to_file",
+            json!({ "path": "a.txt", "content": "new" }),
+        )
+        .await;
+        assert!(result["content"][0]["text"].as_str().unwrap().contains(&t!(
+            "tools.all_rejected",
+            path = "a.txt",
+            rejected = ""
+        )));
+        assert!(!project.path().join("a.txt").exists());
+
+        let approving = serve_project(&project, ApprovalPolicy::AutoApprove).await;
+        let result = call(
+            &approving,
+            "write_to_file",
+            json!({ "path": "nested/a.txt", "content": "new" }),
+        )
+        .await;
+        assert_eq!(result["isError"], json!(false));
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("nested/a.txt")).unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn test_maps_named_arguments_to_positions() {
+        let parameters = vec![
+            ToolParameter::required("message", ""),
+            ToolParameter::optional("staged", ""),
+            ToolParameter::required("paths", "").repeated(),
+        ];
+        let arguments = json!({ "message": "fix", "paths": ["a.rs", "b.rs"] });
+        assert_eq!(
+            positional_arguments(&parameters, arguments.as_object().unwrap()).unwrap(),
+            vec!["fix", "", "a.rs", "b.rs"]
+        );
+
+        let parameters = vec![
+            ToolParameter::optional("path", ""),
+            ToolParameter::optional("staged", ""),
+        ];
+        let arguments = json!({ "path": "src" });
+        assert_eq!(
+            positional_arguments(&parameters, arguments.as_object().unwrap()).unwrap(),
+            vec!["src"]
+        );
+        let arguments = json!({ "other": 1 });
+        assert!(positional_arguments(&parameters, arguments.as_object().unwrap()).is_err());
+    }
+}
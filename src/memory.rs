@@ -0,0 +1,344 @@
+//! 项目记忆：模型通过 `remember` 记下的事实（如"测试需要 DATABASE_URL"），跨会话保存在项目目录中，
+//! 与当前任务相关的记忆会加入系统提示词
+
+use crate::errors::{AgentError, AgentResult};
+use crate::prompt_template::today;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 记忆文件，相对于项目目录
+pub const MEMORY_FILE: &str = ".rust-agent/memory.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: usize,
+    pub content: String,
+    /// 记下的日期，格式为 YYYY-MM-DD
+    pub created: String,
+}
+
+/// 记忆文件的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemoryFile {
+    /// 下一条记忆的编号；删除过的编号不再使用，以免对话中旧的编号指向别的记忆
+    next_id: usize,
+    memories: Vec<Memory>,
+}
+
+/// 兼容只保存了记忆列表的旧格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMemories {
+    File(MemoryFile),
+    Legacy(Vec<Memory>),
+}
+
+impl Default for MemoryFile {
+    fn default() -> Self {
+        Self {
+            next_id: 1,
+            memories: Vec::new(),
+        }
+    }
+}
+
+impl MemoryFile {
+    fn parse(content: &str) -> serde_json::Result<Self> {
+        let mut file = match serde_json::from_str(content)? {
+            StoredMemories::File(file) => file,
+            StoredMemories::Legacy(memories) => Self {
+                next_id: 0,
+                memories,
+            },
+        };
+        let after_last = file.memories.iter().map(|m| m.id + 1).max().unwrap_or(1);
+        file.next_id = file.next_id.max(after_last);
+        Ok(file)
+    }
+}
+
+/// 项目的记忆，在Agent和记忆工具之间共享，每次修改后写入记忆文件
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    path: PathBuf,
+    file: Arc<Mutex<MemoryFile>>,
+    /// 记忆文件无法读取或解析时的错误；此时不再写入，以免覆盖文件中保存的记忆
+    load_error: Option<String>,
+}
+
+impl MemoryStore {
+    /// 读取项目中保存的记忆，文件不存在时为空；文件无法解析时给出警告，记忆在修复前只读
+    pub fn new(project_dir: impl AsRef<Path>) -> Self {
+        let path = project_dir.as_ref().join(MEMORY_FILE);
+        let loaded = match fs::read_to_string(&path) {
+            Ok(content) => MemoryFile::parse(&content).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MemoryFile::default()),
+            Err(e) => Err(e.to_string()),
+        };
+        let (file, load_error) = match loaded {
+            Ok(file) => (file, None),
+            Err(error) => {
+                eprintln!(
+                    "⚠️  {}",
+                    t!("memory.unreadable", path = path.display(), error = error)
+                );
+                (MemoryFile::default(), Some(error))
+            }
+        };
+        Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+            load_error,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryFile> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 记忆文件无法解析时拒绝修改
+    fn check_writable(&self) -> AgentResult<()> {
+        match &self.load_error {
+            Some(error) => Err(AgentError::ToolExecutionError(t!(
+                "memory.read_only",
+                path = self.path.display(),
+                error = error
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// 全部记忆，按记下的先后排列
+    pub fn list(&self) -> Vec<Memory> {
+        self.lock().memories.clone()
+    }
+
+    /// 记下一条事实；内容相同的记忆已存在时直接返回它
+    pub fn remember(&self, content: &str) -> AgentResult<Memory> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(AgentError::ToolExecutionError(t!("memory.empty")));
+        }
+        self.check_writable()?;
+        let mut file = self.lock();
+        if let Some(existing) = file.memories.iter().find(|m| m.content == content) {
+            return Ok(existing.clone());
+        }
+        let memory = Memory {
+            id: file.next_id,
+            content: content.to_string(),
+            created: today(),
+        };
+        let mut updated = file.clone();
+        updated.memories.push(memory.clone());
+        updated.next_id += 1;
+        self.save(&updated)?;
+        *file = updated;
+        Ok(memory)
+    }
+
+    /// 删除指定编号的记忆并返回它
+    pub fn forget(&self, id: usize) -> AgentResult<Memory> {
+        self.check_writable()?;
+        let mut file = self.lock();
+        let index = file
+            .memories
+            .iter()
+            .position(|m| m.id == id)
+            .ok_or_else(|| AgentError::ToolExecutionError(t!("memory.not_found", id = id)))?;
+        let mut updated = file.clone();
+        let memory = updated.memories.remove(index);
+        self.save(&updated)?;
+        *file = updated;
+        Ok(memory)
+    }
+
+    /// 删除全部记忆，编号继续递增
+    pub fn clear(&self) -> AgentResult<()> {
+        self.check_writable()?;
+        let mut file = self.lock();
+        let updated = MemoryFile {
+            next_id: file.next_id,
+            memories: Vec::new(),
+        };
+        self.save(&updated)?;
+        *file = updated;
+        Ok(())
+    }
+
+    /// 与查询有共同词语的记忆，按相关程度排列；查询为空时返回全部记忆，新的在前
+    pub fn recall(&self, query: &str) -> Vec<Memory> {
+        let query: HashSet<String> = terms(query).into_iter().collect();
+        let file = self.lock();
+        if query.is_empty() {
+            return file.memories.iter().rev().cloned().collect();
+        }
+        let mut scored: Vec<(usize, &Memory)> = file
+            .memories
+            .iter()
+            .map(|memory| {
+                let score = terms(&memory.content)
+                    .into_iter()
+                    .collect::<HashSet<_>>()
+                    .intersection(&query)
+                    .count();
+                (score, memory)
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect();
+        scored.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(b.id.cmp(&a.id)));
+        scored
+            .into_iter()
+            .map(|(_, memory)| memory.clone())
+            .collect()
+    }
+
+    /// 加入系统提示词的记忆：先取与任务相关的，再用最近记下的补足，最多 `limit` 条，按记下的先后排列
+    pub fn relevant(&self, task: &str, limit: usize) -> Vec<Memory> {
+        let mut selected = self.recall(task);
+        selected.truncate(limit);
+        for memory in self.recall("") {
+            if selected.len() >= limit {
+                break;
+            }
+            if !selected.iter().any(|m| m.id == memory.id) {
+                selected.push(memory);
+            }
+        }
+        selected.sort_by_key(|m| m.id);
+        selected
+    }
+
+    fn save(&self, file: &MemoryFile) -> AgentResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(file)?)?;
+        Ok(())
+    }
+}
+
+/// 用于匹配的词语：英文和数字按单词（转为小写），中文等其他文字按相邻两个字
+pub(crate) fn terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+    {
+        let mut ascii = String::new();
+        let mut others: Vec<char> = Vec::new();
+        for c in word.chars().chain(std::iter::once(' ')) {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                ascii.push(c.to_ascii_lowercase());
+            } else if !ascii.is_empty() {
+                terms.push(std::mem::take(&mut ascii));
+            }
+            if c != ' ' && !c.is_ascii() {
+                others.push(c);
+            } else {
+                push_bigrams(&mut terms, &std::mem::take(&mut others));
+            }
+        }
+    }
+    terms
+}
+
+pub(crate) fn push_bigrams(terms: &mut Vec<String>, chars: &[char]) {
+    match chars {
+        [] => {}
+        [c] => terms.push(c.to_string()),
+        _ => terms.extend(chars.windows(2).map(|pair| pair.iter().collect())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(
+            terms("Tests need DATABASE_URL, 使用pnpm"),
+            vec!["tests", "need", "database_url", "使用", "pnpm"]
+        );
+        assert_eq!(terms("数据库测试"), vec!["数据", "据库", "库测", "测试"]);
+    }
+
+    #[test]
+    fn test_remember_recall_and_forget() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path());
+        let database = store.remember("测试需要设置 DATABASE_URL").unwrap();
+        let pnpm = store.remember("使用 pnpm 而不是 npm").unwrap();
+        store.remember("提交信息使用英文").unwrap();
+        assert_eq!(store.remember(" 使用 pnpm 而不是 npm ").unwrap(), pnpm);
+        assert!(store.remember("  ").is_err());
+
+        let recalled = store.recall("怎么运行测试？database_url 是什么");
+        assert_eq!(recalled[0], database);
+        assert!(store.recall("cargo").is_empty());
+        assert_eq!(store.recall("").len(), 3);
+
+        // 相关的记忆优先，数量不足时用最近的补足
+        let relevant = store.relevant("安装依赖用 npm", 2);
+        assert_eq!(
+            relevant.iter().map(|m| m.id).collect::<Vec<_>>(),
+            vec![pnpm.id, 3]
+        );
+
+        // 重新读取记忆文件后编号继续递增，删除的最新编号也不会再用
+        store.forget(3).unwrap();
+        assert!(store.forget(3).is_err());
+        let reloaded = MemoryStore::new(dir.path());
+        assert_eq!(reloaded.list().len(), 2);
+        assert_eq!(reloaded.remember("新的事实").unwrap().id, 4);
+
+        reloaded.clear().unwrap();
+        let cleared = MemoryStore::new(dir.path());
+        assert!(cleared.list().is_empty());
+        assert_eq!(cleared.remember("清除后的事实").unwrap().id, 5);
+    }
+
+    #[test]
+    fn test_reads_legacy_memory_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MEMORY_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"[{"id": 2, "content": "使用 pnpm", "created": "2024-01-01"}]"#,
+        )
+        .unwrap();
+
+        let store = MemoryStore::new(dir.path());
+        assert_eq!(store.list()[0].content, "使用 pnpm");
+        assert_eq!(store.remember("新的事实").unwrap().id, 3);
+    }
+
+    #[test]
+    fn test_unreadable_memory_file_is_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MEMORY_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let content = r#"{"next_id": 2, "memories": [{"id": 1, "content": "a",}]}"#;
+        fs::write(&path, content).unwrap();
+
+        let store = MemoryStore::new(dir.path());
+        assert!(store.list().is_empty());
+        for result in [
+            store.remember("新的事实").map(|_| ()),
+            store.forget(1).map(|_| ()),
+            store.clear(),
+        ] {
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains(&path.display().to_string()));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+}
@@ -0,0 +1,363 @@
+//! 先规划后执行：模型给出编号的步骤，用户确认后逐步执行，执行中通过 `update_plan` 工具更新进度。
+//! 计划保存在项目目录中，中断后可以从第一个未完成的步骤继续
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 计划文件，相对于项目目录
+pub const PLAN_FILE: &str = ".rust-agent/plan.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    #[default]
+    Pending,
+    InProgress,
+    Completed,
+}
+
+impl StepStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "pending" => Some(Self::Pending),
+            "in_progress" => Some(Self::InProgress),
+            "completed" | "done" => Some(Self::Completed),
+            _ => None,
+        }
+    }
+
+    fn marker(self) -> &'static str {
+        match self {
+            Self::Pending => "[ ]",
+            Self::InProgress => "[>]",
+            Self::Completed => "[x]",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub description: String,
+    #[serde(default)]
+    pub status: StepStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    /// 用户最初描述的任务
+    pub task: String,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    pub fn new(task: &str, steps: Vec<String>) -> Self {
+        Self {
+            task: task.to_string(),
+            steps: steps
+                .into_iter()
+                .map(|description| PlanStep {
+                    description,
+                    status: StepStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    /// 从文本中取出列表项作为步骤，支持 `1.`、`1)`、`-`、`*` 开头的行；
+    /// 没有任何列表项时每个非空行是一步
+    pub fn parse_steps(text: &str) -> Vec<String> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let items: Vec<String> = lines
+            .iter()
+            .filter_map(|line| list_item(line))
+            .map(str::to_string)
+            .collect();
+        if items.is_empty() {
+            lines.into_iter().map(str::to_string).collect()
+        } else {
+            items
+        }
+    }
+
+    /// 第一个未完成步骤的下标
+    pub fn first_unfinished(&self) -> Option<usize> {
+        self.steps
+            .iter()
+            .position(|step| step.status != StepStatus::Completed)
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.status == StepStatus::Completed)
+            .count()
+    }
+
+    /// 更新第 `number` 步（从 1 开始）的状态，可同时修改描述；`number` 为步骤数加一时追加新步骤
+    pub fn update_step(
+        &mut self,
+        number: usize,
+        status: StepStatus,
+        description: Option<&str>,
+    ) -> AgentResult<()> {
+        let description = description.map(str::trim).filter(|d| !d.is_empty());
+        if number == self.steps.len() + 1 {
+            let description = description.ok_or_else(|| {
+                AgentError::ToolExecutionError(t!("plan.new_step_needs_description"))
+            })?;
+            self.steps.push(PlanStep {
+                description: description.to_string(),
+                status,
+            });
+            return Ok(());
+        }
+        let count = self.steps.len();
+        let step = number
+            .checked_sub(1)
+            .and_then(|index| self.steps.get_mut(index))
+            .ok_or_else(|| {
+                AgentError::ToolExecutionError(t!(
+                    "plan.invalid_step",
+                    step = number,
+                    count = count
+                ))
+            })?;
+        step.status = status;
+        if let Some(description) = description {
+            step.description = description.to_string();
+        }
+        Ok(())
+    }
+
+    /// 带完成标记的编号列表，如 `[x] 1. 添加配置项`
+    pub fn render(&self) -> String {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                format!(
+                    "{} {}. {}",
+                    step.status.marker(),
+                    index + 1,
+                    step.description
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// 去掉列表项前的编号或符号，不是列表项时返回 None
+fn list_item(line: &str) -> Option<&str> {
+    let item = match line.strip_prefix(['-', '*']) {
+        Some(rest) => rest,
+        None => {
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return None;
+            }
+            line[digits..].strip_prefix(['.', ')', '、'])?
+        }
+    };
+    // 模型常在步骤前带上复选框
+    let item = item.trim_start();
+    let item = ["[ ]", "[x]", "[>]"]
+        .iter()
+        .find_map(|marker| item.strip_prefix(marker))
+        .unwrap_or(item)
+        .trim();
+    (!item.is_empty()).then_some(item)
+}
+
+/// 当前计划，在Agent和 `update_plan` 工具之间共享，每次修改后写入计划文件
+#[derive(Debug, Clone)]
+pub struct PlanTracker {
+    path: PathBuf,
+    plan: Arc<Mutex<Option<Plan>>>,
+    /// 计划文件无法读取或解析时的错误；修复或放弃计划前不写入，以免覆盖中断的计划
+    load_error: Arc<Mutex<Option<String>>>,
+}
+
+impl PlanTracker {
+    /// 读取项目中保存的计划，文件不存在时没有计划；文件无法解析时给出警告
+    pub fn new(project_dir: impl AsRef<Path>) -> Self {
+        let path = project_dir.as_ref().join(PLAN_FILE);
+        let loaded = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        };
+        let (plan, load_error) = match loaded {
+            Ok(plan) => (plan, None),
+            Err(error) => {
+                eprintln!(
+                    "⚠️  {}",
+                    t!("plan.unreadable", path = path.display(), error = error)
+                );
+                (None, Some(error))
+            }
+        };
+        Self {
+            path,
+            plan: Arc::new(Mutex::new(plan)),
+            load_error: Arc::new(Mutex::new(load_error)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Plan>> {
+        self.plan.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 计划文件无法解析时拒绝写入新的计划
+    pub fn check_writable(&self) -> AgentResult<()> {
+        match &*self.load_error.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(error) => Err(AgentError::RuntimeError(t!(
+                "plan.read_only",
+                path = self.path.display(),
+                error = error
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn current(&self) -> Option<Plan> {
+        self.lock().clone()
+    }
+
+    /// 设置新的计划，替换之前的计划
+    pub fn set(&self, plan: Plan) -> AgentResult<()> {
+        self.check_writable()?;
+        self.save(&plan)?;
+        *self.lock() = Some(plan);
+        Ok(())
+    }
+
+    /// 修改当前计划并保存，返回修改后的计划
+    pub fn update(&self, change: impl FnOnce(&mut Plan) -> AgentResult<()>) -> AgentResult<Plan> {
+        let mut guard = self.lock();
+        let plan = guard
+            .as_mut()
+            .ok_or_else(|| AgentError::ToolExecutionError(t!("plan.no_plan")))?;
+        let mut updated = plan.clone();
+        change(&mut updated)?;
+        self.save(&updated)?;
+        *plan = updated.clone();
+        Ok(updated)
+    }
+
+    /// 放弃当前计划并删除计划文件，无法解析的计划文件也一并删除
+    pub fn clear(&self) -> AgentResult<()> {
+        *self.lock() = None;
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        *self.load_error.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    fn save(&self, plan: &Plan) -> AgentResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(plan)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_steps() {
+        let text = "计划如下：\n\n1. 阅读 config.rs\n2) 添加配置项\n   \n3、编写测试\n完成后汇报";
+        assert_eq!(
+            Plan::parse_steps(text),
+            vec!["阅读 config.rs", "添加配置项", "编写测试"]
+        );
+        assert_eq!(
+            Plan::parse_steps("- [ ] 第一步\n* [x] 第二步"),
+            vec!["第一步", "第二步"]
+        );
+        assert_eq!(
+            Plan::parse_steps("第一步\n\n第二步"),
+            vec!["第一步", "第二步"]
+        );
+        assert!(Plan::parse_steps("  \n").is_empty());
+    }
+
+    #[test]
+    fn test_update_steps_and_resume_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = PlanTracker::new(dir.path());
+        assert!(tracker.current().is_none());
+        assert!(tracker.update(|_| Ok(())).is_err());
+
+        let plan = Plan::new("重构", vec!["读代码".to_string(), "改代码".to_string()]);
+        tracker.set(plan).unwrap();
+        tracker
+            .update(|plan| plan.update_step(1, StepStatus::Completed, None))
+            .unwrap();
+        tracker
+            .update(|plan| plan.update_step(2, StepStatus::InProgress, Some("改 agent.rs")))
+            .unwrap();
+        tracker
+            .update(|plan| plan.update_step(3, StepStatus::Pending, Some("跑测试")))
+            .unwrap();
+        assert!(tracker
+            .update(|plan| plan.update_step(5, StepStatus::Pending, Some("跳过")))
+            .is_err());
+        assert!(tracker
+            .update(|plan| plan.update_step(4, StepStatus::Pending, None))
+            .is_err());
+
+        // 中断后重新读取计划文件，从第一个未完成的步骤继续
+        let plan = PlanTracker::new(dir.path()).current().unwrap();
+        assert_eq!(plan.first_unfinished(), Some(1));
+        assert_eq!(plan.completed_count(), 1);
+        assert_eq!(
+            plan.render(),
+            "[x] 1. 读代码\n[>] 2. 改 agent.rs\n[ ] 3. 跑测试"
+        );
+
+        tracker.clear().unwrap();
+        assert!(PlanTracker::new(dir.path()).current().is_none());
+    }
+
+    #[test]
+    fn test_unreadable_plan_file_is_reported_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PLAN_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{\"task\": \"重构\",").unwrap();
+
+        let tracker = PlanTracker::new(dir.path());
+        assert!(tracker.current().is_none());
+        let error = tracker
+            .set(Plan::new("新任务", vec!["第一步".to_string()]))
+            .unwrap_err();
+        assert!(error.to_string().contains(&path.display().to_string()));
+        assert!(tracker.check_writable().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"task\": \"重构\",");
+
+        // 明确放弃后可以制定新的计划
+        tracker.clear().unwrap();
+        tracker
+            .set(Plan::new("新任务", vec!["第一步".to_string()]))
+            .unwrap();
+        assert_eq!(
+            PlanTracker::new(dir.path()).current().unwrap().task,
+            "新任务"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! 项目上下文：说明文件、受限的目录树和项目类型，渲染到系统提示词中
+
+use crate::config::ContextConfig;
+use crate::pt;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::path::Path;
+
+/// 嵌套说明文件最多查找到的目录深度
+const NESTED_INSTRUCTIONS_DEPTH: usize = 5;
+
+/// 项目中的一个说明文件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstructionFile {
+    /// 相对于项目目录的路径
+    pub path: String,
+    pub content: String,
+}
+
+/// 识别出的项目类型及常用命令
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectType {
+    pub name: String,
+    /// 识别依据的文件
+    pub marker: String,
+    pub build: Option<String>,
+    pub test: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProjectContext {
+    pub instructions: Vec<InstructionFile>,
+    pub file_tree: String,
+    pub project_types: Vec<ProjectType>,
+}
+
+impl ProjectContext {
+    pub fn collect(project_directory: &Path, config: &ContextConfig) -> Self {
+        let mut context = Self {
+            project_types: detect_project_types(project_directory),
+            ..Default::default()
+        };
+
+        let mut instruction_paths: Vec<String> = config
+            .instruction_files
+            .iter()
+            .filter(|name| project_directory.join(name).is_file())
+            .cloned()
+            .collect();
+
+        // 子目录中与说明文件同名的文件（如 src/AGENTS.md）也一并加载
+        let nested_names: Vec<&str> = config
+            .instruction_files
+            .iter()
+            .filter(|name| !name.contains('/'))
+            .map(|name| name.as_str())
+            .collect();
+
+        let mut tree_lines = Vec::new();
+        let mut omitted = 0;
+        let walker = WalkBuilder::new(project_directory)
+            .max_depth(Some(config.max_depth.max(NESTED_INSTRUCTIONS_DEPTH)))
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walker.filter_map(|e| e.ok()) {
+            let depth = entry.depth();
+            if depth == 0 {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(project_directory) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            let name = entry.file_name().to_string_lossy();
+
+            if !is_dir
+                && depth > 1
+                && nested_names.contains(&name.as_ref())
+                && !instruction_paths.contains(&relative)
+            {
+                instruction_paths.push(relative.clone());
+            }
+
+            if depth > config.max_depth {
+                continue;
+            }
+            if tree_lines.len() >= config.max_entries {
+                omitted += 1;
+                continue;
+            }
+            let indent = "  ".repeat(depth - 1);
+            let suffix = if is_dir { "/" } else { "" };
+            tree_lines.push(format!("{}{}{}", indent, name, suffix));
+        }
+        if omitted > 0 {
+            tree_lines.push(pt!("context.prompt.more_entries", count = omitted));
+        }
+        context.file_tree = tree_lines.join("\n");
+
+        let mut budget = config.max_instruction_bytes;
+        for path in instruction_paths {
+            if budget == 0 {
+                break;
+            }
+            let Ok(content) = std::fs::read_to_string(project_directory.join(&path)) else {
+                continue;
+            };
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            let content = truncate(content, budget);
+            budget = budget.saturating_sub(content.len());
+            context.instructions.push(InstructionFile { path, content });
+        }
+
+        context
+    }
+}
+
+/// 截断到不超过 `max_bytes` 字节，保证不切断字符
+fn truncate(content: &str, max_bytes: usize) -> String {
+    if content.len() <= max_bytes {
+        return content.to_string();
+    }
+    let mut end = max_bytes;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n{}", &content[..end], pt!("context.prompt.truncated"))
+}
+
+fn detect_project_types(project_directory: &Path) -> Vec<ProjectType> {
+    let exists = |name: &str| project_directory.join(name).exists();
+    let mut types = Vec::new();
+
+    if exists("Cargo.toml") {
+        types.push(ProjectType {
+            name: "Rust".to_string(),
+            marker: "Cargo.toml".to_string(),
+            build: Some("cargo build".to_string()),
+            test: "cargo test".to_string(),
+        });
+    }
+
+    if exists("package.json") {
+        let manager = if exists("pnpm-lock.yaml") {
+            "pnpm"
+        } else if exists("yarn.lock") {
+            "yarn"
+        } else {
+            "npm"
+        };
+        let has_build_script = std::fs::read_to_string(project_directory.join("package.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .is_some_and(|package| package["scripts"]["build"].is_string());
+        types.push(ProjectType {
+            name: "Node.js".to_string(),
+            marker: "package.json".to_string(),
+            build: has_build_script.then(|| format!("{} run build", manager)),
+            test: format!("{} test", manager),
+        });
+    }
+
+    if let Some(marker) = ["pyproject.toml", "setup.py", "requirements.txt"]
+        .into_iter()
+        .find(|name| exists(name))
+    {
+        types.push(ProjectType {
+            name: "Python".to_string(),
+            marker: marker.to_string(),
+            build: None,
+            test: "python -m pytest".to_string(),
+        });
+    }
+
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_collects_instructions_and_bounded_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".rust-agent")).unwrap();
+        fs::create_dir_all(root.join("src/deep/deeper/deepest")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join("AGENTS.md"), "使用中文注释").unwrap();
+        fs::write(root.join(".rust-agent/instructions.md"), "提交前运行测试").unwrap();
+        fs::write(
+            root.join("src/deep/deeper/deepest/AGENTS.md"),
+            "这里是生成代码",
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("target/debug/app"), "").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+
+        let config = ContextConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let context = ProjectContext::collect(root, &config);
+
+        let paths: Vec<&str> = context
+            .instructions
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "AGENTS.md",
+                ".rust-agent/instructions.md",
+                "src/deep/deeper/deepest/AGENTS.md"
+            ]
+        );
+        assert_eq!(
+            context.file_tree,
+            "AGENTS.md\nCargo.toml\nsrc/\n  deep/\n  main.rs"
+        );
+        assert_eq!(context.project_types[0].test, "cargo test");
+
+        let config = ContextConfig {
+            max_entries: 2,
+            max_instruction_bytes: 6,
+            ..Default::default()
+        };
+        let context = ProjectContext::collect(root, &config);
+        assert!(context
+            .file_tree
+            .ends_with(&pt!("context.prompt.more_entries", count = 4)));
+        assert_eq!(context.instructions.len(), 1);
+        assert!(context.instructions[0].content.starts_with("使用\n"));
+    }
+
+    #[test]
+    fn test_detects_node_and_python_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"scripts": {"build": "tsc", "test": "vitest"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("pnpm-lock.yaml"), "").unwrap();
+        fs::write(dir.path().join("requirements.txt"), "").unwrap();
+
+        let types = detect_project_types(dir.path());
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].build.as_deref(), Some("pnpm run build"));
+        assert_eq!(types[0].test, "pnpm test");
+        assert_eq!(types[1].name, "Python");
+        assert_eq!(types[1].marker, "requirements.txt");
+    }
+}
@@ -1,3 +1,35 @@
+//! 系统提示词模板，使用 minijinja 渲染
+
+use crate::config::PromptConfig;
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::{prompt_lang, Lang};
+use crate::project_context::{InstructionFile, ProjectType};
+use crate::t;
+use minijinja::{Environment, UndefinedBehavior, Value};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 项目级的提示词模板，存在时替换内置模板
+pub const PROMPT_TEMPLATE_FILE: &str = ".rust-agent/prompt.j2";
+
+/// 模板中可以直接使用的内置变量
+pub const BUILTIN_VARIABLES: &[&str] = &[
+    "tools",
+    "tool_list",
+    "operating_system",
+    "file_list",
+    "file_tree",
+    "instructions",
+    "project_types",
+    "project_directory",
+    "date",
+    "git_branch",
+    "max_actions",
+    "memories",
+    "rules",
+];
+
 pub const REACT_SYSTEM_PROMPT_TEMPLATE: &str = r#"
 你需要解决一个问题。为此，你需要将问题分解为多个步骤。对于每个步骤，首先使用 <thought> 思考要做什么，然后使用可用工具之一决定一个 <action>。接着，你将根据你的行动从环境/工具中收到一个 <observation>。持续这个思考和行动的过程，直到你有足够的信息来提供 <final_answer>。
 
@@ -37,39 +69,355 @@ pub const REACT_SYSTEM_PROMPT_TEMPLATE: &str = r#"
 
 请严格遵守：
 - 你每次回答都必须包括两个标签，第一个是 <thought>，第二个是 <action> 或 <final_answer>
+{% if max_actions > 1 %}
+- 互不依赖的操作（如读取多个文件）可以在一次回答中输出多个 <action>，最多 {{ max_actions }} 个。只读工具会并行执行，其他工具按输出顺序依次执行，观察结果按 [1]、[2] 编号合并在一个 <observation> 中返回
+- 输出全部 <action> 后立即停止生成，等待真实的 <observation>，擅自生成 <observation> 将导致错误
+{% else %}
 - 输出 <action> 后立即停止生成，等待真实的 <observation>，擅自生成 <observation> 将导致错误
-- 如果 <action> 中的某个工具参数有多行的话，请使用 \n 来表示，如：<action>write_to_file("/tmp/test.txt", "a\nb\nc")</action>
+{% endif %}
+- 参数可以按位置传递，也可以按参数名传递，如 find_recipe(dish="番茄炒蛋")，或传入一个JSON对象，如 find_recipe({"dish": "番茄炒蛋"})
+- 字符串参数中的换行写成 \n，如：<action>write_to_file("/tmp/test.txt", "a\nb\nc")</action>。较长的多行内容（如文件内容）请使用 heredoc，其中的内容原样保留、无需转义：
+<action>write_to_file("src/main.rs", <<EOF
+fn main() {
+    println!("hello");
+}
+EOF)</action>
 - 工具参数中的文件路径可以使用相对路径（相对于项目目录）或绝对路径。推荐使用相对路径，例如：write_to_file("index.html", "内容") 或 write_to_file("src/main.rs", "内容")
 
 ⸻
 
 本次任务可用工具：
-{tool_list}
+{% for tool in tools %}
+- {{ tool.signature }}: {{ tool.description }}
+{% endfor %}
 
 ⸻
 
 环境信息：
 
-操作系统：{operating_system}
-当前目录下文件列表：{file_list}
+操作系统：{{ operating_system }}
+当前日期：{{ date }}
+{% if git_branch %}
+当前Git分支：{{ git_branch }}
+{% endif %}
+{% for project in project_types %}
+项目类型：{{ project.name }}（{{ project.marker }}）{% if project.build %}，构建命令：{{ project.build }}{% endif %}，测试命令：{{ project.test }}
+{% endfor %}
+项目文件结构：
+{{ file_tree }}
+{% if instructions %}
+
+⸻
+
+项目说明（来自项目中的说明文件，请遵守）：
+{% for file in instructions %}
+
+## {{ file.path }}
+{{ file.content }}
+{% endfor %}
+{% endif %}
+{% if memories %}
+
+⸻
+
+项目记忆（之前的会话中记下的事实，过时的条目可以用 forget 删除）：
+{% for memory in memories %}
+- {{ memory }}
+{% endfor %}
+{% endif %}
+{% if rules %}
+
+⸻
+
+请遵守以下项目规则：
+{% for rule in rules %}
+- {{ rule }}
+{% endfor %}
+{% endif %}
+"#;
+
+/// 英文版的内置模板，变量与中文版相同
+pub const REACT_SYSTEM_PROMPT_TEMPLATE_EN: &str = r#"
+You need to solve a problem. To do so, break it down into steps. For each step, first use <thought> to think about what to do, then choose an <action> using one of the available tools. You will then receive an <observation> from the environment/tool based on your action. Continue this cycle of thinking and acting until you have enough information to give the <final_answer>.
+
+Use the following XML tags strictly for every step:
+- <question> the user's question
+- <thought> your reasoning
+- <action> the tool call you make
+- <observation> the result returned by the tool or environment
+- <final_answer> the final answer
+
+⸻
+
+Example 1:
+
+<question>How tall is the Eiffel Tower?</question>
+<thought>I need to find the height of the Eiffel Tower. I can use the search tool.</thought>
+<action>get_height("Eiffel Tower")</action>
+<observation>The Eiffel Tower is about 330 meters tall (including antennas).</observation>
+<thought>The search result gives the height. I have the answer.</thought>
+<final_answer>The Eiffel Tower is about 330 meters tall.</final_answer>
+
+⸻
+
+Example 2:
+
+<question>Find me a simple scrambled eggs with tomatoes recipe, and check whether there are tomatoes in the fridge.</question>
+<thought>This task has two steps. First, find the recipe. Second, check the fridge for tomatoes. I'll start with the find_recipe tool.</thought>
+<action>find_recipe(dish="scrambled eggs with tomatoes")</action>
+<observation>Simple recipe: beat 2 eggs and cut 2 tomatoes into chunks. Heat oil, scramble the eggs and set aside. Heat more oil, cook the tomatoes until soft, add the eggs back and season with salt.</observation>
+<thought>I have the recipe, which needs tomatoes. Now I'll use the check_fridge tool to see if there are any.</thought>
+<action>check_fridge(item="tomatoes")</action>
+<observation>Fridge check: 3 tomatoes.</observation>
+<thought>I found the recipe and confirmed there are tomatoes in the fridge. I can answer now.</thought>
+<final_answer>Recipe: beat the eggs and cut the tomatoes into chunks. Scramble the eggs, then cook the tomatoes, combine them and season with salt. There are 3 tomatoes in the fridge.</final_answer>
+
+⸻
+
+Follow these rules strictly:
+- Every reply must contain two tags: first <thought>, then either <action> or <final_answer>
+{% if max_actions > 1 %}
+- Independent operations (such as reading several files) may be issued as several <action> tags in one reply, at most {{ max_actions }}. Read-only tools run in parallel and other tools run one by one in the order given; the observations come back in a single <observation>, numbered [1], [2] and so on
+- Stop generating right after the last </action> and wait for the real <observation>; never write an <observation> yourself
+{% else %}
+- Stop generating right after </action> and wait for the real <observation>; never write an <observation> yourself
+{% endif %}
+- Arguments can be passed by position or by parameter name, e.g. find_recipe(dish="scrambled eggs"), or as a single JSON object, e.g. find_recipe({"dish": "scrambled eggs"})
+- Write line breaks in string arguments as \n, e.g. <action>write_to_file("/tmp/test.txt", "a\nb\nc")</action>. For longer multi-line content such as file bodies, use a heredoc; its content is kept verbatim and needs no escaping:
+<action>write_to_file("src/main.rs", <<EOF
+fn main() {
+    println!("hello");
+}
+EOF)</action>
+- File paths in tool arguments may be relative to the project directory or absolute. Prefer relative paths, e.g. write_to_file("index.html", "content") or write_to_file("src/main.rs", "content")
+
+⸻
+
+Tools available for this task:
+{% for tool in tools %}
+- {{ tool.signature }}: {{ tool.description }}
+{% endfor %}
+
+⸻
+
+Environment:
+
+Operating system: {{ operating_system }}
+Date: {{ date }}
+{% if git_branch %}
+Git branch: {{ git_branch }}
+{% endif %}
+{% for project in project_types %}
+Project type: {{ project.name }} ({{ project.marker }}){% if project.build %}, build: {{ project.build }}{% endif %}, test: {{ project.test }}
+{% endfor %}
+Project files:
+{{ file_tree }}
+{% if instructions %}
+
+⸻
+
+Project instructions (from instruction files in the project; follow them):
+{% for file in instructions %}
+
+## {{ file.path }}
+{{ file.content }}
+{% endfor %}
+{% endif %}
+{% if memories %}
+
+⸻
+
+Project memory (facts recorded in earlier sessions; remove outdated entries with forget):
+{% for memory in memories %}
+- {{ memory }}
+{% endfor %}
+{% endif %}
+{% if rules %}
+
+⸻
+
+Follow these project rules:
+{% for rule in rules %}
+- {{ rule }}
+{% endfor %}
+{% endif %}
 "#;
 
+/// 模板中 `tools` 列表的元素
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    /// 带参数的调用形式，如 `read_file(file_path)`
+    pub signature: String,
+    pub description: String,
+}
+
+/// 渲染提示词时的环境信息
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    pub tools: Vec<ToolInfo>,
+    /// 预先格式化好的工具列表，方便不需要循环的模板直接使用
+    pub tool_list: String,
+    pub operating_system: String,
+    /// 项目根目录下的文件名，逗号分隔
+    pub file_list: String,
+    pub file_tree: String,
+    pub instructions: Vec<InstructionFile>,
+    pub project_types: Vec<ProjectType>,
+    pub project_directory: String,
+    pub date: String,
+    pub git_branch: Option<String>,
+    /// 一次回答中最多可以输出的动作数
+    pub max_actions: usize,
+    /// 与当前任务相关的项目记忆
+    pub memories: Vec<String>,
+}
+
 pub struct PromptRenderer {
+    /// 模板来源，用于错误信息
+    source: String,
     template: String,
+    rules: Vec<String>,
+    variables: BTreeMap<String, String>,
 }
 
 impl PromptRenderer {
+    /// 当前提示词语言的内置模板
     pub fn new() -> Self {
+        Self::builtin(prompt_lang())
+    }
+
+    /// 指定语言的内置模板
+    pub fn builtin(lang: Lang) -> Self {
+        let template = match lang {
+            Lang::Zh => REACT_SYSTEM_PROMPT_TEMPLATE,
+            Lang::En => REACT_SYSTEM_PROMPT_TEMPLATE_EN,
+        };
         Self {
-            template: REACT_SYSTEM_PROMPT_TEMPLATE.to_string(),
+            source: t!("template.builtin"),
+            template: template.to_string(),
+            rules: vec![],
+            variables: BTreeMap::new(),
+        }
+    }
+
+    /// 按配置加载模板：`prompt.template` 指定的文件，其次是项目中的 `.rust-agent/prompt.j2`，
+    /// 都没有时使用 `prompt.language`（默认为当前提示词语言）的内置模板。模板语法错误或使用了未定义的变量时返回错误
+    pub fn load(project_directory: &Path, config: &PromptConfig) -> AgentResult<Self> {
+        let path = match &config.template {
+            Some(path) => Some(project_directory.join(path)),
+            None => Some(project_directory.join(PROMPT_TEMPLATE_FILE)).filter(|p| p.exists()),
+        };
+
+        let mut renderer = Self::builtin(config.language.unwrap_or_else(prompt_lang));
+        if let Some(path) = path {
+            renderer.template = std::fs::read_to_string(&path).map_err(|e| {
+                AgentError::ConfigError(t!(
+                    "template.read_failed",
+                    path = path.display(),
+                    error = e
+                ))
+            })?;
+            renderer.source = path.display().to_string();
         }
+        renderer.rules = config.rules.clone();
+        renderer.variables = config.variables.clone();
+        renderer.validate()?;
+        Ok(renderer)
     }
 
-    pub fn render(&self, tool_list: &str, operating_system: &str, file_list: &str) -> String {
-        self.template
-            .replace("{tool_list}", tool_list)
-            .replace("{operating_system}", operating_system)
-            .replace("{file_list}", file_list)
+    fn environment() -> Environment<'static> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env
+    }
+
+    fn validate(&self) -> AgentResult<()> {
+        if let Some(name) = self
+            .variables
+            .keys()
+            .find(|name| BUILTIN_VARIABLES.contains(&name.as_str()))
+        {
+            return Err(AgentError::ConfigError(t!(
+                "template.reserved_variable",
+                name = name
+            )));
+        }
+
+        let env = Self::environment();
+        let template = env.template_from_str(&self.template).map_err(|e| {
+            AgentError::ConfigError(t!(
+                "template.syntax_error",
+                source = self.source,
+                error = format!("{:#}", e)
+            ))
+        })?;
+
+        let mut unknown: Vec<String> = template
+            .undeclared_variables(false)
+            .into_iter()
+            .filter(|name| {
+                !BUILTIN_VARIABLES.contains(&name.as_str()) && !self.variables.contains_key(name)
+            })
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(AgentError::ConfigError(t!(
+                "template.undefined_variables",
+                source = self.source,
+                unknown = unknown.join(", "),
+                available = BUILTIN_VARIABLES
+                    .iter()
+                    .copied()
+                    .chain(self.variables.keys().map(|k| k.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, context: &PromptContext) -> AgentResult<String> {
+        let mut values: BTreeMap<&str, Value> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), Value::from(value.as_str())))
+            .collect();
+        values.insert("tools", Value::from_serialize(&context.tools));
+        values.insert("tool_list", Value::from(context.tool_list.as_str()));
+        values.insert(
+            "operating_system",
+            Value::from(context.operating_system.as_str()),
+        );
+        values.insert("file_list", Value::from(context.file_list.as_str()));
+        values.insert("file_tree", Value::from(context.file_tree.as_str()));
+        values.insert("instructions", Value::from_serialize(&context.instructions));
+        values.insert(
+            "project_types",
+            Value::from_serialize(&context.project_types),
+        );
+        values.insert(
+            "project_directory",
+            Value::from(context.project_directory.as_str()),
+        );
+        values.insert("date", Value::from(context.date.as_str()));
+        values.insert("git_branch", Value::from(context.git_branch.clone()));
+        values.insert("max_actions", Value::from(context.max_actions));
+        values.insert("memories", Value::from_serialize(&context.memories));
+        values.insert("rules", Value::from_serialize(&self.rules));
+
+        let env = Self::environment();
+        env.template_from_str(&self.template)
+            .and_then(|template| template.render(values))
+            .map_err(|e| {
+                AgentError::RuntimeError(t!(
+                    "template.render_failed",
+                    source = self.source,
+                    error = format!("{:#}", e)
+                ))
+            })
     }
 }
 
@@ -78,3 +426,141 @@ impl Default for PromptRenderer {
         Self::new()
     }
 }
+
+/// 当前日期（UTC），格式为 YYYY-MM-DD
+pub fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // 由1970-01-01起的天数换算公历日期
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> PromptContext {
+        PromptContext {
+            tools: vec![ToolInfo {
+                name: "read_file".to_string(),
+                signature: "read_file(file_path)".to_string(),
+                description: "读取文件内容".to_string(),
+            }],
+            tool_list: "- read_file(file_path): 读取文件内容".to_string(),
+            operating_system: "Linux".to_string(),
+            file_list: "src, Cargo.toml".to_string(),
+            file_tree: "{{ tool_list }}.txt\n{tool_list}.md".to_string(),
+            instructions: vec![InstructionFile {
+                path: "AGENTS.md".to_string(),
+                content: "使用中文注释".to_string(),
+            }],
+            project_types: vec![ProjectType {
+                name: "Rust".to_string(),
+                marker: "Cargo.toml".to_string(),
+                build: Some("cargo build".to_string()),
+                test: "cargo test".to_string(),
+            }],
+            project_directory: "/tmp/project".to_string(),
+            date: "2025-01-02".to_string(),
+            git_branch: Some("main".to_string()),
+            max_actions: 1,
+            memories: vec!["#1 [2025-01-01] 使用 pnpm 而不是 npm".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_builtin_template_renders_values_verbatim() {
+        let config = PromptConfig {
+            rules: vec!["提交前运行 cargo test".to_string()],
+            ..Default::default()
+        };
+        let renderer = PromptRenderer::load(Path::new("/nonexistent"), &config).unwrap();
+        let prompt = renderer.render(&context()).unwrap();
+
+        assert!(prompt.contains("\n- read_file(file_path): 读取文件内容\n"));
+        // 变量的值不会被再次当作模板替换
+        assert!(prompt.contains("项目文件结构：\n{{ tool_list }}.txt\n{tool_list}.md\n"));
+        assert!(prompt.contains(
+            "项目类型：Rust（Cargo.toml），构建命令：cargo build，测试命令：cargo test\n"
+        ));
+        assert!(prompt.contains("## AGENTS.md\n使用中文注释\n"));
+        assert!(prompt.contains("当前Git分支：main"));
+        assert!(prompt.contains("- 提交前运行 cargo test"));
+        assert!(prompt.contains("- 输出 <action> 后立即停止生成"));
+        assert!(prompt.contains("项目记忆"));
+        assert!(prompt.contains("\n- #1 [2025-01-01] 使用 pnpm 而不是 npm\n"));
+
+        let mut without_branch = context();
+        without_branch.git_branch = None;
+        without_branch.instructions.clear();
+        without_branch.max_actions = 3;
+        without_branch.memories.clear();
+        let prompt = PromptRenderer::builtin(Lang::Zh)
+            .render(&without_branch)
+            .unwrap();
+        assert!(prompt.contains("可以在一次回答中输出多个 <action>，最多 3 个"));
+        assert!(!prompt.contains("当前Git分支"));
+        assert!(!prompt.contains("项目说明"));
+        assert!(!prompt.contains("项目记忆"));
+        assert!(!prompt.contains("项目规则"));
+    }
+
+    #[test]
+    fn test_prompt_language_selects_builtin_template() {
+        let config = PromptConfig {
+            language: Some(Lang::En),
+            ..Default::default()
+        };
+        let renderer = PromptRenderer::load(Path::new("/nonexistent"), &config).unwrap();
+        let prompt = renderer.render(&context()).unwrap();
+        assert!(prompt.contains("Tools available for this task:\n- read_file(file_path)"));
+        assert!(prompt.contains("Git branch: main"));
+        // 示例同样使用提示词语言
+        assert!(prompt.contains("<question>How tall is the Eiffel Tower?</question>"));
+        assert!(prompt.contains("find_recipe(dish=\"scrambled eggs with tomatoes\")"));
+        assert!(!prompt.contains("埃菲尔铁塔"));
+
+        let default = crate::i18n::with_prompt_lang(Lang::En, PromptRenderer::new);
+        assert_eq!(default.render(&context()).unwrap(), prompt);
+    }
+
+    #[test]
+    fn test_project_template_is_validated_at_load() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".rust-agent")).unwrap();
+        let path = dir.path().join(PROMPT_TEMPLATE_FILE);
+
+        std::fs::write(&path, "{{ team }}团队\n{{ tool_lsit }}").unwrap();
+        let err = PromptRenderer::load(dir.path(), &PromptConfig::default())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("team, tool_lsit"));
+
+        let config = PromptConfig {
+            variables: BTreeMap::from([("team".to_string(), "基础设施".to_string())]),
+            ..Default::default()
+        };
+        std::fs::write(&path, "{{ team }}团队，今天是{{ date }}\n{{ tool_list }}").unwrap();
+        let renderer = PromptRenderer::load(dir.path(), &config).unwrap();
+        assert_eq!(
+            renderer.render(&context()).unwrap(),
+            "基础设施团队，今天是2025-01-02\n- read_file(file_path): 读取文件内容"
+        );
+
+        std::fs::write(&path, "{% if tools %}").unwrap();
+        assert!(PromptRenderer::load(dir.path(), &config).is_err());
+    }
+}
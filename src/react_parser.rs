@@ -0,0 +1,381 @@
+//! ReAct 标签协议的增量解析器。模型输出按块输入，标签可以跨块拆分。
+//! 闭合的 `<action>` 达到上限或模型开始自行输出 `<observation>` 时解析结束，之后的输入全部忽略
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactTag {
+    Question,
+    Thought,
+    Action,
+    Observation,
+    FinalAnswer,
+}
+
+impl ReactTag {
+    const ALL: [ReactTag; 5] = [
+        Self::Question,
+        Self::Thought,
+        Self::Action,
+        Self::Observation,
+        Self::FinalAnswer,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Question => "question",
+            Self::Thought => "thought",
+            Self::Action => "action",
+            Self::Observation => "observation",
+            Self::FinalAnswer => "final_answer",
+        }
+    }
+
+    /// 动作参数和最终答案中可能出现任意标记，这两种标签内只识别自身的结束标签
+    fn is_verbatim(self) -> bool {
+        matches!(self, Self::Action | Self::FinalAnswer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseEvent {
+    Open(ReactTag),
+    /// 标签内（`tag` 为 `Some`）或标签外的文本，同一段内容可能分成多个事件
+    Content {
+        tag: Option<ReactTag>,
+        text: String,
+    },
+    /// 标签闭合，`content` 为标签内的完整内容
+    Close {
+        tag: ReactTag,
+        content: String,
+    },
+}
+
+/// 一次模型输出的解析结果，各标签的内容已去除首尾空白；思考和最终答案只保留第一个
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReactOutput {
+    /// 解析器接受的原始输出，不含解析结束后被丢弃的部分
+    pub raw: String,
+    pub thought: Option<String>,
+    /// 按输出顺序排列的完整动作
+    pub actions: Vec<String>,
+    pub final_answer: Option<String>,
+    /// 输出结束时仍未闭合的标签
+    pub unclosed: Option<ReactTag>,
+}
+
+impl ReactOutput {
+    /// 取出动作，没有完整的 `<action>` 标签时返回解析错误；末尾被截断的动作会被忽略
+    pub fn require_actions(&self) -> AgentResult<Vec<String>> {
+        if !self.actions.is_empty() {
+            return Ok(self.actions.clone());
+        }
+        match self.unclosed {
+            Some(ReactTag::Action) => Err(AgentError::ParseError(t!("agent.action_unclosed"))),
+            _ => Err(AgentError::ParseError(t!("agent.action_missing"))),
+        }
+    }
+}
+
+enum Marker {
+    Open(ReactTag),
+    Close(ReactTag),
+}
+
+pub struct ReactParser {
+    /// 闭合这么多个动作后结束解析
+    max_actions: usize,
+    output: ReactOutput,
+    /// 以 `<` 开头、可能是标签的一部分但尚未确定的输入
+    pending: String,
+    /// 当前打开的标签及其内容
+    current: Option<(ReactTag, String)>,
+    /// 尚未作为事件发出的文本
+    text: String,
+    finished: bool,
+}
+
+impl ReactParser {
+    /// 只接受一个动作的解析器
+    pub fn new() -> Self {
+        Self::with_max_actions(1)
+    }
+
+    pub fn with_max_actions(max_actions: usize) -> Self {
+        Self {
+            max_actions: max_actions.max(1),
+            output: ReactOutput::default(),
+            pending: String::new(),
+            current: None,
+            text: String::new(),
+            finished: false,
+        }
+    }
+
+    /// 解析已结束，不再接受输入
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 目前为止接受的原始输出
+    pub fn raw(&self) -> &str {
+        &self.output.raw
+    }
+
+    pub fn feed(&mut self, chunk: &str) -> Vec<ParseEvent> {
+        let mut events = Vec::new();
+        for ch in chunk.chars() {
+            if self.finished {
+                break;
+            }
+            self.output.raw.push(ch);
+            self.push_char(ch, &mut events);
+        }
+        self.flush_text(&mut events);
+        events
+    }
+
+    /// 结束输入，未能构成标签的剩余内容按文本处理
+    pub fn finish(mut self) -> ReactOutput {
+        let pending = std::mem::take(&mut self.pending);
+        self.push_text(&pending);
+        if let Some((tag, content)) = self.current.take() {
+            // 输出被截断时思考内容仍可展示，动作和最终答案不完整则不采用
+            if tag == ReactTag::Thought {
+                self.record(tag, &content);
+            }
+            self.output.unclosed = Some(tag);
+        }
+        self.output
+    }
+
+    fn push_char(&mut self, ch: char, events: &mut Vec<ParseEvent>) {
+        if self.pending.is_empty() && ch != '<' {
+            self.push_text(ch.encode_utf8(&mut [0; 4]));
+            return;
+        }
+
+        self.pending.push(ch);
+        if let Some(marker) = self.match_marker() {
+            self.pending.clear();
+            self.apply_marker(marker, events);
+        } else if !self.could_be_marker() {
+            // 不是标签：`<` 之后的字符作为文本，最后一个字符可能是新标签的开头
+            let mut pending = std::mem::take(&mut self.pending);
+            let last = pending.pop();
+            self.push_text(&pending);
+            if let Some(last) = last {
+                self.push_char(last, events);
+            }
+        }
+    }
+
+    fn markers() -> impl Iterator<Item = (String, Marker)> {
+        ReactTag::ALL.into_iter().flat_map(|tag| {
+            [
+                (format!("<{}>", tag.name()), Marker::Open(tag)),
+                (format!("</{}>", tag.name()), Marker::Close(tag)),
+            ]
+        })
+    }
+
+    fn match_marker(&self) -> Option<Marker> {
+        Self::markers()
+            .find(|(text, _)| *text == self.pending)
+            .map(|(_, marker)| marker)
+    }
+
+    fn could_be_marker(&self) -> bool {
+        Self::markers().any(|(text, _)| text.starts_with(&self.pending))
+    }
+
+    fn apply_marker(&mut self, marker: Marker, events: &mut Vec<ParseEvent>) {
+        let current = self.current.as_ref().map(|(tag, _)| *tag);
+        match marker {
+            // 动作之后模型不应自行输出观察结果，此时结束解析并丢弃该标签
+            Marker::Open(ReactTag::Observation)
+                if !current.is_some_and(ReactTag::is_verbatim)
+                    && !self.output.actions.is_empty() =>
+            {
+                self.close_current(events);
+                let marker_len = "<observation>".len();
+                self.output.raw.truncate(self.output.raw.len() - marker_len);
+                self.finished = true;
+            }
+            Marker::Open(tag) => {
+                if let Some(open) = current {
+                    if open.is_verbatim() {
+                        self.push_text(&format!("<{}>", tag.name()));
+                        return;
+                    }
+                    // 未闭合的思考等标签在下一个标签开始时视为结束
+                    self.close_current(events);
+                }
+                self.flush_text(events);
+                self.current = Some((tag, String::new()));
+                events.push(ParseEvent::Open(tag));
+            }
+            Marker::Close(tag) if current == Some(tag) => {
+                self.close_current(events);
+                if self.output.actions.len() >= self.max_actions {
+                    self.finished = true;
+                }
+            }
+            // 与当前标签不匹配的结束标签按文本处理
+            Marker::Close(tag) => self.push_text(&format!("</{}>", tag.name())),
+        }
+    }
+
+    fn close_current(&mut self, events: &mut Vec<ParseEvent>) {
+        self.flush_text(events);
+        if let Some((tag, content)) = self.current.take() {
+            self.record(tag, &content);
+            events.push(ParseEvent::Close { tag, content });
+        }
+    }
+
+    fn record(&mut self, tag: ReactTag, content: &str) {
+        let slot = match tag {
+            ReactTag::Thought => &mut self.output.thought,
+            ReactTag::FinalAnswer => &mut self.output.final_answer,
+            ReactTag::Action => {
+                self.output.actions.push(content.trim().to_string());
+                return;
+            }
+            ReactTag::Question | ReactTag::Observation => return,
+        };
+        slot.get_or_insert_with(|| content.trim().to_string());
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if let Some((_, content)) = &mut self.current {
+            content.push_str(text);
+        }
+        self.text.push_str(text);
+    }
+
+    fn flush_text(&mut self, events: &mut Vec<ParseEvent>) {
+        if !self.text.is_empty() {
+            events.push(ParseEvent::Content {
+                tag: self.current.as_ref().map(|(tag, _)| *tag),
+                text: std::mem::take(&mut self.text),
+            });
+        }
+    }
+}
+
+impl Default for ReactParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析一段完整的模型输出，最多接受 `max_actions` 个动作
+pub fn parse(content: &str, max_actions: usize) -> ReactOutput {
+    let mut parser = ReactParser::with_max_actions(max_actions);
+    parser.feed(content);
+    parser.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_split_across_chunks() {
+        let mut parser = ReactParser::new();
+        let mut events = Vec::new();
+        for chunk in [
+            "<tho",
+            "ught>第一行\n",
+            "第二行</th",
+            "ought>\n<act",
+            "ion>read_file(\"a.rs\")<",
+            "/action>\n<observation>",
+        ] {
+            events.extend(parser.feed(chunk));
+        }
+        assert!(parser.is_finished());
+        assert_eq!(events.first(), Some(&ParseEvent::Open(ReactTag::Thought)));
+        assert!(events.contains(&ParseEvent::Close {
+            tag: ReactTag::Thought,
+            content: "第一行\n第二行".to_string()
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&ParseEvent::Close {
+                tag: ReactTag::Action,
+                content: "read_file(\"a.rs\")".to_string()
+            })
+        );
+
+        let output = parser.finish();
+        assert_eq!(output.thought.as_deref(), Some("第一行\n第二行"));
+        assert_eq!(output.actions, vec!["read_file(\"a.rs\")"]);
+        assert!(output.raw.ends_with("</action>"));
+        assert_eq!(output.unclosed, None);
+    }
+
+    #[test]
+    fn test_malformed_and_nested_markup() {
+        // 动作参数中的标记原样保留，未闭合的思考在动作开始时结束
+        let output = parse(
+            "<thought>写入页面<action>write_to_file(\"a.html\", \"<p>1 < 2</p>\n</thought>\")</action>",
+            1,
+        );
+        assert_eq!(output.thought.as_deref(), Some("写入页面"));
+        assert_eq!(
+            output.actions,
+            vec!["write_to_file(\"a.html\", \"<p>1 < 2</p>\n</thought>\")"]
+        );
+
+        let output = parse("<final_answer>多行\n答案 <<thought>></final_answer>", 1);
+        assert_eq!(
+            output.final_answer.as_deref(),
+            Some("多行\n答案 <<thought>>")
+        );
+
+        // 顺序错误或被截断的动作不算完整
+        let output = parse("</action>read_file(\"test.txt\")<action>", 1);
+        assert!(output.actions.is_empty());
+        assert_eq!(output.unclosed, Some(ReactTag::Action));
+        assert!(output.require_actions().is_err());
+
+        assert_eq!(
+            parse("<action>read_file(\"test.txt\")</action>", 1)
+                .require_actions()
+                .unwrap(),
+            vec!["read_file(\"test.txt\")"]
+        );
+        assert!(parse("没有action标签", 1).require_actions().is_err());
+        assert!(parse("read_file(\"test.txt\")</action>", 1)
+            .actions
+            .is_empty());
+    }
+
+    #[test]
+    fn test_multiple_actions_up_to_limit() {
+        let content = "<thought>读取两个文件</thought>\n<action>read_file(\"a.rs\")</action>\n<action>read_file(\"b.rs\")</action>\n<action>read_file(\"c.rs\")</action>";
+        let output = parse(content, 2);
+        assert_eq!(
+            output.actions,
+            vec!["read_file(\"a.rs\")", "read_file(\"b.rs\")"]
+        );
+        assert!(output.raw.ends_with("read_file(\"b.rs\")</action>"));
+
+        // 模型擅自输出的观察结果及之后的内容被丢弃
+        let output = parse(
+            "<action>read_file(\"a.rs\")</action>\n<observation>假的</observation><action>x()</action>",
+            5,
+        );
+        assert_eq!(output.actions, vec!["read_file(\"a.rs\")"]);
+        assert_eq!(output.raw, "<action>read_file(\"a.rs\")</action>\n");
+
+        // 末尾被截断的动作被忽略
+        let output = parse("<action>a()</action><action>b(", 5);
+        assert_eq!(output.require_actions().unwrap(), vec!["a()"]);
+        assert_eq!(output.unclosed, Some(ReactTag::Action));
+    }
+}
@@ -0,0 +1,393 @@
+//! 交互式输入：行编辑、按项目保存的输入历史、多行输入和斜杠命令补全
+
+use rust_agent::t;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Config, Context, Editor, Helper};
+use std::path::{Path, PathBuf};
+
+/// 输入历史文件，相对于项目目录
+pub const HISTORY_FILE: &str = ".rust-agent/history";
+/// /save 和 /load 使用的会话目录，相对于项目目录
+pub const SESSIONS_DIR: &str = ".rust-agent/sessions";
+
+/// 多行输入的定界符，成对出现之间的换行不会提交输入
+const BLOCK_DELIMITER: &str = "\"\"\"";
+
+/// 斜杠命令，说明文本在消息目录的 `command.<name>` 中
+pub struct SlashCommand {
+    pub name: &'static str,
+    /// 可选参数的占位说明，对应消息目录中的 `command.<argument>_placeholder`
+    pub argument: Option<&'static str>,
+}
+
+impl SlashCommand {
+    pub fn usage(&self) -> String {
+        match self.argument {
+            Some(argument) => format!(
+                "/{} [{}]",
+                self.name,
+                t!(&format!("command.{}_placeholder", argument))
+            ),
+            None => format!("/{}", self.name),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        t!(&format!("command.{}", self.name))
+    }
+}
+
+pub const COMMANDS: &[SlashCommand] = &[
+    SlashCommand {
+        name: "help",
+        argument: None,
+    },
+    SlashCommand {
+        name: "tools",
+        argument: None,
+    },
+    SlashCommand {
+        name: "history",
+        argument: None,
+    },
+    SlashCommand {
+        name: "save",
+        argument: Some("name"),
+    },
+    SlashCommand {
+        name: "load",
+        argument: Some("name"),
+    },
+    SlashCommand {
+        name: "model",
+        argument: Some("name"),
+    },
+    SlashCommand {
+        name: "clear",
+        argument: None,
+    },
+    SlashCommand {
+        name: "undo",
+        argument: None,
+    },
+    SlashCommand {
+        name: "checkpoints",
+        argument: None,
+    },
+    SlashCommand {
+        name: "plan",
+        argument: Some("task"),
+    },
+    SlashCommand {
+        name: "memory",
+        argument: Some("memory"),
+    },
+    SlashCommand {
+        name: "quit",
+        argument: None,
+    },
+];
+
+/// 一次输入的解析结果
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Task(String),
+    Command { name: String, args: String },
+}
+
+/// 解析输入；以 `/` 开头的是命令，兼容旧的 quit、exit、clear、undo、checkpoints
+pub fn parse_input(line: &str) -> Input {
+    let trimmed = line.trim();
+    if let Some(command) = trimmed.strip_prefix('/') {
+        let (name, args) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        return Input::Command {
+            name: name.to_lowercase(),
+            args: args.trim().to_string(),
+        };
+    }
+
+    let lower = trimmed.to_lowercase();
+    if matches!(
+        lower.as_str(),
+        "quit" | "exit" | "clear" | "undo" | "checkpoints"
+    ) {
+        return Input::Command {
+            name: lower,
+            args: String::new(),
+        };
+    }
+    Input::Task(normalize_multiline(trimmed))
+}
+
+/// 去掉续行用的行尾反斜杠和包裹整段输入的 `"""`
+fn normalize_multiline(input: &str) -> String {
+    let text = input.replace("\\\n", "\n");
+    let text = text.trim();
+    match text
+        .strip_prefix(BLOCK_DELIMITER)
+        .and_then(|t| t.strip_suffix(BLOCK_DELIMITER))
+    {
+        Some(inner) => inner.trim().to_string(),
+        None => text.to_string(),
+    }
+}
+
+fn is_incomplete(input: &str) -> bool {
+    input.ends_with('\\') || input.matches(BLOCK_DELIMITER).count() % 2 == 1
+}
+
+/// 会话名称只能是简单的文件名，避免写到会话目录之外
+pub fn session_path(project_dir: &Path, name: &str) -> Option<PathBuf> {
+    let name = if name.is_empty() { "default" } else { name };
+    let valid = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.');
+    valid.then(|| {
+        project_dir
+            .join(SESSIONS_DIR)
+            .join(format!("{}.json", name))
+    })
+}
+
+fn session_names(project_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(project_dir.join(SESSIONS_DIR)) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".json").map(|n| n.to_string())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// 补全斜杠命令、/model 的配置名称和 /load 的会话名称
+pub struct ReplHelper {
+    project_dir: PathBuf,
+    profiles: Vec<String>,
+}
+
+impl ReplHelper {
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let Some(command) = line.strip_prefix('/') else {
+            return (0, vec![]);
+        };
+
+        match command.split_once(' ') {
+            None => (
+                1,
+                COMMANDS
+                    .iter()
+                    .map(|c| c.name)
+                    .filter(|name| name.starts_with(command))
+                    .map(|name| format!("{} ", name))
+                    .collect(),
+            ),
+            Some((name, arg)) => {
+                let options = match name {
+                    "model" => self.profiles.clone(),
+                    "load" => session_names(&self.project_dir),
+                    _ => vec![],
+                };
+                (
+                    line.len() - arg.len(),
+                    options
+                        .into_iter()
+                        .filter(|option| option.starts_with(arg))
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, candidates) = self.candidates(&line[..pos]);
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .map(|c| Pair {
+                    display: c.trim_end().to_string(),
+                    replacement: c,
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ReplHelper {}
+
+pub struct Repl {
+    editor: Editor<ReplHelper, FileHistory>,
+    history_path: PathBuf,
+}
+
+impl Repl {
+    pub fn new(project_dir: &Path, profiles: Vec<String>) -> rustyline::Result<Self> {
+        let config = Config::builder()
+            .max_history_size(1000)?
+            .history_ignore_dups(true)?
+            .auto_add_history(false)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(ReplHelper {
+            project_dir: project_dir.to_path_buf(),
+            profiles,
+        }));
+
+        let history_path = project_dir.join(HISTORY_FILE);
+        if history_path.exists() {
+            if let Err(e) = editor.load_history(&history_path) {
+                eprintln!("{}", t!("repl.history_load_failed", error = e));
+            }
+        }
+
+        Ok(Self {
+            editor,
+            history_path,
+        })
+    }
+
+    /// 读取一次输入，Ctrl-D 时返回 None；Ctrl-C 放弃当前输入并重新读取
+    pub fn read_input(&mut self, prompt: &str) -> rustyline::Result<Option<Input>> {
+        loop {
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        self.editor.add_history_entry(line.as_str())?;
+                        self.save_history();
+                    }
+                    return Ok(Some(parse_input(&line)));
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 读取一段文本作为回答（如确认或修改计划），不解析命令也不记入历史；Ctrl-D 时返回 None
+    pub fn read_text(&mut self, prompt: &str) -> rustyline::Result<Option<String>> {
+        loop {
+            match self.editor.readline(prompt) {
+                Ok(line) => return Ok(Some(normalize_multiline(&line))),
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 每次输入后追加到历史文件，避免异常退出时丢失
+    fn save_history(&mut self) {
+        if let Some(parent) = self.history_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = self.editor.append_history(&self.history_path) {
+            eprintln!("{}", t!("repl.history_save_failed", error = e));
+        }
+    }
+}
+
+pub fn print_help() {
+    println!("{}", t!("repl.help_title"));
+    for command in COMMANDS {
+        println!("  {:<16} {}", command.usage(), command.description());
+    }
+    println!("{}", t!("repl.help_multiline"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_input("/model  strong "),
+            Input::Command {
+                name: "model".to_string(),
+                args: "strong".to_string()
+            }
+        );
+        assert_eq!(
+            parse_input("EXIT"),
+            Input::Command {
+                name: "exit".to_string(),
+                args: String::new()
+            }
+        );
+        assert_eq!(
+            parse_input("第一行\\\n第二行"),
+            Input::Task("第一行\n第二行".to_string())
+        );
+        assert_eq!(
+            parse_input("\"\"\"\n修复 bug\n\n并补充测试\n\"\"\""),
+            Input::Task("修复 bug\n\n并补充测试".to_string())
+        );
+
+        assert!(is_incomplete("第一行\\"));
+        assert!(is_incomplete("\"\"\"\n未结束"));
+        assert!(!is_incomplete("\"\"\"\n已结束\n\"\"\""));
+    }
+
+    #[test]
+    fn test_completion_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(SESSIONS_DIR)).unwrap();
+        std::fs::write(dir.path().join(SESSIONS_DIR).join("bugfix.json"), "[]").unwrap();
+        let helper = ReplHelper {
+            project_dir: dir.path().to_path_buf(),
+            profiles: vec!["cheap".to_string(), "strong".to_string()],
+        };
+
+        assert_eq!(helper.candidates("/to"), (1, vec!["tools ".to_string()]));
+        assert_eq!(
+            helper.candidates("/model s"),
+            (7, vec!["strong".to_string()])
+        );
+        assert_eq!(helper.candidates("/load "), (6, vec!["bugfix".to_string()]));
+        assert_eq!(helper.candidates("修复"), (0, vec![]));
+
+        assert!(session_path(dir.path(), "../x").is_none());
+        assert!(session_path(dir.path(), "")
+            .unwrap()
+            .ends_with(".rust-agent/sessions/default.json"));
+    }
+}
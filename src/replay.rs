@@ -0,0 +1,467 @@
+//! 回放录制的运行：用运行轨迹中的模型输出代替API，在项目的副本中重新执行工具，
+//! 报告解析出的动作、最终答案或观察结果与录制时不同的地方，用于检查提示词或解析器的修改
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use crate::tools::{DELEGATE_TRANSCRIPTS_DIR, INDEX_FILE};
+use crate::trajectory::{push_indented, Outcome, Trajectory, TrajectoryEvent, TRAJECTORIES_DIR};
+use ignore::WalkBuilder;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 复制项目时跳过的路径（相对于项目目录）：运行中产生的记录，以及另行完整复制的版本库
+const FIXTURE_EXCLUDED: [&str; 6] = [
+    ".git",
+    TRAJECTORIES_DIR,
+    DELEGATE_TRANSCRIPTS_DIR,
+    INDEX_FILE,
+    ".rust-agent/sessions",
+    ".rust-agent/history",
+];
+
+/// 按录制的顺序返回模型输出，代替请求API
+#[derive(Debug, Default)]
+pub struct RecordedModel {
+    responses: Mutex<VecDeque<String>>,
+}
+
+impl RecordedModel {
+    pub fn new(responses: Vec<String>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+        }
+    }
+
+    /// 轨迹中每次回复的原始输出
+    pub fn from_trajectory(trajectory: &Trajectory) -> Self {
+        Self::new(
+            trajectory
+                .entries
+                .iter()
+                .filter_map(|entry| match &entry.event {
+                    TrajectoryEvent::Response { raw, .. } => Some(raw.clone()),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// 下一次回复，录制的回复已用完时返回错误
+    pub fn next_response(&self) -> AgentResult<String> {
+        self.responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .ok_or_else(|| AgentError::RuntimeError(t!("replay.exhausted")))
+    }
+}
+
+/// 把项目复制到 `target` 作为回放的工作目录，遵守 .gitignore，返回复制的项目文件数（不含版本库）
+pub fn copy_fixture(source: &Path, target: &Path) -> AgentResult<usize> {
+    let root = source.to_path_buf();
+    let walker = WalkBuilder::new(source)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            !FIXTURE_EXCLUDED
+                .iter()
+                .any(|excluded| relative == Path::new(excluded))
+        })
+        .build();
+    let mut count = 0;
+    for entry in walker {
+        let entry = entry.map_err(|e| AgentError::RuntimeError(e.to_string()))?;
+        let Ok(relative) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let destination = target.join(relative);
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            fs::create_dir_all(&destination)?;
+        } else if entry.file_type().is_some_and(|t| t.is_file()) {
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(entry.path(), &destination)?;
+            count += 1;
+        }
+    }
+
+    // git工具需要版本库；.git 是指向其他位置的文件（工作树、子模块）时不复制，以免回放修改原仓库
+    let git_dir = source.join(".git");
+    if git_dir.is_dir() {
+        copy_dir(&git_dir, &target.join(".git"))?;
+    }
+    Ok(count)
+}
+
+fn copy_dir(source: &Path, target: &Path) -> AgentResult<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let destination = target.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &destination)?;
+        }
+    }
+    Ok(())
+}
+
+/// 回放与录制不同的地方
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// 第 `turn` 次回复解析出的动作或最终答案不同
+    Response {
+        turn: usize,
+        expected: String,
+        actual: String,
+    },
+    /// 第 `index` 个执行的动作（从 1 开始）不同
+    Action {
+        index: usize,
+        expected: String,
+        actual: String,
+    },
+    /// 第 `index` 个动作相同，观察结果不同
+    Observation {
+        index: usize,
+        action: String,
+        expected: String,
+        actual: String,
+    },
+    /// 运行结果的类型不同，如录制时完成、回放时失败
+    Outcome { expected: String, actual: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReplayReport {
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// 比较录制的轨迹和回放产生的轨迹；`normalize` 用于处理回放结果中与录制环境有关的内容，如副本的路径
+    pub fn compare(
+        expected: &Trajectory,
+        actual: &Trajectory,
+        normalize: impl Fn(&str) -> String,
+    ) -> Self {
+        let mut divergences = Vec::new();
+
+        let expected_responses = responses(expected);
+        let actual_responses = responses(actual);
+        for index in 0..expected_responses.len().max(actual_responses.len()) {
+            let expected = expected_responses.get(index);
+            let actual = actual_responses.get(index);
+            if expected != actual {
+                divergences.push(Divergence::Response {
+                    turn: index + 1,
+                    expected: describe(expected),
+                    actual: describe(actual),
+                });
+            }
+        }
+
+        let expected_tools = tool_calls(expected);
+        let actual_tools = tool_calls(actual);
+        for index in 0..expected_tools.len().max(actual_tools.len()) {
+            match (expected_tools.get(index), actual_tools.get(index)) {
+                (Some((action, expected)), Some((actual_action, actual)))
+                    if action == actual_action =>
+                {
+                    let actual = normalize(actual);
+                    if *expected != actual {
+                        divergences.push(Divergence::Observation {
+                            index: index + 1,
+                            action: action.clone(),
+                            expected: expected.clone(),
+                            actual,
+                        });
+                    }
+                }
+                (expected, actual) => divergences.push(Divergence::Action {
+                    index: index + 1,
+                    expected: describe(expected.map(|(action, _)| action)),
+                    actual: describe(actual.map(|(action, _)| action)),
+                }),
+            }
+        }
+
+        // 未正常结束的录制没有可比较的结果
+        if let Some(expected) = expected.outcome() {
+            let actual = actual.outcome();
+            if actual.map(outcome_kind) != Some(outcome_kind(expected)) {
+                divergences.push(Divergence::Outcome {
+                    expected: outcome_label(Some(expected)),
+                    actual: outcome_label(actual),
+                });
+            }
+        }
+
+        Self { divergences }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        if self.passed() {
+            return t!("replay.passed");
+        }
+        let mut lines = vec![t!("replay.diverged", count = self.divergences.len())];
+        for divergence in &self.divergences {
+            let (title, expected, actual) = match divergence {
+                Divergence::Response {
+                    turn,
+                    expected,
+                    actual,
+                } => (t!("replay.response", turn = turn), expected, actual),
+                Divergence::Action {
+                    index,
+                    expected,
+                    actual,
+                } => (t!("replay.action", index = index), expected, actual),
+                Divergence::Observation {
+                    index,
+                    action,
+                    expected,
+                    actual,
+                } => (
+                    t!("replay.observation", index = index, action = action),
+                    expected,
+                    actual,
+                ),
+                Divergence::Outcome { expected, actual } => {
+                    (t!("replay.outcome"), expected, actual)
+                }
+            };
+            lines.push(title);
+            lines.push(format!("   {}", t!("replay.expected")));
+            push_indented(&mut lines, expected, false);
+            lines.push(format!("   {}", t!("replay.actual")));
+            push_indented(&mut lines, actual, false);
+        }
+        lines.join("\n")
+    }
+}
+
+/// 每次回复解析出的动作和最终答案
+fn responses(trajectory: &Trajectory) -> Vec<String> {
+    trajectory
+        .entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            TrajectoryEvent::Response {
+                actions,
+                final_answer,
+                ..
+            } => Some(
+                actions
+                    .iter()
+                    .map(|action| format!("🔧 {}", action))
+                    .chain(final_answer.iter().map(|answer| format!("✅ {}", answer)))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 按执行顺序排列的动作及其观察结果
+fn tool_calls(trajectory: &Trajectory) -> Vec<(String, String)> {
+    trajectory
+        .entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            TrajectoryEvent::Tool {
+                action,
+                observation,
+                ..
+            } => Some((action.clone(), observation.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn describe(value: Option<&String>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value.clone(),
+        Some(_) => t!("replay.nothing_parsed"),
+        None => t!("replay.missing"),
+    }
+}
+
+fn outcome_kind(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Answer(_) => "answer",
+        Outcome::Cancelled => "cancelled",
+        Outcome::Failed(_) => "failed",
+    }
+}
+
+fn outcome_label(outcome: Option<&Outcome>) -> String {
+    match outcome {
+        Some(Outcome::Answer(_)) => t!("trajectory.status_answered"),
+        Some(Outcome::Cancelled) => t!("trajectory.status_cancelled"),
+        Some(Outcome::Failed(error)) => {
+            format!("{}: {}", t!("trajectory.status_failed"), error)
+        }
+        None => t!("trajectory.status_unfinished"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trajectory::TrajectoryEntry;
+
+    fn trajectory(events: Vec<TrajectoryEvent>) -> Trajectory {
+        Trajectory {
+            entries: events
+                .into_iter()
+                .map(|event| TrajectoryEntry {
+                    elapsed_ms: 0,
+                    event,
+                })
+                .collect(),
+        }
+    }
+
+    fn response(turn: usize, actions: &[&str], final_answer: Option<&str>) -> TrajectoryEvent {
+        TrajectoryEvent::Response {
+            turn,
+            raw: String::new(),
+            thought: None,
+            actions: actions.iter().map(|a| a.to_string()).collect(),
+            final_answer: final_answer.map(str::to_string),
+            duration_ms: 0,
+        }
+    }
+
+    fn tool(action: &str, observation: &str) -> TrajectoryEvent {
+        TrajectoryEvent::Tool {
+            action: action.to_string(),
+            tool: "read_file".to_string(),
+            arguments: None,
+            observation: observation.to_string(),
+            failed: false,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_recorded_model_returns_responses_in_order() {
+        let recorded = trajectory(vec![
+            response(1, &["read_file(\"a\")"], None),
+            tool("read_file(a)", "A"),
+        ]);
+        let model = RecordedModel::from_trajectory(&recorded);
+        assert_eq!(model.next_response().unwrap(), "");
+        assert!(model.next_response().is_err());
+    }
+
+    #[test]
+    fn test_compare_reports_divergences() {
+        let recorded = trajectory(vec![
+            response(1, &["read_file(\"a\")", "read_file(\"b\")"], None),
+            tool("read_file(a)", "A in /project"),
+            tool("read_file(b)", "B"),
+            response(2, &[], Some("完成")),
+            TrajectoryEvent::Finish {
+                outcome: Outcome::Answer("完成".to_string()),
+                duration_ms: 0,
+            },
+        ]);
+        let normalize = |text: &str| text.replace("/tmp/fixture", "/project");
+
+        let same = trajectory(vec![
+            response(1, &["read_file(\"a\")", "read_file(\"b\")"], None),
+            tool("read_file(a)", "A in /tmp/fixture"),
+            tool("read_file(b)", "B"),
+            response(2, &[], Some("完成")),
+            TrajectoryEvent::Finish {
+                outcome: Outcome::Answer("完成".to_string()),
+                duration_ms: 0,
+            },
+        ]);
+        let report = ReplayReport::compare(&recorded, &same, normalize);
+        assert!(report.passed());
+        assert_eq!(report.render(), t!("replay.passed"));
+
+        // 解析器只解析出第一个动作，第二个观察结果变化，录制的回复用完后失败
+        let diverged = trajectory(vec![
+            response(1, &["read_file(\"a\")"], None),
+            tool("read_file(a)", "A changed"),
+            TrajectoryEvent::Finish {
+                outcome: Outcome::Failed("没有更多录制的回复".to_string()),
+                duration_ms: 0,
+            },
+        ]);
+        let report = ReplayReport::compare(&recorded, &diverged, normalize);
+        assert_eq!(
+            report.divergences,
+            vec![
+                Divergence::Response {
+                    turn: 1,
+                    expected: "🔧 read_file(\"a\")\n🔧 read_file(\"b\")".to_string(),
+                    actual: "🔧 read_file(\"a\")".to_string(),
+                },
+                Divergence::Response {
+                    turn: 2,
+                    expected: "✅ 完成".to_string(),
+                    actual: t!("replay.missing"),
+                },
+                Divergence::Observation {
+                    index: 1,
+                    action: "read_file(a)".to_string(),
+                    expected: "A in /project".to_string(),
+                    actual: "A changed".to_string(),
+                },
+                Divergence::Action {
+                    index: 2,
+                    expected: "read_file(b)".to_string(),
+                    actual: t!("replay.missing"),
+                },
+                Divergence::Outcome {
+                    expected: t!("trajectory.status_answered"),
+                    actual: format!("{}: 没有更多录制的回复", t!("trajectory.status_failed")),
+                },
+            ]
+        );
+        assert!(report.render().contains("A changed"));
+    }
+
+    #[test]
+    fn test_copy_fixture_skips_run_records_and_keeps_the_repository() {
+        let source = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        let root = source.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/app"), "binary").unwrap();
+        fs::create_dir_all(root.join(".git/refs/heads")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref").unwrap();
+        fs::write(root.join(".git/refs/heads/main"), "abc").unwrap();
+        fs::create_dir_all(root.join(TRAJECTORIES_DIR)).unwrap();
+        fs::write(root.join(TRAJECTORIES_DIR).join("1-1.jsonl"), "").unwrap();
+        fs::write(root.join(".rust-agent/memory.json"), "[]").unwrap();
+
+        assert_eq!(copy_fixture(root, target.path()).unwrap(), 3);
+        let copied = target.path();
+        assert!(copied.join("src/main.rs").is_file());
+        assert!(copied.join(".gitignore").is_file());
+        assert!(copied.join(".rust-agent/memory.json").is_file());
+        assert!(!copied.join("target").exists());
+        assert!(copied.join(".git/HEAD").is_file());
+        assert!(copied.join(".git/refs/heads/main").is_file());
+        assert!(!copied.join(TRAJECTORIES_DIR).exists());
+    }
+}
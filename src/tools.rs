@@ -1,11 +1,44 @@
+use crate::approval::{ApprovalPolicy, ApprovalRules};
+use crate::checkpoint::CheckpointManager;
+use crate::config::AgentConfig;
 use crate::errors::AgentResult;
+use crate::i18n::prompt_text;
+use crate::memory::MemoryStore;
+use crate::plan::PlanTracker;
 use async_trait::async_trait;
 use std::collections::HashMap;
-use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 use tokio::fs;
 
+mod cargo;
+mod custom;
+mod delegate;
+mod diff;
+mod git;
+mod http;
+mod memory;
+mod outline;
+mod plan;
+mod plugin;
+mod retrieval;
+
+use diff::{review_file_change, ReviewOutcome};
+
+pub use cargo::CargoTool;
+pub use custom::{
+    load_custom_tools, CustomParameterConfig, CustomTool, CustomToolConfig, CUSTOM_TOOLS_FILE,
+};
+pub use delegate::{DelegateSlots, DelegateTool, DELEGATE_TOOL, DELEGATE_TRANSCRIPTS_DIR};
+pub use git::{current_branch, GitCommitTool, GitDiffTool, GitLogTool, GitStatusTool};
+pub use http::{HttpRequestTool, DEFAULT_ALLOWED_HOSTS};
+pub use memory::{format_memory, ForgetTool, RecallTool, RememberTool};
+pub use outline::{CodeOutlineTool, ReadSymbolTool};
+pub use plan::UpdatePlanTool;
+pub use plugin::{load_plugin_tools, Capability, PluginConfig, WasmPluginTool, PLUGINS_FILE};
+pub use retrieval::{FindRelevantTool, IndexUpdate, SearchHit, SearchIndex, INDEX_FILE};
+
 /// 验证路径是否在项目目录内
 fn is_path_within_project(project_dir: &Path, target_path: &Path) -> bool {
     let project_dir = project_dir
@@ -37,46 +70,240 @@ fn safe_resolve_path(
 
     // 验证路径是否在项目目录内
     if !is_path_within_project(&project_path, &final_path) {
-        return Err(crate::errors::AgentError::RuntimeError(format!(
-            "路径 '{}' 不在项目目录 '{}' 内，操作被拒绝",
-            final_path.display(),
-            project_path.display()
+        return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+            "tools.path_outside_project",
+            path = final_path.display(),
+            project = project_path.display()
         )));
     }
 
     Ok(final_path)
 }
 
+/// 相对于项目目录的显示路径
+fn display_path<'a>(project_dir: &str, path: &'a Path) -> &'a Path {
+    path.strip_prefix(project_dir).unwrap_or(path)
+}
+
+/// 读取文件当前内容，文件不存在时视为空
+async fn read_existing_content(path: &Path) -> AgentResult<String> {
+    if path.is_file() {
+        Ok(fs::read_to_string(path).await?)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// 生成告知模型哪些修改被用户拒绝的观察结果
+fn describe_rejected_changes(file_path: &str, rejected: &str, partially_applied: bool) -> String {
+    if partially_applied {
+        crate::t!(
+            "tools.partially_written",
+            path = file_path,
+            rejected = rejected
+        )
+    } else {
+        crate::t!("tools.all_rejected", path = file_path, rejected = rejected)
+    }
+}
+
+/// 修改路径之前保存检查点快照（如果启用了检查点）
+fn record_checkpoint(checkpoints: Option<&CheckpointManager>, path: &Path) -> AgentResult<()> {
+    if let Some(checkpoints) = checkpoints {
+        checkpoints.record(path)?;
+    }
+    Ok(())
+}
+
+/// 工具参数说明，按位置参数的顺序排列
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolParameter {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+    /// 可以重复出现（只用于最后一个参数），如多个文件路径
+    pub repeated: bool,
+}
+
+impl ToolParameter {
+    pub fn required(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: true,
+            repeated: false,
+        }
+    }
+
+    pub fn optional(name: &str, description: &str) -> Self {
+        Self {
+            required: false,
+            ..Self::required(name, description)
+        }
+    }
+
+    pub fn repeated(mut self) -> Self {
+        self.repeated = true;
+        self
+    }
+}
+
 #[async_trait]
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     async fn execute(&self, args: Vec<String>) -> AgentResult<String>;
+
+    /// 执行前是否需要用户确认
+    fn requires_approval(&self) -> bool {
+        false
+    }
+
+    /// 不修改文件或其他状态；同一轮中连续的只读调用会并行执行
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// 参数列表，默认不声明（参数写在描述中）
+    fn parameters(&self) -> Vec<ToolParameter> {
+        Vec::new()
+    }
+}
+
+/// 工具的调用形式，如 `read_file(file_path)`；可选参数带 `?`，可重复参数带 `...`
+pub fn tool_signature(tool: &dyn Tool) -> String {
+    let parameters = tool.parameters();
+    if parameters.is_empty() {
+        return tool.name().to_string();
+    }
+    let signature = parameters
+        .iter()
+        .map(|p| match (p.required, p.repeated) {
+            (_, true) => format!("{}...", p.name),
+            (true, false) => p.name.clone(),
+            (false, false) => format!("{}?", p.name),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", tool.name(), signature)
 }
 
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn Tool>>,
+    tools: HashMap<String, Arc<dyn Tool>>,
+    checkpoints: Option<CheckpointManager>,
+    plan: Option<PlanTracker>,
+    memory: Option<MemoryStore>,
+    approval: ApprovalRules,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            checkpoints: None,
+            plan: None,
+            memory: None,
+            approval: ApprovalRules::default(),
         }
     }
 
+    /// 设置执行需要确认的工具时使用的审批规则
+    pub fn set_approval(&mut self, approval: ApprovalRules) {
+        self.approval = approval;
+    }
+
+    pub fn approval(&self) -> &ApprovalRules {
+        &self.approval
+    }
+
+    /// 指定工具适用的审批策略
+    pub fn approval_for(&self, tool_name: &str) -> ApprovalPolicy {
+        self.approval.policy_for(tool_name)
+    }
+
+    /// 设置文件工具共用的检查点管理器，Agent据此在每个任务前后保存检查点
+    pub fn set_checkpoints(&mut self, checkpoints: CheckpointManager) {
+        self.checkpoints = Some(checkpoints);
+    }
+
+    pub fn checkpoints(&self) -> Option<&CheckpointManager> {
+        self.checkpoints.as_ref()
+    }
+
+    /// 设置 update_plan 工具使用的计划，Agent据此按计划执行任务
+    pub fn set_plan(&mut self, plan: PlanTracker) {
+        self.plan = Some(plan);
+    }
+
+    pub fn plan(&self) -> Option<&PlanTracker> {
+        self.plan.as_ref()
+    }
+
+    /// 设置记忆工具使用的项目记忆，Agent据此在系统提示词中加入相关记忆
+    pub fn set_memory(&mut self, memory: MemoryStore) {
+        self.memory = Some(memory);
+    }
+
+    pub fn memory(&self) -> Option<&MemoryStore> {
+        self.memory.as_ref()
+    }
+
     pub fn register<T: Tool + 'static>(&mut self, tool: T) {
-        self.tools.insert(tool.name().to_string(), Box::new(tool));
+        self.register_boxed(Box::new(tool));
+    }
+
+    pub fn register_boxed(&mut self, tool: Box<dyn Tool>) {
+        self.register_shared(Arc::from(tool));
+    }
+
+    /// 注册与其他注册表共用的工具实例
+    pub fn register_shared(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name().to_string(), tool);
+    }
+
+    /// 由满足条件的工具组成的新注册表，工具实例和审批规则与当前注册表共用。
+    /// 新注册表共用项目记忆，但不设置检查点和计划，使用它的Agent不会在任务前后保存检查点
+    pub fn subset(&self, mut keep: impl FnMut(&dyn Tool) -> bool) -> ToolRegistry {
+        ToolRegistry {
+            tools: self
+                .tools
+                .iter()
+                .filter(|(_, tool)| keep(tool.as_ref()))
+                .map(|(name, tool)| (name.clone(), Arc::clone(tool)))
+                .collect(),
+            checkpoints: None,
+            plan: None,
+            memory: self.memory.clone(),
+            approval: self.approval.clone(),
+        }
+    }
+
+    /// 只保留满足条件的工具
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.tools.retain(|name, _| keep(name));
     }
 
     pub fn get_tool(&self, name: &str) -> Option<&dyn Tool> {
         self.tools.get(name).map(|t| t.as_ref())
     }
 
+    /// 按名称排序的全部工具
+    pub fn tools(&self) -> Vec<&dyn Tool> {
+        let mut tools: Vec<&dyn Tool> = self.tools.values().map(|t| t.as_ref()).collect();
+        tools.sort_by(|a, b| a.name().cmp(b.name()));
+        tools
+    }
+
     pub fn get_tool_list(&self) -> String {
         self.tools
             .values()
-            .map(|tool| format!("- {}: {}", tool.name(), tool.description()))
+            .map(|tool| {
+                format!(
+                    "- {}: {}",
+                    tool_signature(tool.as_ref()),
+                    tool.description()
+                )
+            })
             .collect::<Vec<_>>()
             .join("\n")
     }
@@ -111,14 +338,25 @@ impl Tool for ReadFileTool {
     }
 
     fn description(&self) -> &str {
-        "读取指定文件的内容。支持相对路径（相对于项目目录）和绝对路径"
+        prompt_text("tools.prompt.read_file")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "path",
+            prompt_text("tools.prompt.file_path"),
+        )]
     }
 
     async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
         if args.len() != 1 {
-            return Err(crate::errors::AgentError::RuntimeError(
-                "read_file 需要一个文件路径参数".to_string(),
-            ));
+            return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.read_file_args"
+            )));
         }
 
         let file_path = &args[0];
@@ -133,11 +371,27 @@ impl Tool for ReadFileTool {
 
 pub struct WriteFileTool {
     project_directory: String,
+    checkpoints: Option<CheckpointManager>,
+    approval: ApprovalPolicy,
 }
 
 impl WriteFileTool {
     pub fn new(project_directory: String) -> Self {
-        Self { project_directory }
+        Self {
+            project_directory,
+            checkpoints: None,
+            approval: ApprovalPolicy::default(),
+        }
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointManager) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn with_approval(mut self, approval: ApprovalPolicy) -> Self {
+        self.approval = approval;
+        self
     }
 }
 
@@ -148,14 +402,21 @@ impl Tool for WriteFileTool {
     }
 
     fn description(&self) -> &str {
-        "将指定内容写入指定文件。支持相对路径（相对于项目目录）和绝对路径"
+        prompt_text("tools.prompt.write_to_file")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("path", prompt_text("tools.prompt.file_path")),
+            ToolParameter::required("content", prompt_text("tools.prompt.content")),
+        ]
     }
 
     async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
         if args.len() != 2 {
-            return Err(crate::errors::AgentError::RuntimeError(
-                "write_to_file 需要文件路径和内容两个参数".to_string(),
-            ));
+            return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.write_file_args"
+            )));
         }
 
         let file_path = &args[0];
@@ -164,31 +425,67 @@ impl Tool for WriteFileTool {
         // 使用安全的路径解析，确保路径在项目目录内
         let final_path = safe_resolve_path(&self.project_directory, file_path)?;
 
+        // 展示与当前内容的差异，由用户决定接受哪些修改
+        let current = read_existing_content(&final_path).await?;
+        let (content, rejected) = match review_file_change(
+            display_path(&self.project_directory, &final_path),
+            &current,
+            content,
+            self.approval,
+        )? {
+            ReviewOutcome::Unchanged if final_path.exists() => {
+                return Ok(crate::t!(
+                    "tools.file_unchanged",
+                    path = final_path.display()
+                ));
+            }
+            ReviewOutcome::Unchanged => (content.clone(), None),
+            ReviewOutcome::Accepted(content) => (content, None),
+            ReviewOutcome::Partial { content, rejected } => (content, Some(rejected)),
+            ReviewOutcome::Rejected(rejected) => {
+                return Ok(describe_rejected_changes(file_path, &rejected, false));
+            }
+        };
+
         // 确保目录存在
         if let Some(parent) = final_path.parent() {
             if !parent.exists() {
-                print!("父目录 '{}' 不存在，是否创建？(Y/N): ", parent.display());
-                io::stdout().flush()?;
-
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-
-                if input.trim().to_lowercase() != "y" {
-                    return Ok("用户取消写入文件".to_string());
+                let question = crate::t!("tools.create_parent", path = parent.display());
+                if !self.approval.confirm(&question)? {
+                    return Ok(crate::t!("tools.write_cancelled"));
                 }
 
+                record_checkpoint(self.checkpoints.as_ref(), &final_path)?;
+
                 // 创建父目录
                 fs::create_dir_all(parent).await?;
-                println!("已创建父目录: {}", parent.display());
+                self.approval
+                    .notify(&crate::t!("tools.parent_created", path = parent.display()));
             }
         }
 
+        record_checkpoint(self.checkpoints.as_ref(), &final_path)?;
         fs::write(&final_path, content).await?;
-        Ok(format!("写入成功: {}", final_path.display()))
+        match rejected {
+            Some(rejected) => Ok(describe_rejected_changes(file_path, &rejected, true)),
+            None => Ok(crate::t!(
+                "tools.write_succeeded",
+                path = final_path.display()
+            )),
+        }
     }
 }
 
-pub struct RunTerminalCommandTool;
+/// 在项目目录中执行终端命令
+pub struct RunTerminalCommandTool {
+    project_directory: String,
+}
+
+impl RunTerminalCommandTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
 
 #[async_trait]
 impl Tool for RunTerminalCommandTool {
@@ -197,27 +494,42 @@ impl Tool for RunTerminalCommandTool {
     }
 
     fn description(&self) -> &str {
-        "执行终端命令"
+        prompt_text("tools.prompt.run_terminal_command")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "command",
+            prompt_text("tools.prompt.command"),
+        )]
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
     }
 
     async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
         if args.is_empty() {
-            return Err(crate::errors::AgentError::RuntimeError(
-                "run_terminal_command 需要命令参数".to_string(),
-            ));
+            return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.command_args"
+            )));
         }
 
         let command = &args[0];
-        let output = Command::new("sh").arg("-c").arg(command).output()?;
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.project_directory)
+            .output()?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
-            Ok(format!("执行成功: {}", stdout))
+            Ok(crate::t!("tools.command_succeeded", output = stdout))
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(crate::errors::AgentError::RuntimeError(format!(
-                "命令执行错误: {}",
-                stderr
+            Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.command_failed",
+                output = stderr
             )))
         }
     }
@@ -225,11 +537,27 @@ impl Tool for RunTerminalCommandTool {
 
 pub struct CreateDirectoryTool {
     project_directory: String,
+    checkpoints: Option<CheckpointManager>,
+    approval: ApprovalPolicy,
 }
 
 impl CreateDirectoryTool {
     pub fn new(project_directory: String) -> Self {
-        Self { project_directory }
+        Self {
+            project_directory,
+            checkpoints: None,
+            approval: ApprovalPolicy::default(),
+        }
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointManager) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn with_approval(mut self, approval: ApprovalPolicy) -> Self {
+        self.approval = approval;
+        self
     }
 }
 
@@ -240,14 +568,21 @@ impl Tool for CreateDirectoryTool {
     }
 
     fn description(&self) -> &str {
-        "创建目录，如果父目录不存在会询问是否创建"
+        prompt_text("tools.prompt.create_directory")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "path",
+            prompt_text("tools.prompt.directory_path"),
+        )]
     }
 
     async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
         if args.len() != 1 {
-            return Err(crate::errors::AgentError::RuntimeError(
-                "create_directory 需要一个目录路径参数".to_string(),
-            ));
+            return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.create_directory_args"
+            )));
         }
 
         let dir_path = &args[0];
@@ -259,41 +594,62 @@ impl Tool for CreateDirectoryTool {
 
         // 如果目录已存在，直接返回
         if path.exists() && path.is_dir() {
-            return Ok(format!("目录已存在: {}", final_path.display()));
+            return Ok(crate::t!(
+                "tools.directory_exists",
+                path = final_path.display()
+            ));
         }
 
         // 检查父目录是否存在
         if let Some(parent) = path.parent() {
             if !parent.exists() {
-                print!("父目录 '{}' 不存在，是否创建？(Y/N): ", parent.display());
-                io::stdout().flush()?;
-
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-
-                if input.trim().to_lowercase() != "y" {
-                    return Ok("用户取消创建目录".to_string());
+                let question = crate::t!("tools.create_parent", path = parent.display());
+                if !self.approval.confirm(&question)? {
+                    return Ok(crate::t!("tools.create_directory_cancelled"));
                 }
 
+                record_checkpoint(self.checkpoints.as_ref(), path)?;
+
                 // 创建父目录
                 fs::create_dir_all(parent).await?;
-                println!("已创建父目录: {}", parent.display());
+                self.approval
+                    .notify(&crate::t!("tools.parent_created", path = parent.display()));
             }
         }
 
         // 创建目标目录
+        record_checkpoint(self.checkpoints.as_ref(), path)?;
         fs::create_dir_all(path).await?;
-        Ok(format!("目录创建成功: {}", final_path.display()))
+        Ok(crate::t!(
+            "tools.directory_created",
+            path = final_path.display()
+        ))
     }
 }
 
 pub struct CreateFileTool {
     project_directory: String,
+    checkpoints: Option<CheckpointManager>,
+    approval: ApprovalPolicy,
 }
 
 impl CreateFileTool {
     pub fn new(project_directory: String) -> Self {
-        Self { project_directory }
+        Self {
+            project_directory,
+            checkpoints: None,
+            approval: ApprovalPolicy::default(),
+        }
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointManager) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn with_approval(mut self, approval: ApprovalPolicy) -> Self {
+        self.approval = approval;
+        self
     }
 }
 
@@ -304,14 +660,21 @@ impl Tool for CreateFileTool {
     }
 
     fn description(&self) -> &str {
-        "创建空文件，如果父目录不存在会询问是否创建。创建后可以使用 write_to_file 工具写入内容"
+        prompt_text("tools.prompt.create_file")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "path",
+            prompt_text("tools.prompt.file_path"),
+        )]
     }
 
     async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
         if args.len() != 1 {
-            return Err(crate::errors::AgentError::RuntimeError(
-                "create_file 需要一个文件路径参数".to_string(),
-            ));
+            return Err(crate::errors::AgentError::RuntimeError(crate::t!(
+                "tools.create_file_args"
+            )));
         }
 
         let file_path = &args[0];
@@ -321,51 +684,137 @@ impl Tool for CreateFileTool {
 
         let path = &final_path;
 
-        // 如果文件已存在，询问是否覆盖
+        // 如果文件已存在，展示清空文件的差异，由用户决定保留哪些内容
+        let mut rejected = None;
+        let mut content = String::new();
         if path.exists() {
-            print!("文件 '{}' 已存在，是否覆盖？(Y/N): ", path.display());
-            io::stdout().flush()?;
-
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-
-            if input.trim().to_lowercase() != "y" {
-                return Ok("用户取消创建文件".to_string());
+            let current = read_existing_content(path).await?;
+            match review_file_change(
+                display_path(&self.project_directory, path),
+                &current,
+                "",
+                self.approval,
+            )? {
+                ReviewOutcome::Unchanged => {
+                    return Ok(crate::t!(
+                        "tools.file_exists_empty",
+                        path = final_path.display()
+                    ));
+                }
+                ReviewOutcome::Accepted(_) => {}
+                ReviewOutcome::Partial {
+                    content: merged,
+                    rejected: refused,
+                } => {
+                    content = merged;
+                    rejected = Some(refused);
+                }
+                ReviewOutcome::Rejected(refused) => {
+                    return Ok(describe_rejected_changes(file_path, &refused, false));
+                }
             }
         }
 
         // 检查父目录是否存在
         if let Some(parent) = path.parent() {
             if !parent.exists() {
-                print!("父目录 '{}' 不存在，是否创建？(Y/N): ", parent.display());
-                io::stdout().flush()?;
-
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-
-                if input.trim().to_lowercase() != "y" {
-                    return Ok("用户取消创建文件".to_string());
+                let question = crate::t!("tools.create_parent", path = parent.display());
+                if !self.approval.confirm(&question)? {
+                    return Ok(crate::t!("tools.create_file_cancelled"));
                 }
 
+                record_checkpoint(self.checkpoints.as_ref(), path)?;
+
                 // 创建父目录
                 fs::create_dir_all(parent).await?;
-                println!("已创建父目录: {}", parent.display());
+                self.approval
+                    .notify(&crate::t!("tools.parent_created", path = parent.display()));
             }
         }
 
         // 创建空文件
-        fs::write(path, "").await?;
-        Ok(format!("文件创建成功: {}", final_path.display()))
+        record_checkpoint(self.checkpoints.as_ref(), path)?;
+        fs::write(path, content).await?;
+        match rejected {
+            Some(rejected) => Ok(describe_rejected_changes(file_path, &rejected, true)),
+            None => Ok(crate::t!("tools.file_created", path = final_path.display())),
+        }
     }
 }
 
 // 工具工厂函数
 pub fn create_default_tools(project_directory: String) -> ToolRegistry {
+    create_configured_tools(project_directory, &AgentConfig::default())
+}
+
+/// 按配置创建内置工具：审批规则决定各工具如何确认操作，http_request 使用配置的主机允许列表
+pub fn create_configured_tools(project_directory: String, config: &AgentConfig) -> ToolRegistry {
+    let approval = &config.approval;
+    let checkpoints = CheckpointManager::new(&project_directory);
+    let plan = PlanTracker::new(&project_directory);
+    let memory = MemoryStore::new(&project_directory);
+
     let mut registry = ToolRegistry::new();
     registry.register(ReadFileTool::new(project_directory.clone()));
-    registry.register(WriteFileTool::new(project_directory.clone()));
-    registry.register(RunTerminalCommandTool);
-    registry.register(CreateDirectoryTool::new(project_directory.clone()));
-    registry.register(CreateFileTool::new(project_directory));
+    registry.register(
+        WriteFileTool::new(project_directory.clone())
+            .with_checkpoints(checkpoints.clone())
+            .with_approval(approval.policy_for("write_to_file")),
+    );
+    registry.register(RunTerminalCommandTool::new(project_directory.clone()));
+    registry.register(
+        CreateDirectoryTool::new(project_directory.clone())
+            .with_checkpoints(checkpoints.clone())
+            .with_approval(approval.policy_for("create_directory")),
+    );
+    registry.register(
+        CreateFileTool::new(project_directory.clone())
+            .with_checkpoints(checkpoints.clone())
+            .with_approval(approval.policy_for("create_file")),
+    );
+    registry.register(GitStatusTool::new(project_directory.clone()));
+    registry.register(GitDiffTool::new(project_directory.clone()));
+    registry.register(GitLogTool::new(project_directory.clone()));
+    registry.register(GitCommitTool::new(project_directory.clone()));
+    registry.register(CargoTool::new(project_directory.clone()));
+    registry.register(CodeOutlineTool::new(project_directory.clone()));
+    registry.register(ReadSymbolTool::new(project_directory.clone()));
+    registry.register(FindRelevantTool::new(project_directory));
+    registry.register(UpdatePlanTool::new(plan.clone()));
+    registry.register(RememberTool::new(memory.clone()));
+    registry.register(RecallTool::new(memory.clone()));
+    registry.register(ForgetTool::new(memory.clone()));
+
+    // HTTP请求默认只允许访问本机
+    let mut http_tool = HttpRequestTool::new();
+    if let Some(hosts) = &config.tools.http_allowed_hosts {
+        http_tool = http_tool.with_allowed_hosts(hosts.clone());
+    }
+    registry.register(http_tool);
+    registry.set_checkpoints(checkpoints);
+    registry.set_plan(plan);
+    registry.set_memory(memory);
+    registry.set_approval(approval.clone());
     registry
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_terminal_commands_run_in_project_directory() {
+        let project = tempfile::tempdir().unwrap();
+        let directory = project.path().canonicalize().unwrap();
+        let tool = RunTerminalCommandTool::new(directory.display().to_string());
+
+        let output = tool.execute(vec!["pwd".to_string()]).await.unwrap();
+        assert_eq!(
+            output,
+            crate::t!(
+                "tools.command_succeeded",
+                output = format!("{}\n", directory.display())
+            )
+        );
+    }
+}
@@ -0,0 +1,614 @@
+use super::{Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::t;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::process::Command;
+
+const SUPPORTED_SUBCOMMANDS: &[&str] = &["check", "build", "test", "clippy"];
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+/// 观察结果中最多列出的诊断条数
+const MAX_DIAGNOSTICS: usize = 30;
+/// 每个失败测试最多保留的输出行数
+const MAX_FAILURE_LINES: usize = 20;
+/// 允许的额外参数；--config、--manifest-path、+toolchain 等可以让cargo执行任意程序，一律拒绝
+const ALLOWED_FLAGS: &[&str] = &[
+    "--lib",
+    "--all-targets",
+    "--workspace",
+    "--release",
+    "--all-features",
+    "--no-default-features",
+];
+/// 允许的带值参数，值可以跟在后面或写成 `--flag=值`
+const ALLOWED_VALUE_FLAGS: &[&str] = &["-p", "--package", "--bin", "--test", "--features"];
+/// `cargo test -- ...` 中允许交给测试程序的参数，其余只能是测试过滤条件
+const ALLOWED_TEST_FLAGS: &[&str] = &["--exact", "--nocapture", "--ignored", "--include-ignored"];
+/// `cargo clippy -- ...` 中允许的lint级别参数，后面跟lint名称
+const ALLOWED_LINT_FLAGS: &[&str] = &["-D", "-W", "-A"];
+
+/// 编译器给出的一条诊断
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub level: String,
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    fn from_json(message: &Value) -> Option<Self> {
+        let level = message["level"].as_str()?.to_string();
+        let spans = message["spans"].as_array()?;
+        // 没有位置信息的汇总消息（如 "aborting due to..."）对修复没有帮助
+        let primary = spans
+            .iter()
+            .find(|s| s["is_primary"].as_bool() == Some(true))?;
+
+        let suggestions = message["children"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|child| {
+                let text = child["message"].as_str()?;
+                let replacement = child["spans"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find_map(|span| span["suggested_replacement"].as_str());
+                // 只保留help和带替换文本的子消息，lint说明之类的note意义不大
+                if replacement.is_none() && child["level"].as_str() != Some("help") {
+                    return None;
+                }
+                Some(match replacement {
+                    Some(replacement) => format!("{}: `{}`", text, replacement),
+                    None => text.to_string(),
+                })
+            })
+            .collect();
+
+        Some(Self {
+            level,
+            code: message["code"]["code"].as_str().map(|c| c.to_string()),
+            message: message["message"].as_str().unwrap_or_default().to_string(),
+            file: primary["file_name"].as_str().map(|f| f.to_string()),
+            line: primary["line_start"].as_u64(),
+            column: primary["column_start"].as_u64(),
+            suggestions,
+        })
+    }
+
+    fn is_error(&self) -> bool {
+        self.level.starts_with("error")
+    }
+
+    fn render(&self) -> String {
+        let location = match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => format!("{}:{}:{} ", file, line, column),
+            (Some(file), _, _) => format!("{} ", file),
+            _ => String::new(),
+        };
+        let level = match &self.code {
+            Some(code) => format!("{}[{}]", self.level, code),
+            None => self.level.clone(),
+        };
+        let mut lines = vec![format!("  {}{}: {}", location, level, self.message)];
+        lines.extend(
+            self.suggestions
+                .iter()
+                .map(|suggestion| format!("    {}", t!("cargo.suggestion", text = suggestion))),
+        );
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub output: Vec<String>,
+}
+
+/// 从cargo输出中整理出的结构化结果
+#[derive(Debug, Default)]
+pub struct CargoReport {
+    pub diagnostics: Vec<Diagnostic>,
+    pub tests: Vec<TestCase>,
+    pub build_success: Option<bool>,
+}
+
+impl CargoReport {
+    fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.is_error()).count()
+    }
+
+    fn warning_count(&self) -> usize {
+        self.diagnostics.len() - self.error_count()
+    }
+
+    fn count_tests(&self, outcome: TestOutcome) -> usize {
+        self.tests.iter().filter(|t| t.outcome == outcome).count()
+    }
+}
+
+/// 解析 `--message-format=json` 的标准输出，测试结果来自其中夹杂的libtest文本
+pub fn parse_cargo_output(stdout: &str) -> CargoReport {
+    let mut report = CargoReport::default();
+    let mut current_failure: Option<usize> = None;
+
+    for line in stdout.lines() {
+        if line.starts_with('{') {
+            if let Ok(value) = serde_json::from_str::<Value>(line) {
+                match value["reason"].as_str() {
+                    Some("compiler-message") => {
+                        if let Some(diagnostic) = Diagnostic::from_json(&value["message"]) {
+                            // 同一个诊断可能在多个编译目标中重复出现
+                            if !report.diagnostics.contains(&diagnostic) {
+                                report.diagnostics.push(diagnostic);
+                            }
+                        }
+                    }
+                    Some("build-finished") => {
+                        report.build_success = value["success"].as_bool();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+        }
+
+        if let Some(rest) = line.strip_prefix("test ") {
+            if let Some((name, result)) = rest.rsplit_once(" ... ") {
+                let outcome = match result.trim() {
+                    "ok" => TestOutcome::Passed,
+                    "FAILED" => TestOutcome::Failed,
+                    r if r.starts_with("ignored") => TestOutcome::Ignored,
+                    _ => continue,
+                };
+                report.tests.push(TestCase {
+                    name: name.trim().to_string(),
+                    outcome,
+                    output: Vec::new(),
+                });
+                continue;
+            }
+        }
+
+        // 失败测试的输出块: ---- name stdout ----
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"))
+        {
+            current_failure = report.tests.iter().position(|t| t.name == name);
+            continue;
+        }
+        if line == "failures:"
+            || line.starts_with("test result:")
+            || line.starts_with("stack backtrace:")
+        {
+            current_failure = None;
+            continue;
+        }
+        if let Some(index) = current_failure {
+            let output = &mut report.tests[index].output;
+            if !line.trim().is_empty() && output.len() < MAX_FAILURE_LINES {
+                output.push(line.to_string());
+            }
+        }
+    }
+
+    report
+}
+
+/// 生成适合放入上下文的简洁结果
+pub fn format_report(subcommand: &str, success: bool, report: &CargoReport) -> String {
+    let mut lines = vec![t!(
+        "cargo.summary",
+        subcommand = subcommand,
+        result = if success {
+            t!("cargo.succeeded")
+        } else {
+            t!("cargo.failed")
+        },
+        errors = report.error_count(),
+        warnings = report.warning_count()
+    )];
+
+    for (title, is_error) in [("cargo.errors", true), ("cargo.warnings", false)] {
+        let items: Vec<&Diagnostic> = report
+            .diagnostics
+            .iter()
+            .filter(|d| d.is_error() == is_error)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        lines.push(t!(title));
+        lines.extend(items.iter().take(MAX_DIAGNOSTICS).map(|d| d.render()));
+        if items.len() > MAX_DIAGNOSTICS {
+            lines.push(format!(
+                "  {}",
+                t!(
+                    "cargo.more_diagnostics",
+                    count = items.len() - MAX_DIAGNOSTICS
+                )
+            ));
+        }
+    }
+
+    if !report.tests.is_empty() {
+        lines.push(t!(
+            "cargo.tests",
+            passed = report.count_tests(TestOutcome::Passed),
+            failed = report.count_tests(TestOutcome::Failed),
+            ignored = report.count_tests(TestOutcome::Ignored)
+        ));
+        let failed: Vec<&TestCase> = report
+            .tests
+            .iter()
+            .filter(|t| t.outcome == TestOutcome::Failed)
+            .collect();
+        if !failed.is_empty() {
+            lines.push(t!("cargo.failed_tests"));
+            for test in failed {
+                lines.push(format!("  {}", test.name));
+                lines.extend(test.output.iter().map(|l| format!("    {}", l)));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// 按shell规则拆分参数：支持单引号、双引号和反斜杠转义，不做变量或通配符展开
+fn split_args(input: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(t!("cargo.unclosed_quote")),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(t!("cargo.unclosed_quote")),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(t!("cargo.unclosed_quote")),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+fn disallowed_arg(arg: &str) -> String {
+    let allowed: Vec<&str> = ALLOWED_FLAGS
+        .iter()
+        .chain(ALLOWED_VALUE_FLAGS)
+        .copied()
+        .collect();
+    t!(
+        "cargo.disallowed_arg",
+        arg = arg,
+        allowed = allowed.join(", ")
+    )
+}
+
+/// 检查额外参数是否都在允许范围内，`--` 之后的参数交给测试程序或clippy
+fn check_args(subcommand: &str, args: &[String]) -> Result<(), String> {
+    let is_value = |value: Option<&String>| value.is_some_and(|v| !v.starts_with(['-', '+']));
+
+    let separator = args.iter().position(|arg| arg == "--");
+    let (cargo_args, rest) = match separator {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut filters = 0;
+    let mut iter = cargo_args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        if ALLOWED_FLAGS.contains(&arg.as_str()) {
+            continue;
+        }
+        if ALLOWED_VALUE_FLAGS.contains(&flag) {
+            if flag == arg && !is_value(iter.next()) {
+                return Err(t!("cargo.missing_value", flag = arg));
+            }
+            continue;
+        }
+        // cargo test 可以直接跟一个测试过滤条件
+        if subcommand == "test" && filters == 0 && !arg.starts_with(['-', '+']) {
+            filters += 1;
+            continue;
+        }
+        return Err(disallowed_arg(arg));
+    }
+
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        let allowed = match subcommand {
+            "test" => ALLOWED_TEST_FLAGS.contains(&arg.as_str()) || !arg.starts_with('-'),
+            "clippy" => ALLOWED_LINT_FLAGS.contains(&arg.as_str()) && is_value(iter.next()),
+            _ => false,
+        };
+        if !allowed {
+            return Err(disallowed_arg(arg));
+        }
+    }
+    Ok(())
+}
+
+pub struct CargoTool {
+    project_directory: String,
+    timeout: Duration,
+}
+
+impl CargoTool {
+    pub fn new(project_directory: String) -> Self {
+        Self {
+            project_directory,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for CargoTool {
+    fn name(&self) -> &str {
+        "cargo"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.cargo")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("subcommand", prompt_text("tools.prompt.cargo_subcommand")),
+            ToolParameter::optional("args", prompt_text("tools.prompt.cargo_args")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(AgentError::RuntimeError(t!("cargo.args")));
+        }
+
+        let subcommand = args[0].trim();
+        if !SUPPORTED_SUBCOMMANDS.contains(&subcommand) {
+            return Err(AgentError::RuntimeError(t!(
+                "cargo.unsupported_subcommand",
+                subcommand = subcommand,
+                available = SUPPORTED_SUBCOMMANDS.join(", ")
+            )));
+        }
+        let extra_args = match split_args(args.get(1).map(String::as_str).unwrap_or_default())
+            .and_then(|extra| check_args(subcommand, &extra).map(|()| extra))
+        {
+            Ok(extra) => extra,
+            Err(message) => return Ok(message),
+        };
+
+        // 通过检查的额外参数传给cargo，例如 -p 包名或测试过滤条件
+        let mut command = Command::new("cargo");
+        command
+            .arg(subcommand)
+            .arg("--message-format=json")
+            .args(&extra_args)
+            .current_dir(&self.project_directory)
+            .env("CARGO_TERM_COLOR", "never")
+            .kill_on_drop(true);
+
+        let output = tokio::time::timeout(self.timeout, command.output())
+            .await
+            .map_err(|_| {
+                AgentError::CommandExecutionError(t!(
+                    "cargo.timeout",
+                    subcommand = subcommand,
+                    seconds = self.timeout.as_secs()
+                ))
+            })??;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let report = parse_cargo_output(&stdout);
+        let mut result = format_report(subcommand, output.status.success(), &report);
+
+        // 没有任何结构化信息的失败（如Cargo.toml错误），附上stderr末尾作为线索
+        if !output.status.success() && report.diagnostics.is_empty() && report.tests.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(MAX_FAILURE_LINES).collect();
+            result.push_str("\nstderr:\n");
+            result.push_str(&tail.into_iter().rev().collect::<Vec<_>>().join("\n"));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK_OUTPUT: &str = r#"{"reason":"compiler-artifact","package_id":"demo"}
+{"reason":"compiler-message","message":{"level":"error","code":{"code":"E0425"},"message":"cannot find value `countr` in this scope","spans":[{"file_name":"src/main.rs","line_start":4,"column_start":20,"is_primary":true,"suggested_replacement":null}],"children":[{"level":"help","message":"a local variable with a similar name exists","spans":[{"file_name":"src/main.rs","line_start":4,"column_start":20,"is_primary":true,"suggested_replacement":"counter"}]}]}}
+{"reason":"compiler-message","message":{"level":"warning","code":null,"message":"unused variable: `x`","spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true}],"children":[{"level":"note","message":"`#[warn(unused_variables)]` on by default","spans":[]}]}}
+{"reason":"compiler-message","message":{"level":"error","code":null,"message":"aborting due to 1 previous error","spans":[],"children":[]}}
+{"reason":"build-finished","success":false}
+"#;
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let report = parse_cargo_output(CHECK_OUTPUT);
+        assert_eq!(report.build_success, Some(false));
+        assert_eq!(report.diagnostics.len(), 2);
+
+        let error = &report.diagnostics[0];
+        assert_eq!(error.code.as_deref(), Some("E0425"));
+        assert_eq!(error.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(error.line, Some(4));
+        assert_eq!(
+            error.suggestions,
+            vec!["a local variable with a similar name exists: `counter`"]
+        );
+
+        let text = format_report("check", false, &report);
+        assert!(text.starts_with(&t!(
+            "cargo.summary",
+            subcommand = "check",
+            result = t!("cargo.failed"),
+            errors = 1,
+            warnings = 1
+        )));
+        assert!(text.contains("src/main.rs:4:20 error[E0425]: cannot find value"));
+        assert!(text.contains(&t!(
+            "cargo.suggestion",
+            text = "a local variable with a similar name exists: `counter`"
+        )));
+        assert!(text.contains("src/lib.rs:2:9 warning: unused variable"));
+        assert!(!text.contains("on by default"));
+    }
+
+    #[test]
+    fn test_parse_test_results() {
+        let stdout = "\
+{\"reason\":\"build-finished\",\"success\":true}
+
+running 3 tests
+test parser::tests::ok_case ... ok
+test parser::tests::bad_case ... FAILED
+test parser::tests::slow ... ignored, needs network
+
+failures:
+
+---- parser::tests::bad_case stdout ----
+thread 'parser::tests::bad_case' panicked at src/parser.rs:10:5:
+assertion `left == right` failed
+
+failures:
+    parser::tests::bad_case
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+";
+        let report = parse_cargo_output(stdout);
+        assert_eq!(report.tests.len(), 3);
+        assert_eq!(report.tests[1].outcome, TestOutcome::Failed);
+        assert_eq!(report.tests[1].output.len(), 2);
+
+        let text = format_report("test", false, &report);
+        assert!(text.contains(&t!("cargo.tests", passed = 1, failed = 1, ignored = 1)));
+        assert!(text
+            .contains("  parser::tests::bad_case\n    thread 'parser::tests::bad_case' panicked"));
+    }
+
+    #[test]
+    fn test_split_args_follows_shell_quoting() {
+        assert_eq!(
+            split_args(r#"-p my_crate "parser handles quotes" -- 'a b' c\ d"#).unwrap(),
+            vec![
+                "-p",
+                "my_crate",
+                "parser handles quotes",
+                "--",
+                "a b",
+                "c d"
+            ]
+        );
+        assert_eq!(split_args(r#"say"\"hi\"""#).unwrap(), vec![r#"say"hi""#]);
+        assert_eq!(split_args("  ").unwrap(), Vec::<String>::new());
+        assert_eq!(split_args("'open").unwrap_err(), t!("cargo.unclosed_quote"));
+    }
+
+    #[test]
+    fn test_only_allowed_args_reach_cargo() {
+        let check =
+            |subcommand: &str, args: &str| check_args(subcommand, &split_args(args).unwrap());
+
+        assert!(check("test", "-p my_crate parser").is_ok());
+        assert!(check(
+            "test",
+            "--package=my_crate --lib -- 'parser::tests' --exact"
+        )
+        .is_ok());
+        assert!(check("build", "--all-targets --features \"a b\" --bin demo").is_ok());
+        assert!(check("clippy", "--all-targets -- -D warnings").is_ok());
+
+        for (subcommand, args, rejected) in [
+            ("build", "--config build.rustc-wrapper=/tmp/x", "--config"),
+            (
+                "test",
+                "--config=target.x86_64-unknown-linux-gnu.runner=sh",
+                "--config=target.x86_64-unknown-linux-gnu.runner=sh",
+            ),
+            (
+                "check",
+                "--manifest-path /elsewhere/Cargo.toml",
+                "--manifest-path",
+            ),
+            ("check", "+nightly", "+nightly"),
+            ("check", "parser", "parser"),
+            ("test", "one two", "two"),
+            ("test", "-- --logfile /tmp/out", "--logfile"),
+            ("clippy", "-- -C linker=/tmp/x", "-C"),
+            ("build", "-- -D warnings", "-D"),
+        ] {
+            assert_eq!(
+                check(subcommand, args).unwrap_err(),
+                disallowed_arg(rejected)
+            );
+        }
+        assert_eq!(
+            check("test", "-p --config").unwrap_err(),
+            t!("cargo.missing_value", flag = "-p")
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use super::{safe_resolve_path, Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// 项目级自定义工具配置文件
+pub const CUSTOM_TOOLS_FILE: &str = ".rust-agent/tools.toml";
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+/// stdout/stderr 各自最多保留的字符数（保留末尾，错误信息通常在最后）
+const MAX_OUTPUT_CHARS: usize = 8000;
+
+#[derive(Debug, Deserialize)]
+struct CustomToolsFile {
+    #[serde(default, rename = "tool")]
+    tools: Vec<CustomToolConfig>,
+}
+
+/// `[[tool]]` 配置项
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomToolConfig {
+    pub name: String,
+    pub description: String,
+    /// 命令模板，`{参数名}` 会被替换为转义后的参数值，`{{`/`}}` 表示字面量花括号
+    pub command: String,
+    #[serde(default)]
+    pub parameters: Vec<CustomParameterConfig>,
+    /// 超时秒数
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// 工作目录，相对于项目目录，默认为项目目录
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub requires_approval: bool,
+    /// 命令不修改任何状态，同一轮中可以与其他只读工具并行执行
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomParameterConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    /// 未传入时使用的默认值；可选参数没有默认值时，占位符替换为空
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+pub(super) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 检查参数名是否有效且不重复，必填参数必须在可选参数之前
+pub(super) fn validate_parameters(parameters: &[CustomParameterConfig]) -> Result<(), String> {
+    let mut declared = HashSet::new();
+    for parameter in parameters {
+        if !is_valid_name(&parameter.name) || !declared.insert(parameter.name.as_str()) {
+            return Err(t!("custom.invalid_parameter", name = parameter.name));
+        }
+    }
+    if let Some(position) = parameters.iter().position(|p| !p.required) {
+        if let Some(late) = parameters[position..].iter().find(|p| p.required) {
+            return Err(t!("custom.required_after_optional", name = late.name));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn tool_parameters(parameters: &[CustomParameterConfig]) -> Vec<ToolParameter> {
+    parameters
+        .iter()
+        .map(|p| {
+            if p.required && p.default.is_none() {
+                ToolParameter::required(&p.name, &p.description)
+            } else {
+                ToolParameter::optional(&p.name, &p.description)
+            }
+        })
+        .collect()
+}
+
+/// 将位置参数与声明的参数对应起来，未传入的参数使用默认值
+pub(super) fn bind_arguments<'a>(
+    tool_name: &str,
+    parameters: &'a [CustomParameterConfig],
+    args: &[String],
+) -> AgentResult<Vec<(&'a str, Option<String>)>> {
+    if args.len() > parameters.len() {
+        return Err(AgentError::ToolExecutionError(t!(
+            "tools.too_many_arguments",
+            tool = tool_name,
+            max = parameters.len(),
+            actual = args.len()
+        )));
+    }
+
+    let mut values = Vec::with_capacity(parameters.len());
+    for (index, parameter) in parameters.iter().enumerate() {
+        // 可选参数传入空字符串视为未传入（MCP等调用方用空字符串占位）
+        let value = args
+            .get(index)
+            .filter(|arg| parameter.required || !arg.is_empty())
+            .cloned()
+            .or_else(|| parameter.default.clone());
+        if value.is_none() && parameter.required {
+            return Err(AgentError::ToolExecutionError(t!(
+                "tools.missing_argument",
+                tool = tool_name,
+                name = parameter.name
+            )));
+        }
+        values.push((parameter.name.as_str(), value));
+    }
+    Ok(values)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// 解析命令模板
+fn parse_template(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(t!("custom.unclosed_placeholder", name = name)),
+                    }
+                }
+                if !is_valid_name(&name) {
+                    return Err(t!("custom.invalid_placeholder", name = name));
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Placeholder(name));
+            }
+            '}' => return Err(t!("custom.unmatched_brace")),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+/// 按POSIX shell规则转义参数，只含安全字符时原样保留
+fn shell_quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// 保留输出末尾，并注明被省略的字符数
+fn tail_output(output: &str) -> String {
+    let total = output.chars().count();
+    if total <= MAX_OUTPUT_CHARS {
+        return output.trim_end().to_string();
+    }
+    let kept: String = output.chars().skip(total - MAX_OUTPUT_CHARS).collect();
+    format!(
+        "{}\n{}",
+        t!("custom.truncated", count = total - MAX_OUTPUT_CHARS),
+        kept.trim_end()
+    )
+}
+
+/// 由配置文件声明的命令工具
+pub struct CustomTool {
+    config: CustomToolConfig,
+    segments: Vec<Segment>,
+    working_dir: PathBuf,
+    timeout: Duration,
+}
+
+impl CustomTool {
+    pub fn new(project_directory: &str, config: CustomToolConfig) -> AgentResult<Self> {
+        let invalid = |message: String| {
+            AgentError::ParseError(t!(
+                "custom.invalid_tool",
+                name = config.name,
+                error = message
+            ))
+        };
+
+        if !is_valid_name(&config.name) {
+            return Err(invalid(t!("custom.invalid_name")));
+        }
+
+        validate_parameters(&config.parameters).map_err(invalid)?;
+
+        let segments = parse_template(&config.command).map_err(invalid)?;
+        for segment in &segments {
+            if let Segment::Placeholder(name) = segment {
+                if !config.parameters.iter().any(|p| &p.name == name) {
+                    return Err(invalid(t!("custom.undeclared_placeholder", name = name)));
+                }
+            }
+        }
+
+        // 工作目录同样限制在项目目录内
+        let working_dir = match &config.working_dir {
+            Some(dir) => safe_resolve_path(project_directory, dir)?,
+            None => PathBuf::from(project_directory),
+        };
+        let timeout = Duration::from_secs(config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+        Ok(Self {
+            config,
+            segments,
+            working_dir,
+            timeout,
+        })
+    }
+
+    /// 用位置参数填充模板，生成要执行的命令
+    fn render_command(&self, args: &[String]) -> AgentResult<String> {
+        let values = bind_arguments(&self.config.name, &self.config.parameters, args)?;
+
+        let mut command = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => command.push_str(text),
+                Segment::Placeholder(name) => {
+                    if let Some((_, Some(value))) = values.iter().find(|(n, _)| n == name) {
+                        command.push_str(&shell_quote(value));
+                    }
+                }
+            }
+        }
+        Ok(command.trim().to_string())
+    }
+}
+
+#[async_trait]
+impl Tool for CustomTool {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn description(&self) -> &str {
+        &self.config.description
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.config.requires_approval
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.config.read_only
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        tool_parameters(&self.config.parameters)
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let command_line = self.render_command(&args)?;
+        if !self.working_dir.is_dir() {
+            return Err(AgentError::ToolExecutionError(t!(
+                "custom.missing_working_dir",
+                path = self.working_dir.display()
+            )));
+        }
+
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&command_line)
+            .current_dir(&self.working_dir)
+            .kill_on_drop(true);
+
+        let output = tokio::time::timeout(self.timeout, command.output())
+            .await
+            .map_err(|_| {
+                AgentError::CommandExecutionError(t!(
+                    "custom.timeout",
+                    tool = self.config.name,
+                    seconds = self.timeout.as_secs(),
+                    command = command_line
+                ))
+            })??;
+
+        let mut lines = vec![
+            format!("$ {}", command_line),
+            match output.status.code() {
+                Some(code) => t!("custom.exit_code", code = code),
+                None => t!("custom.killed_by_signal"),
+            },
+        ];
+        for (label, stream) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                lines.push(format!("{}:\n{}", label, tail_output(&text)));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+/// 读取项目中声明的自定义工具，配置文件不存在时返回空列表
+pub fn load_custom_tools(project_directory: &str) -> AgentResult<Vec<CustomTool>> {
+    let path = Path::new(project_directory).join(CUSTOM_TOOLS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    let file: CustomToolsFile = toml::from_str(&content).map_err(|e| {
+        AgentError::ParseError(t!(
+            "custom.invalid_config",
+            path = path.display(),
+            error = e
+        ))
+    })?;
+
+    let mut names = HashSet::new();
+    let mut tools = Vec::with_capacity(file.tools.len());
+    for config in file.tools {
+        if !names.insert(config.name.clone()) {
+            return Err(AgentError::ParseError(t!(
+                "custom.duplicate_tool",
+                name = config.name
+            )));
+        }
+        tools.push(CustomTool::new(project_directory, config)?);
+    }
+    Ok(tools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(project: &tempfile::TempDir, content: &str) -> String {
+        let dir = project.path().join(".rust-agent");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tools.toml"), content).unwrap();
+        project.path().display().to_string()
+    }
+
+    #[test]
+    fn test_renders_template_with_escaped_arguments() {
+        assert_eq!(shell_quote("src/main.rs"), "src/main.rs");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's; rm -rf /"), r"'it'\''s; rm -rf /'");
+        assert_eq!(shell_quote(""), "''");
+
+        let config = CustomToolConfig {
+            name: "lint".to_string(),
+            description: String::new(),
+            command: "npm run lint -- {path} {fix} {{x}}".to_string(),
+            parameters: vec![
+                CustomParameterConfig {
+                    name: "path".to_string(),
+                    description: String::new(),
+                    required: true,
+                    default: None,
+                },
+                CustomParameterConfig {
+                    name: "fix".to_string(),
+                    description: String::new(),
+                    required: false,
+                    default: None,
+                },
+            ],
+            timeout: None,
+            working_dir: None,
+            requires_approval: false,
+            read_only: false,
+        };
+        let tool = CustomTool::new("/tmp", config).unwrap();
+        assert_eq!(
+            tool.render_command(&["my file.ts".to_string()]).unwrap(),
+            "npm run lint -- 'my file.ts'  {x}"
+        );
+        assert_eq!(
+            tool.render_command(&["a.ts".to_string(), "--fix".to_string()])
+                .unwrap(),
+            "npm run lint -- a.ts --fix {x}"
+        );
+        assert!(tool.render_command(&[]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_configs() {
+        let project = tempfile::tempdir().unwrap();
+        let dir = write_config(
+            &project,
+            r#"
+            [[tool]]
+            name = "lint"
+            description = "lint"
+            command = "eslint {path}"
+            "#,
+        );
+        let error = load_custom_tools(&dir).err().unwrap().to_string();
+        assert!(error.contains(&t!("custom.undeclared_placeholder", name = "path")));
+
+        write_config(
+            &project,
+            r#"
+            [[tool]]
+            name = "outside"
+            description = "outside"
+            command = "ls"
+            working_dir = "/"
+            "#,
+        );
+        assert!(load_custom_tools(&dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runs_declared_tools() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("web")).unwrap();
+        let dir = write_config(
+            &project,
+            r#"
+            [[tool]]
+            name = "greet"
+            description = "打招呼"
+            command = "printf '%s:%s' \"$(basename \"$PWD\")\" {name}"
+            working_dir = "web"
+            requires_approval = true
+            parameters = [{ name = "name", description = "名字", default = "world" }]
+
+            [[tool]]
+            name = "slow"
+            description = "超时"
+            command = "sleep 5"
+            timeout = 1
+            read_only = true
+            "#,
+        );
+
+        let tools = load_custom_tools(&dir).unwrap();
+        let greet = &tools[0];
+        assert!(greet.requires_approval());
+        assert!(!greet.is_read_only());
+        assert!(tools[1].is_read_only());
+        assert!(!greet.parameters()[0].required);
+
+        let output = greet.execute(vec!["$(whoami)".to_string()]).await.unwrap();
+        assert!(output.contains(&t!("custom.exit_code", code = 0)));
+        assert!(output.contains("web:$(whoami)"));
+        let output = greet.execute(vec![]).await.unwrap();
+        assert!(output.contains("web:world"));
+
+        let error = tools[1].execute(vec![]).await.unwrap_err();
+        assert!(error.to_string().contains(&t!(
+            "custom.timeout",
+            tool = "slow",
+            seconds = 1,
+            command = "sleep 5"
+        )));
+    }
+}
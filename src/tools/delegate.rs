@@ -0,0 +1,314 @@
+use super::{Tool, ToolParameter, ToolRegistry};
+use crate::agent::ReActAgent;
+use crate::config::AgentConfig;
+use crate::errors::AgentResult;
+use crate::i18n::prompt_text;
+use crate::t;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+pub const DELEGATE_TOOL: &str = "delegate";
+/// 子Agent对话记录的保存目录，相对于项目目录
+pub const DELEGATE_TRANSCRIPTS_DIR: &str = ".rust-agent/delegates";
+
+/// 本进程内子任务的编号，用于区分输出和对话记录文件
+static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// 子任务的并发名额，整个委派树共用 `delegate.max_concurrent` 个
+#[derive(Clone)]
+pub struct DelegateSlots {
+    shared: Arc<Semaphore>,
+    /// 父Agent占用的名额：父Agent等待子任务时不在运行，其中一个子任务可以沿用，
+    /// 避免名额用尽时嵌套委派互相等待
+    parent: Option<Arc<Semaphore>>,
+}
+
+impl DelegateSlots {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            shared: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            parent: None,
+        }
+    }
+
+    /// 子Agent委派时使用的名额，与当前委派树共用上限
+    fn for_child(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            parent: Some(Arc::new(Semaphore::new(1))),
+        }
+    }
+
+    /// 等待一个名额，优先沿用父Agent的名额
+    async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.parent {
+            Some(parent) => tokio::select! {
+                biased;
+                permit = parent.acquire() => permit.ok(),
+                permit = self.shared.acquire() => permit.ok(),
+            },
+            None => self.shared.acquire().await.ok(),
+        }
+    }
+}
+
+/// 把一个子任务交给新的Agent完成，只有子Agent的最终答案作为观察结果返回
+pub struct DelegateTool {
+    /// 子Agent可以使用的工具
+    tools: ToolRegistry,
+    config: AgentConfig,
+    project_directory: String,
+    /// 子Agent的嵌套层数，顶层Agent为 0
+    depth: usize,
+    slots: DelegateSlots,
+}
+
+impl DelegateTool {
+    /// `tools` 为父Agent的工具，未允许修改时只保留其中的只读工具；
+    /// `slots` 为父Agent所在委派树的并发名额
+    pub fn new(
+        tools: &ToolRegistry,
+        config: AgentConfig,
+        project_directory: String,
+        depth: usize,
+        slots: DelegateSlots,
+    ) -> Self {
+        let allow_mutating = config.delegate.allow_mutating;
+        let tools = tools
+            .subset(|tool| tool.name() != DELEGATE_TOOL && (allow_mutating || tool.is_read_only()));
+        Self {
+            tools,
+            config,
+            project_directory,
+            depth,
+            slots,
+        }
+    }
+
+    /// 子Agent可以使用的工具名称，按名称排序
+    pub fn available_tools(&self) -> Vec<&str> {
+        self.tools
+            .tools()
+            .into_iter()
+            .map(|tool| tool.name())
+            .collect()
+    }
+
+    /// 按逗号分隔的工具名称选出子Agent的工具，为空时使用全部可用工具
+    fn select_tools(&self, names: &str) -> Result<ToolRegistry, String> {
+        let names: Vec<&str> = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.is_empty() {
+            return Ok(self.tools.subset(|_| true));
+        }
+        let unknown: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|name| self.tools.get_tool(name).is_none())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(t!(
+                "delegate.unknown_tools",
+                names = unknown.join(", "),
+                available = self.available_tools().join(", ")
+            ));
+        }
+        Ok(self.tools.subset(|tool| names.contains(&tool.name())))
+    }
+
+    /// 子Agent的工具调用上限，不超过 `delegate.max_steps`
+    fn step_limit(&self, max_steps: &str) -> Result<usize, String> {
+        let limit = self.config.delegate.max_steps;
+        if max_steps.trim().is_empty() {
+            return Ok(limit);
+        }
+        match max_steps.trim().parse::<usize>() {
+            Ok(steps) if steps > 0 => Ok(steps.min(limit)),
+            _ => Err(t!("delegate.invalid_max_steps", value = max_steps)),
+        }
+    }
+
+    /// 与Agent的 `verbose` 一致：只有顶层Agent委派的子任务输出开始和结束
+    fn verbose(&self) -> bool {
+        self.depth == 1
+    }
+
+    fn transcript_path(&self, task_id: usize) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Path::new(&self.project_directory)
+            .join(DELEGATE_TRANSCRIPTS_DIR)
+            .join(format!("{}-{}.json", timestamp, task_id))
+    }
+}
+
+#[async_trait]
+impl Tool for DelegateTool {
+    fn name(&self) -> &str {
+        DELEGATE_TOOL
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.delegate")
+    }
+
+    /// 子Agent只能使用只读工具时，委派本身也不会修改任何内容
+    fn is_read_only(&self) -> bool {
+        !self.config.delegate.allow_mutating
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("task", prompt_text("tools.prompt.delegate_task")),
+            ToolParameter::optional("tools", prompt_text("tools.prompt.delegate_tools")),
+            ToolParameter::optional("max_steps", prompt_text("tools.prompt.delegate_max_steps")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let arg = |index: usize| args.get(index).map(String::as_str).unwrap_or_default();
+        let task = arg(0).trim();
+        if task.is_empty() {
+            return Ok(t!("delegate.task_required"));
+        }
+        let tools = match self.select_tools(arg(1)) {
+            Ok(tools) => tools,
+            Err(message) => return Ok(message),
+        };
+        let max_steps = match self.step_limit(arg(2)) {
+            Ok(steps) => steps,
+            Err(message) => return Ok(message),
+        };
+
+        let mut config = self.config.clone();
+        config.limits.max_steps = max_steps;
+        let mut agent = ReActAgent::with_depth(
+            tools,
+            config,
+            self.project_directory.clone(),
+            self.depth,
+            self.slots.for_child(),
+        )?;
+
+        // 整个委派树超过并发上限的子任务等待前面的完成
+        let _permit = self.slots.acquire().await;
+        let task_id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+        if self.verbose() {
+            println!("\n\n{}", t!("delegate.started", id = task_id, task = task));
+        }
+
+        let result = agent.run(task).await;
+
+        let path = self.transcript_path(task_id);
+        match agent.save_conversation(&path) {
+            Ok(()) if !self.verbose() => {}
+            Ok(()) => println!(
+                "\n\n{}",
+                t!(
+                    "delegate.finished",
+                    id = task_id,
+                    path = path
+                        .strip_prefix(&self.project_directory)
+                        .unwrap_or(&path)
+                        .display()
+                )
+            ),
+            Err(e) => eprintln!("\n\n{}", t!("delegate.save_failed", error = e)),
+        }
+
+        Ok(match result {
+            Ok(answer) => answer,
+            Err(e) => t!("delegate.failed", error = e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ReadFileTool, RunTerminalCommandTool};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn delegate_tool(config: AgentConfig) -> DelegateTool {
+        let mut tools = ToolRegistry::new();
+        tools.register(ReadFileTool::new("/tmp".to_string()));
+        tools.register(RunTerminalCommandTool::new("/tmp".to_string()));
+        let slots = DelegateSlots::new(config.delegate.max_concurrent);
+        DelegateTool::new(&tools, config, "/tmp".to_string(), 1, slots)
+    }
+
+    #[tokio::test]
+    async fn test_nested_delegates_share_the_concurrency_limit() {
+        let wait = Duration::from_millis(50);
+        let top = DelegateSlots::new(1);
+        let running = top.acquire().await.unwrap();
+        // 顶层的名额用尽后，同层的其他子任务需要等待
+        assert!(timeout(wait, top.acquire()).await.is_err());
+
+        // 子Agent继续委派时沿用自己的名额，不会与父Agent互相等待
+        let child = top.for_child();
+        let nested = timeout(wait, child.acquire()).await.unwrap().unwrap();
+        assert!(timeout(wait, child.acquire()).await.is_err());
+        assert!(Arc::ptr_eq(&child.shared, &top.shared));
+
+        drop(nested);
+        drop(running);
+        assert!(timeout(wait, top.acquire()).await.is_ok());
+    }
+
+    #[test]
+    fn test_children_only_get_read_only_tools_by_default() {
+        let tool = delegate_tool(AgentConfig::default());
+        assert!(tool.is_read_only());
+        assert_eq!(tool.available_tools(), vec!["read_file"]);
+
+        let mut config = AgentConfig::default();
+        config.delegate.allow_mutating = true;
+        let tool = delegate_tool(config);
+        assert!(!tool.is_read_only());
+        assert_eq!(
+            tool.available_tools(),
+            vec!["read_file", "run_terminal_command"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_are_reported_to_the_model() {
+        let tool = delegate_tool(AgentConfig::default());
+
+        let observation = tool
+            .execute(vec![
+                "检查代码".to_string(),
+                "run_terminal_command".to_string(),
+            ])
+            .await
+            .unwrap();
+        assert!(observation.contains("run_terminal_command"));
+        assert!(observation.contains("read_file"));
+
+        let observation = tool
+            .execute(vec![
+                "检查代码".to_string(),
+                String::new(),
+                "多步".to_string(),
+            ])
+            .await
+            .unwrap();
+        assert!(observation.contains("多步"));
+
+        assert_eq!(tool.step_limit("").unwrap(), 20);
+        assert_eq!(tool.step_limit("5").unwrap(), 5);
+        assert_eq!(tool.step_limit("100").unwrap(), 20);
+        assert!(tool.step_limit("0").is_err());
+    }
+}
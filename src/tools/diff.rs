@@ -0,0 +1,323 @@
+use crate::approval::ApprovalPolicy;
+use similar::{DiffOp, DiffTag, TextDiff};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// 每个hunk前后保留的上下文行数
+const CONTEXT_LINES: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// 文件当前内容与待写入内容之间的差异
+pub struct FileDiff {
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+    ops: Vec<DiffOp>,
+    hunks: Vec<Vec<DiffOp>>,
+}
+
+impl FileDiff {
+    pub fn compute(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        Self {
+            old_lines: diff.old_slices().iter().map(|s| s.to_string()).collect(),
+            new_lines: diff.new_slices().iter().map(|s| s.to_string()).collect(),
+            ops: diff.ops().to_vec(),
+            hunks: diff.grouped_ops(CONTEXT_LINES),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.hunks.len()
+    }
+
+    /// 形如 `@@ -1,3 +1,4 @@` 的hunk头
+    pub fn hunk_header(&self, index: usize) -> String {
+        let hunk = &self.hunks[index];
+        let (first, last) = (&hunk[0], &hunk[hunk.len() - 1]);
+        let old_start = first.old_range().start;
+        let new_start = first.new_range().start;
+        let old_len = last.old_range().end - old_start;
+        let new_len = last.new_range().end - new_start;
+        format!(
+            "@@ -{},{} +{},{} @@",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        )
+    }
+
+    /// 渲染单个hunk，`colored` 为 true 时带终端颜色
+    pub fn render_hunk(&self, index: usize, colored: bool) -> String {
+        let paint = |color: &str, text: String| {
+            if colored {
+                format!("{}{}{}", color, text, RESET)
+            } else {
+                text
+            }
+        };
+
+        let mut lines = vec![paint(CYAN, self.hunk_header(index))];
+        for op in &self.hunks[index] {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let removed = &self.old_lines[old_range];
+            let added = &self.new_lines[new_range];
+            match tag {
+                DiffTag::Equal => {
+                    lines.extend(removed.iter().map(|l| format!(" {}", trim_newline(l))))
+                }
+                DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                    lines.extend(
+                        removed
+                            .iter()
+                            .map(|l| paint(RED, format!("-{}", trim_newline(l)))),
+                    );
+                    lines.extend(
+                        added
+                            .iter()
+                            .map(|l| paint(GREEN, format!("+{}", trim_newline(l)))),
+                    );
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// 渲染完整的unified diff
+    pub fn render(&self, path: &Path, colored: bool) -> String {
+        let header = format!("--- a/{0}\n+++ b/{0}", path.display());
+        let mut parts = vec![if colored {
+            format!("{}{}{}", BOLD, header, RESET)
+        } else {
+            header
+        }];
+        parts.extend((0..self.hunk_count()).map(|i| self.render_hunk(i, colored)));
+        parts.join("\n")
+    }
+
+    /// 只应用被接受的hunk，未接受的部分保留原内容
+    pub fn apply(&self, accepted: &[bool]) -> String {
+        let mut result = String::new();
+        for op in &self.ops {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let take_new = tag != DiffTag::Equal
+                && self
+                    .hunks
+                    .iter()
+                    .position(|hunk| hunk.contains(op))
+                    .is_some_and(|index| accepted.get(index).copied().unwrap_or(false));
+
+            let lines = if take_new {
+                &self.new_lines[new_range]
+            } else {
+                &self.old_lines[old_range]
+            };
+            lines.iter().for_each(|line| result.push_str(line));
+        }
+        result
+    }
+}
+
+fn trim_newline(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .unwrap_or(line)
+}
+
+/// 用户审阅修改后的结果
+pub enum ReviewOutcome {
+    /// 内容没有变化，无需写入
+    Unchanged,
+    /// 全部接受，写入完整的新内容
+    Accepted(String),
+    /// 部分接受，写入合并后的内容，并附带被拒绝部分的说明
+    Partial { content: String, rejected: String },
+    /// 全部拒绝，附带被拒绝部分的说明
+    Rejected(String),
+}
+
+/// 展示彩色diff并询问用户：全部接受、拒绝或逐个选择hunk；非交互策略下直接全部接受或拒绝
+pub fn review_file_change(
+    path: &Path,
+    old: &str,
+    new: &str,
+    approval: ApprovalPolicy,
+) -> io::Result<ReviewOutcome> {
+    review_file_change_from(path, old, new, approval, &mut io::stdin().lock())
+}
+
+/// 从 `input` 读取用户的选择；输入结束（如管道已关闭）时拒绝尚未确认的修改
+fn review_file_change_from(
+    path: &Path,
+    old: &str,
+    new: &str,
+    approval: ApprovalPolicy,
+    input: &mut dyn BufRead,
+) -> io::Result<ReviewOutcome> {
+    let diff = FileDiff::compute(old, new);
+    if diff.is_empty() {
+        return Ok(ReviewOutcome::Unchanged);
+    }
+
+    let all_rejected = || {
+        (0..diff.hunk_count())
+            .map(|i| diff.render_hunk(i, false))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    match approval {
+        ApprovalPolicy::AutoApprove => return Ok(ReviewOutcome::Accepted(new.to_string())),
+        ApprovalPolicy::Reject => return Ok(ReviewOutcome::Rejected(all_rejected())),
+        ApprovalPolicy::Interactive => {}
+    }
+
+    println!(
+        "\n\n{}",
+        crate::t!("review.about_to_modify", path = path.display())
+    );
+    println!("{}", diff.render(path, true));
+
+    loop {
+        print!(
+            "{}",
+            crate::t!("review.apply_all", count = diff.hunk_count())
+        );
+        io::stdout().flush()?;
+
+        let mut choice = String::new();
+        if input.read_line(&mut choice)? == 0 {
+            println!();
+            return Ok(ReviewOutcome::Rejected(all_rejected()));
+        }
+
+        match choice.trim().to_lowercase().as_str() {
+            "a" | "y" => return Ok(ReviewOutcome::Accepted(new.to_string())),
+            "r" | "n" => return Ok(ReviewOutcome::Rejected(all_rejected())),
+            "s" => break,
+            _ => println!("{}", crate::t!("review.invalid_choice")),
+        }
+    }
+
+    let mut accepted = Vec::with_capacity(diff.hunk_count());
+    for index in 0..diff.hunk_count() {
+        println!(
+            "\n[{}/{}]\n{}",
+            index + 1,
+            diff.hunk_count(),
+            diff.render_hunk(index, true)
+        );
+        print!("{}", crate::t!("review.apply_hunk"));
+        io::stdout().flush()?;
+
+        let mut choice = String::new();
+        if input.read_line(&mut choice)? == 0 {
+            println!();
+            accepted.resize(diff.hunk_count(), false);
+            break;
+        }
+        accepted.push(choice.trim().to_lowercase() == "y");
+    }
+
+    if accepted.iter().all(|a| *a) {
+        return Ok(ReviewOutcome::Accepted(new.to_string()));
+    }
+
+    let rejected = accepted
+        .iter()
+        .enumerate()
+        .filter(|(_, accepted)| !**accepted)
+        .map(|(index, _)| diff.render_hunk(index, false))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if accepted.iter().any(|a| *a) {
+        Ok(ReviewOutcome::Partial {
+            content: diff.apply(&accepted),
+            rejected,
+        })
+    } else {
+        Ok(ReviewOutcome::Rejected(rejected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    const NEW: &str = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nK\nl\n";
+
+    #[test]
+    fn test_compute_splits_distant_changes_into_hunks() {
+        let diff = FileDiff::compute(OLD, NEW);
+        assert_eq!(diff.hunk_count(), 2);
+        assert_eq!(diff.hunk_header(0), "@@ -1,5 +1,5 @@");
+
+        let rendered = diff.render(Path::new("letters.txt"), false);
+        assert!(rendered.starts_with("--- a/letters.txt\n+++ b/letters.txt"));
+        assert!(rendered.contains("-b\n+B"));
+        assert!(rendered.contains("-k\n+K"));
+    }
+
+    #[test]
+    fn test_apply_selected_hunks() {
+        let diff = FileDiff::compute(OLD, NEW);
+        assert_eq!(diff.apply(&[true, true]), NEW);
+        assert_eq!(diff.apply(&[false, false]), OLD);
+        assert_eq!(
+            diff.apply(&[true, false]),
+            "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n"
+        );
+        assert_eq!(
+            diff.apply(&[false, true]),
+            "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nK\nl\n"
+        );
+    }
+
+    #[test]
+    fn test_new_file_diff() {
+        let diff = FileDiff::compute("", "hello\nworld");
+        assert_eq!(diff.hunk_count(), 1);
+        assert_eq!(diff.hunk_header(0), "@@ -0,0 +1,2 @@");
+        assert!(FileDiff::compute("same", "same").is_empty());
+    }
+
+    #[test]
+    fn test_closed_input_rejects_unconfirmed_hunks() {
+        let path = Path::new("letters.txt");
+        let outcome = review_file_change_from(
+            path,
+            OLD,
+            NEW,
+            ApprovalPolicy::Interactive,
+            &mut io::empty(),
+        )
+        .unwrap();
+        assert!(
+            matches!(outcome, ReviewOutcome::Rejected(rejected) if rejected.contains("-k\n+K"))
+        );
+
+        let mut input = "s\ny\n".as_bytes();
+        let outcome =
+            review_file_change_from(path, OLD, NEW, ApprovalPolicy::Interactive, &mut input)
+                .unwrap();
+        match outcome {
+            ReviewOutcome::Partial { content, rejected } => {
+                assert_eq!(content, "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n");
+                assert!(rejected.contains("-k\n+K"));
+                assert!(!rejected.contains("-b\n+B"));
+            }
+            _ => panic!("expected a partial review"),
+        }
+    }
+}
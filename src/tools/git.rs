@@ -0,0 +1,617 @@
+use super::{safe_resolve_path, Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::t;
+use async_trait::async_trait;
+use std::path::Path;
+use std::process::Command;
+
+/// 单次输出的最大字符数，避免大段diff挤占上下文
+const MAX_OUTPUT_CHARS: usize = 12_000;
+const DEFAULT_LOG_LIMIT: usize = 10;
+const MAX_LOG_LIMIT: usize = 100;
+
+/// `git status --porcelain` 中的一条记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitFileStatus {
+    pub path: String,
+    pub orig_path: Option<String>,
+    pub index: char,
+    pub worktree: char,
+}
+
+/// 解析 `git status --porcelain=v1 --branch -z` 的输出，返回分支行和文件列表
+pub fn parse_porcelain_status(output: &str) -> (Option<String>, Vec<GitFileStatus>) {
+    let mut branch = None;
+    let mut files = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+
+    while let Some(entry) = entries.next() {
+        if let Some(header) = entry.strip_prefix("## ") {
+            branch = Some(header.to_string());
+            continue;
+        }
+
+        let mut chars = entry.chars();
+        let (Some(index), Some(worktree)) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        let path = entry.get(3..).unwrap_or_default().to_string();
+
+        // 重命名和复制会在下一个条目中给出原路径
+        let orig_path = if matches!(index, 'R' | 'C') || matches!(worktree, 'R' | 'C') {
+            entries.next().map(|p| p.to_string())
+        } else {
+            None
+        };
+
+        files.push(GitFileStatus {
+            path,
+            orig_path,
+            index,
+            worktree,
+        });
+    }
+
+    (branch, files)
+}
+
+fn describe_status_code(code: char) -> String {
+    match code {
+        'M' => t!("git.modified"),
+        'A' => t!("git.added"),
+        'D' => t!("git.deleted"),
+        'R' => t!("git.renamed"),
+        'C' => t!("git.copied"),
+        'T' => t!("git.type_changed"),
+        'U' => t!("git.conflicted"),
+        _ => t!("git.changed"),
+    }
+}
+
+/// 将状态列表整理成分组的简洁文本
+pub fn format_status(branch: Option<&str>, files: &[GitFileStatus]) -> String {
+    let mut staged = Vec::new();
+    let mut unstaged = Vec::new();
+    let mut untracked = Vec::new();
+    let mut conflicted = Vec::new();
+
+    for file in files {
+        let display_path = match &file.orig_path {
+            Some(orig) => format!("{} -> {}", orig, file.path),
+            None => file.path.clone(),
+        };
+
+        if file.index == '?' && file.worktree == '?' {
+            untracked.push(display_path);
+            continue;
+        }
+        if file.index == 'U' || file.worktree == 'U' || (file.index == 'A' && file.worktree == 'A')
+        {
+            conflicted.push(display_path);
+            continue;
+        }
+        if file.index != ' ' && file.index != '?' {
+            staged.push(format!(
+                "{}  {}",
+                describe_status_code(file.index),
+                display_path
+            ));
+        }
+        if file.worktree != ' ' && file.worktree != '?' {
+            unstaged.push(format!(
+                "{}  {}",
+                describe_status_code(file.worktree),
+                display_path
+            ));
+        }
+    }
+
+    let mut lines = Vec::new();
+    if let Some(branch) = branch {
+        lines.push(t!("git.branch", branch = branch));
+    }
+    if files.is_empty() {
+        lines.push(t!("git.clean"));
+        return lines.join("\n");
+    }
+
+    for (title, items) in [
+        ("git.staged", &staged),
+        ("git.unstaged", &unstaged),
+        ("git.untracked", &untracked),
+        ("git.conflicts", &conflicted),
+    ] {
+        if items.is_empty() {
+            continue;
+        }
+        lines.push(format!("{} ({}):", t!(title), items.len()));
+        lines.extend(items.iter().map(|item| format!("  {}", item)));
+    }
+
+    lines.join("\n")
+}
+
+/// 截断过长的输出，并注明被省略的字符数
+fn truncate_output(output: &str) -> String {
+    let total = output.chars().count();
+    if total <= MAX_OUTPUT_CHARS {
+        return output.to_string();
+    }
+
+    let kept: String = output.chars().take(MAX_OUTPUT_CHARS).collect();
+    format!(
+        "{}\n{}",
+        kept,
+        t!("git.truncated", count = total - MAX_OUTPUT_CHARS)
+    )
+}
+
+/// 在项目目录中执行git命令，并确保项目位于一个git仓库内
+fn run_git(project_directory: &str, args: &[&str]) -> AgentResult<String> {
+    let project_path = Path::new(project_directory);
+    ensure_git_repository(project_path)?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args(args)
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(AgentError::CommandExecutionError(t!(
+            "git.command_failed",
+            command = args.first().unwrap_or(&""),
+            error = String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// 项目所在的git分支，处于分离头指针状态时返回提交的短哈希；不在git仓库中时返回 None
+pub fn current_branch(project_path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch != "HEAD" {
+        return Some(branch);
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn ensure_git_repository(project_path: &Path) -> AgentResult<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(AgentError::RuntimeError(t!(
+            "git.not_a_repository",
+            path = project_path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// 将用户提供的路径校验后转换为git可用的路径参数
+fn resolve_pathspec(project_directory: &str, user_path: &str) -> AgentResult<String> {
+    let final_path = safe_resolve_path(project_directory, user_path)?;
+    Ok(final_path.to_string_lossy().to_string())
+}
+
+fn is_staged_flag(arg: &str) -> bool {
+    matches!(
+        arg.trim().to_lowercase().as_str(),
+        "staged" | "--staged" | "cached" | "--cached" | "true"
+    )
+}
+
+pub struct GitStatusTool {
+    project_directory: String,
+}
+
+impl GitStatusTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for GitStatusTool {
+    fn name(&self) -> &str {
+        "git_status"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.git_status")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if !args.is_empty() {
+            return Err(AgentError::RuntimeError(t!("git.status_args")));
+        }
+
+        let output = run_git(
+            &self.project_directory,
+            &["status", "--porcelain=v1", "--branch", "-z", "--", "."],
+        )?;
+        let (branch, files) = parse_porcelain_status(&output);
+        Ok(format_status(branch.as_deref(), &files))
+    }
+}
+
+pub struct GitDiffTool {
+    project_directory: String,
+}
+
+impl GitDiffTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for GitDiffTool {
+    fn name(&self) -> &str {
+        "git_diff"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.git_diff")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::optional("path", prompt_text("tools.prompt.git_diff_path")),
+            ToolParameter::optional("staged", prompt_text("tools.prompt.git_diff_staged")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() > 2 {
+            return Err(AgentError::RuntimeError(t!("git.diff_args")));
+        }
+
+        let mut staged = false;
+        let mut path = None;
+        for arg in &args {
+            if is_staged_flag(arg) {
+                staged = true;
+            } else if !arg.trim().is_empty() {
+                path = Some(resolve_pathspec(&self.project_directory, arg)?);
+            }
+        }
+
+        let pathspec = path.as_deref().unwrap_or(".");
+        let diff_args = |extra: &[&'static str]| {
+            let mut git_args = vec!["diff", "--no-color", "--no-ext-diff"];
+            if staged {
+                git_args.push("--cached");
+            }
+            git_args.extend_from_slice(extra);
+            git_args.push("--");
+            git_args.push(pathspec);
+            git_args
+        };
+
+        let diff = run_git(&self.project_directory, &diff_args(&[]))?;
+        if diff.trim().is_empty() {
+            return Ok(if staged {
+                t!("git.no_staged_changes")
+            } else {
+                t!("git.no_unstaged_changes")
+            });
+        }
+
+        let stat = run_git(&self.project_directory, &diff_args(&["--stat"]))?;
+        Ok(truncate_output(&format!("{}\n{}", stat.trim_end(), diff)))
+    }
+}
+
+pub struct GitLogTool {
+    project_directory: String,
+}
+
+impl GitLogTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for GitLogTool {
+    fn name(&self) -> &str {
+        "git_log"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.git_log")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::optional(
+            "count",
+            prompt_text("tools.prompt.git_log_count"),
+        )]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() > 1 {
+            return Err(AgentError::RuntimeError(t!("git.log_args")));
+        }
+
+        let limit = match args.first() {
+            Some(arg) => arg
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| AgentError::RuntimeError(t!("git.invalid_log_count", count = arg)))?,
+            None => DEFAULT_LOG_LIMIT,
+        }
+        .clamp(1, MAX_LOG_LIMIT);
+
+        let limit_arg = format!("-n{}", limit);
+        let output = run_git(
+            &self.project_directory,
+            &[
+                "log",
+                &limit_arg,
+                "--date=short",
+                "--pretty=format:%h %ad %an: %s",
+                "--",
+                ".",
+            ],
+        )?;
+
+        if output.trim().is_empty() {
+            Ok(t!("git.no_commits"))
+        } else {
+            Ok(output)
+        }
+    }
+}
+
+pub struct GitCommitTool {
+    project_directory: String,
+}
+
+impl GitCommitTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for GitCommitTool {
+    fn name(&self) -> &str {
+        "git_commit"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.git_commit")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("message", prompt_text("tools.prompt.git_commit_message")),
+            ToolParameter::required("paths", prompt_text("tools.prompt.git_commit_paths"))
+                .repeated(),
+        ]
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() < 2 {
+            return Err(AgentError::RuntimeError(t!("git.commit_args")));
+        }
+
+        let message = args[0].trim();
+        if message.is_empty() {
+            return Err(AgentError::RuntimeError(t!("git.empty_message")));
+        }
+
+        let paths = args[1..]
+            .iter()
+            .map(|path| resolve_pathspec(&self.project_directory, path))
+            .collect::<AgentResult<Vec<_>>>()?;
+
+        let mut add_args = vec!["add", "--"];
+        add_args.extend(paths.iter().map(|p| p.as_str()));
+        run_git(&self.project_directory, &add_args)?;
+
+        let mut commit_args = vec!["commit", "-m", message, "--"];
+        commit_args.extend(paths.iter().map(|p| p.as_str()));
+        run_git(&self.project_directory, &commit_args)?;
+
+        let summary = run_git(
+            &self.project_directory,
+            &["show", "--stat", "--oneline", "--no-color", "HEAD"],
+        )?;
+        Ok(truncate_output(&t!(
+            "git.committed",
+            summary = summary.trim_end()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain_status() {
+        let output = "## main...origin/main [ahead 1]\0M  src/lib.rs\0 M README.md\0R  new.rs\0old.rs\0?? notes.txt\0";
+        let (branch, files) = parse_porcelain_status(output);
+
+        assert_eq!(branch.as_deref(), Some("main...origin/main [ahead 1]"));
+        assert_eq!(files.len(), 4);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].index, 'M');
+        assert_eq!(files[1].worktree, 'M');
+        assert_eq!(files[2].path, "new.rs");
+        assert_eq!(files[2].orig_path.as_deref(), Some("old.rs"));
+        assert_eq!(files[3].index, '?');
+    }
+
+    #[test]
+    fn test_format_status_groups_files() {
+        let (branch, files) =
+            parse_porcelain_status("## main\0MM src/lib.rs\0?? notes.txt\0UU conflict.rs\0");
+        let text = format_status(branch.as_deref(), &files);
+
+        let modified = t!("git.modified");
+        assert!(text.contains(&t!("git.branch", branch = "main")));
+        assert!(text.contains(&format!(
+            "{} (1):\n  {}  src/lib.rs",
+            t!("git.staged"),
+            modified
+        )));
+        assert!(text.contains(&format!(
+            "{} (1):\n  {}  src/lib.rs",
+            t!("git.unstaged"),
+            modified
+        )));
+        assert!(text.contains(&format!("{} (1):\n  notes.txt", t!("git.untracked"))));
+        assert!(text.contains(&format!("{} (1):\n  conflict.rs", t!("git.conflicts"))));
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} 失败", args);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    /// 带有一个初始提交的临时仓库
+    fn test_repository() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        git(path, &["init", "-q"]);
+        git(path, &["config", "user.name", "Test"]);
+        git(path, &["config", "user.email", "test@example.com"]);
+        git(path, &["config", "commit.gpgsign", "false"]);
+        std::fs::write(path.join("a.txt"), "a\n").unwrap();
+        std::fs::write(path.join("b.txt"), "b\n").unwrap();
+        git(path, &["add", "."]);
+        git(path, &["commit", "-q", "-m", "init"]);
+        dir
+    }
+
+    fn project(dir: &tempfile::TempDir) -> String {
+        dir.path().to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_git_tools_refuse_to_run_outside_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ensure_git_repository(dir.path()).is_err());
+        assert!(GitStatusTool::new(project(&dir))
+            .execute(vec![])
+            .await
+            .is_err());
+        assert!(GitCommitTool::new(project(&dir))
+            .execute(vec!["提交".to_string(), "a.txt".to_string()])
+            .await
+            .is_err());
+
+        let repository = test_repository();
+        assert!(ensure_git_repository(repository.path()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_git_commit_only_commits_listed_paths() {
+        let dir = test_repository();
+        let path = dir.path();
+        std::fs::write(path.join("a.txt"), "a2\n").unwrap();
+        std::fs::write(path.join("b.txt"), "b2\n").unwrap();
+        std::fs::write(path.join("c.txt"), "c\n").unwrap();
+
+        let tool = GitCommitTool::new(project(&dir));
+        // 提交会修改仓库，执行前需要用户确认
+        assert!(tool.requires_approval());
+        assert!(tool
+            .execute(vec!["越界".to_string(), "../outside.txt".to_string()])
+            .await
+            .is_err());
+
+        tool.execute(vec!["更新 a".to_string(), "a.txt".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            git(path, &["show", "--name-only", "--pretty=format:%s", "HEAD"]),
+            "更新 a\na.txt\n"
+        );
+        let (_, files) = parse_porcelain_status(&git(path, &["status", "--porcelain=v1", "-z"]));
+        let remaining: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(remaining, vec!["b.txt", "c.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_git_diff_staged() {
+        let dir = test_repository();
+        let path = dir.path();
+        std::fs::write(path.join("a.txt"), "staged\n").unwrap();
+        git(path, &["add", "a.txt"]);
+        std::fs::write(path.join("b.txt"), "unstaged\n").unwrap();
+
+        let tool = GitDiffTool::new(project(&dir));
+        let staged = tool.execute(vec!["staged".to_string()]).await.unwrap();
+        assert!(staged.contains("+staged"));
+        assert!(!staged.contains("+unstaged"));
+
+        let unstaged = tool.execute(vec![]).await.unwrap();
+        assert!(unstaged.contains("+unstaged"));
+        assert!(!unstaged.contains("+staged"));
+
+        let other_file = tool
+            .execute(vec!["b.txt".to_string(), "--staged".to_string()])
+            .await
+            .unwrap();
+        assert!(!other_file.contains("+staged"));
+    }
+
+    #[test]
+    fn test_format_status_clean() {
+        let text = format_status(Some("main"), &[]);
+        assert!(text.contains(&t!("git.clean")));
+    }
+}
@@ -0,0 +1,338 @@
+use super::{Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::t;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{redirect, Method, Url};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 默认只允许访问本机
+pub const DEFAULT_ALLOWED_HOSTS: &[&str] = &["localhost", "127.0.0.1", "::1"];
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 64 * 1024;
+const MAX_REDIRECTS: usize = 5;
+
+/// 判断主机是否在允许列表中，支持 `*.example.com` 形式的通配
+fn is_host_allowed(allowed_hosts: &[String], host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    allowed_hosts.iter().any(|pattern| {
+        let pattern = pattern.trim().to_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+            None => host.eq_ignore_ascii_case(&pattern),
+        }
+    })
+}
+
+/// 解析请求头参数，支持JSON对象或每行一个 `Name: Value`
+fn parse_headers(raw: &str) -> AgentResult<HeaderMap> {
+    let mut pairs = Vec::new();
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(HeaderMap::new());
+    }
+
+    if raw.starts_with('{') {
+        let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(raw)?;
+        for (name, value) in object {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            pairs.push((name, value));
+        }
+    } else {
+        for line in raw.lines().filter(|l| !l.trim().is_empty()) {
+            let (name, value) = line.split_once(':').ok_or_else(|| {
+                AgentError::ParseError(t!("http.invalid_header", header = line.trim()))
+            })?;
+            pairs.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| AgentError::ParseError(t!("http.invalid_header_name", name = name)))?;
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| AgentError::ParseError(t!("http.invalid_header_value", value = value)))?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+/// JSON响应格式化输出，其他内容原样返回
+fn format_body(body: &str, content_type: Option<&str>, truncated: bool) -> String {
+    let looks_like_json = content_type.is_some_and(|ct| ct.contains("json"))
+        || body.trim_start().starts_with('{')
+        || body.trim_start().starts_with('[');
+
+    if looks_like_json && !truncated {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(body) {
+            if let Ok(pretty) = serde_json::to_string_pretty(&value) {
+                return pretty;
+            }
+        }
+    }
+    body.to_string()
+}
+
+pub struct HttpRequestTool {
+    allowed_hosts: Arc<Vec<String>>,
+    timeout: Duration,
+    max_response_bytes: usize,
+}
+
+impl HttpRequestTool {
+    pub fn new() -> Self {
+        Self {
+            allowed_hosts: Arc::new(
+                DEFAULT_ALLOWED_HOSTS
+                    .iter()
+                    .map(|h| h.to_string())
+                    .collect(),
+            ),
+            timeout: DEFAULT_TIMEOUT,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+        }
+    }
+
+    pub fn with_allowed_hosts(mut self, allowed_hosts: Vec<String>) -> Self {
+        self.allowed_hosts = Arc::new(allowed_hosts);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    fn check_url(&self, url: &Url) -> AgentResult<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AgentError::RuntimeError(t!(
+                "http.unsupported_scheme",
+                url = url
+            )));
+        }
+        let host = url.host_str().unwrap_or_default();
+        if !is_host_allowed(&self.allowed_hosts, host) {
+            return Err(AgentError::RuntimeError(t!(
+                "http.host_not_allowed",
+                host = host,
+                allowed = self.allowed_hosts.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn build_client(&self) -> AgentResult<reqwest::Client> {
+        // 重定向的目标同样需要在允许列表中
+        let allowed_hosts = Arc::clone(&self.allowed_hosts);
+        let policy = redirect::Policy::custom(move |attempt| {
+            let host = attempt.url().host_str().unwrap_or_default().to_string();
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error(t!("http.too_many_redirects"))
+            } else if is_host_allowed(&allowed_hosts, &host) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        });
+
+        reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(policy)
+            .build()
+            .map_err(|e| AgentError::RuntimeError(t!("http.client_failed", error = e)))
+    }
+}
+
+impl Default for HttpRequestTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for HttpRequestTool {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.http_request")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("method", prompt_text("tools.prompt.http_method")),
+            ToolParameter::required("url", prompt_text("tools.prompt.http_url")),
+            ToolParameter::optional("headers", prompt_text("tools.prompt.http_headers")),
+            ToolParameter::optional("body", prompt_text("tools.prompt.http_body")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() < 2 || args.len() > 4 {
+            return Err(AgentError::RuntimeError(t!("http.args")));
+        }
+
+        let method = Method::from_bytes(args[0].trim().to_uppercase().as_bytes())
+            .map_err(|_| AgentError::RuntimeError(t!("http.invalid_method", method = args[0])))?;
+        let url = Url::parse(args[1].trim()).map_err(|e| {
+            AgentError::RuntimeError(t!("http.invalid_url", url = args[1], error = e))
+        })?;
+        self.check_url(&url)?;
+
+        let headers = match args.get(2) {
+            Some(raw) => parse_headers(raw)?,
+            None => HeaderMap::new(),
+        };
+
+        let mut request = self.build_client()?.request(method, url).headers(headers);
+        if let Some(body) = args.get(3) {
+            request = request.body(body.clone());
+        }
+
+        let mut response = request.send().await.map_err(|e| {
+            AgentError::RuntimeError(if e.is_timeout() {
+                t!("http.timeout", seconds = self.timeout.as_secs())
+            } else {
+                t!("http.request_failed", error = e)
+            })
+        })?;
+
+        let status = response.status();
+        let final_url = response.url().to_string();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        // 分块读取响应体，超过上限后停止
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AgentError::RuntimeError(t!("http.read_failed", error = e)))?
+        {
+            let remaining = self.max_response_bytes - body.len();
+            if chunk.len() > remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let body = String::from_utf8_lossy(&body);
+        let mut lines = vec![format!("HTTP {} ({})", status, final_url)];
+        if let Some(content_type) = &content_type {
+            lines.push(format!("Content-Type: {}", content_type));
+        }
+        if status.is_redirection() {
+            if let Some(location) = location {
+                lines.push(t!("http.redirect_not_followed", location = location));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format_body(&body, content_type.as_deref(), truncated));
+        if truncated {
+            lines.push(t!("http.truncated", bytes = self.max_response_bytes));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 启动只响应一次的本地HTTP服务，返回其地址
+    async fn serve_once(content_type: &'static str, body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 4096];
+            let _ = socket.read(&mut buffer).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                content_type,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_pretty_prints_json_response() {
+        let base = serve_once("application/json", r#"{"ok":true,"items":[1,2]}"#.into()).await;
+        let output = HttpRequestTool::new()
+            .execute(vec!["GET".into(), format!("{}/status", base)])
+            .await
+            .unwrap();
+
+        assert!(output.starts_with("HTTP 200 OK"));
+        assert!(output.contains("\"ok\": true"));
+        assert!(output.contains("\"items\": [\n"));
+    }
+
+    #[tokio::test]
+    async fn test_truncates_large_response() {
+        let base = serve_once("text/plain", "x".repeat(1000)).await;
+        let output = HttpRequestTool::new()
+            .with_max_response_bytes(100)
+            .execute(vec!["GET".into(), base])
+            .await
+            .unwrap();
+
+        assert!(output.contains(&format!("{}\n", "x".repeat(100))));
+        assert!(!output.contains(&"x".repeat(101)));
+        assert!(output.contains(&t!("http.truncated", bytes = 100)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_hosts_outside_allowlist() {
+        let result = HttpRequestTool::new()
+            .execute(vec!["GET".into(), "https://example.com/".into()])
+            .await;
+        assert!(result.unwrap_err().to_string().contains(&t!(
+            "http.host_not_allowed",
+            host = "example.com",
+            allowed = DEFAULT_ALLOWED_HOSTS.join(", ")
+        )));
+    }
+
+    #[test]
+    fn test_host_patterns_and_headers() {
+        let allowed = vec!["localhost".to_string(), "*.internal.test".to_string()];
+        assert!(is_host_allowed(&allowed, "LOCALHOST"));
+        assert!(is_host_allowed(&allowed, "api.internal.test"));
+        assert!(!is_host_allowed(&allowed, "internal.test.evil.com"));
+
+        let headers = parse_headers("{\"X-Token\": \"abc\"}").unwrap();
+        assert_eq!(headers["x-token"], "abc");
+        let headers = parse_headers("Accept: text/plain\nX-Id: 7").unwrap();
+        assert_eq!(headers["accept"], "text/plain");
+        assert_eq!(headers["x-id"], "7");
+    }
+}
@@ -0,0 +1,163 @@
+use super::{Tool, ToolParameter};
+use crate::errors::AgentResult;
+use crate::i18n::prompt_text;
+use crate::memory::{Memory, MemoryStore};
+use crate::t;
+use async_trait::async_trait;
+
+/// 记忆在观察结果和 `/memory` 中的显示形式
+pub fn format_memory(memory: &Memory) -> String {
+    format!("#{} [{}] {}", memory.id, memory.created, memory.content)
+}
+
+/// 记下以后的会话也需要知道的项目事实
+pub struct RememberTool {
+    memory: MemoryStore,
+}
+
+impl RememberTool {
+    pub fn new(memory: MemoryStore) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for RememberTool {
+    fn name(&self) -> &str {
+        "remember"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.remember")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "content",
+            prompt_text("tools.prompt.memory_content"),
+        )]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let content = args.first().map(String::as_str).unwrap_or_default();
+        Ok(match self.memory.remember(content) {
+            Ok(memory) => t!("memory.remembered", memory = format_memory(&memory)),
+            Err(e) => e.to_string(),
+        })
+    }
+}
+
+/// 按关键词查找记忆
+pub struct RecallTool {
+    memory: MemoryStore,
+}
+
+impl RecallTool {
+    pub fn new(memory: MemoryStore) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for RecallTool {
+    fn name(&self) -> &str {
+        "recall"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.recall")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::optional(
+            "query",
+            prompt_text("tools.prompt.memory_query"),
+        )]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let query = args.first().map(String::as_str).unwrap_or_default();
+        let memories = self.memory.recall(query);
+        if memories.is_empty() {
+            return Ok(t!("memory.none_found"));
+        }
+        Ok(memories
+            .iter()
+            .map(format_memory)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// 删除过时或错误的记忆
+pub struct ForgetTool {
+    memory: MemoryStore,
+}
+
+impl ForgetTool {
+    pub fn new(memory: MemoryStore) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for ForgetTool {
+    fn name(&self) -> &str {
+        "forget"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.forget")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "id",
+            prompt_text("tools.prompt.memory_id"),
+        )]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let id = args.first().map(String::as_str).unwrap_or_default();
+        let Ok(id) = id.trim().trim_start_matches('#').parse::<usize>() else {
+            return Ok(t!("memory.invalid_id", value = id));
+        };
+        Ok(match self.memory.forget(id) {
+            Ok(memory) => t!("memory.forgotten", memory = format_memory(&memory)),
+            Err(e) => e.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_tools_share_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path());
+        let remember = RememberTool::new(store.clone());
+        let recall = RecallTool::new(store.clone());
+        let forget = ForgetTool::new(store.clone());
+
+        let observation = remember
+            .execute(vec!["use pnpm not npm".to_string()])
+            .await
+            .unwrap();
+        assert!(observation.contains("#1"));
+        let observation = recall.execute(vec!["PNPM".to_string()]).await.unwrap();
+        assert!(observation.ends_with("use pnpm not npm"));
+
+        let observation = forget.execute(vec!["one".to_string()]).await.unwrap();
+        assert!(observation.contains("one"));
+        forget.execute(vec!["#1".to_string()]).await.unwrap();
+        assert!(store.list().is_empty());
+        let observation = recall.execute(vec![]).await.unwrap();
+        assert_eq!(observation, t!("memory.none_found"));
+    }
+}
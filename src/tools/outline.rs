@@ -0,0 +1,559 @@
+use super::{safe_resolve_path, Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::t;
+use async_trait::async_trait;
+use std::path::Path;
+use tokio::fs;
+use tree_sitter::{Node, Parser};
+
+/// 大纲最多输出的符号数，避免超大文件挤占上下文
+const MAX_OUTLINE_SYMBOLS: usize = 500;
+/// 同名符号最多返回的个数
+const MAX_SYMBOL_MATCHES: usize = 5;
+
+/// 支持解析的源码语言
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceLanguage {
+    Rust,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Python,
+    Html,
+}
+
+impl SourceLanguage {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "rs" => Some(Self::Rust),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Self::JavaScript),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            "py" | "pyi" => Some(Self::Python),
+            "html" | "htm" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::JavaScript => "javascript",
+            Self::TypeScript => "typescript",
+            Self::Tsx => "tsx",
+            Self::Python => "python",
+            Self::Html => "html",
+        }
+    }
+
+    fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::Html => tree_sitter_html::LANGUAGE.into(),
+        }
+    }
+}
+
+/// 源码中的一个符号（函数、结构体、类、方法等）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub kind: &'static str,
+    pub name: String,
+    /// 名称之外的补充信息，例如 `impl Display for Foo`
+    pub detail: Option<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub children: Vec<Symbol>,
+}
+
+impl Symbol {
+    fn label(&self) -> String {
+        match &self.detail {
+            Some(detail) => detail.clone(),
+            None => format!("{} {}", self.kind, self.name),
+        }
+    }
+}
+
+/// 解析源码并返回层级化的符号列表
+pub fn outline(source: &str, language: SourceLanguage) -> AgentResult<Vec<Symbol>> {
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).map_err(|e| {
+        AgentError::ParseError(t!(
+            "outline.grammar_failed",
+            language = language.name(),
+            error = e
+        ))
+    })?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| AgentError::ParseError(t!("outline.parse_failed")))?;
+
+    let mut symbols = Vec::new();
+    collect_symbols(tree.root_node(), source, language, false, &mut symbols);
+    Ok(symbols)
+}
+
+fn collect_symbols(
+    node: Node,
+    source: &str,
+    language: SourceLanguage,
+    in_container: bool,
+    symbols: &mut Vec<Symbol>,
+) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match symbol_for_node(child, source, language, in_container) {
+            Some(mut symbol) => {
+                collect_symbols(child, source, language, true, &mut symbol.children);
+                symbols.push(symbol);
+            }
+            None => collect_symbols(child, source, language, in_container, symbols),
+        }
+    }
+}
+
+fn node_text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or_default()
+}
+
+fn field_text(node: Node, field: &str, source: &str) -> Option<String> {
+    node.child_by_field_name(field)
+        .map(|n| node_text(n, source).to_string())
+}
+
+fn make_symbol(node: Node, kind: &'static str, name: String, detail: Option<String>) -> Symbol {
+    Symbol {
+        kind,
+        name,
+        detail,
+        start_line: node.start_position().row + 1,
+        end_line: node.end_position().row + 1,
+        start_byte: node.start_byte(),
+        end_byte: node.end_byte(),
+        children: Vec::new(),
+    }
+}
+
+fn symbol_for_node(
+    node: Node,
+    source: &str,
+    language: SourceLanguage,
+    in_container: bool,
+) -> Option<Symbol> {
+    match language {
+        SourceLanguage::Rust => rust_symbol(node, source),
+        SourceLanguage::Python => python_symbol(node, source, in_container),
+        SourceLanguage::JavaScript | SourceLanguage::TypeScript | SourceLanguage::Tsx => {
+            script_symbol(node, source)
+        }
+        SourceLanguage::Html => html_symbol(node, source),
+    }
+}
+
+fn rust_symbol(node: Node, source: &str) -> Option<Symbol> {
+    let kind = match node.kind() {
+        "function_item" | "function_signature_item" => "fn",
+        "struct_item" => "struct",
+        "enum_item" => "enum",
+        "union_item" => "union",
+        "trait_item" => "trait",
+        "mod_item" => "mod",
+        "const_item" => "const",
+        "static_item" => "static",
+        "type_item" => "type",
+        "macro_definition" => "macro",
+        "impl_item" => {
+            let type_name = field_text(node, "type", source)?;
+            let detail = match field_text(node, "trait", source) {
+                Some(trait_name) => format!("impl {} for {}", trait_name, type_name),
+                None => format!("impl {}", type_name),
+            };
+            // 以类型名（去掉泛型参数）作为名称，便于用 Type::method 查找
+            let name = type_name
+                .split('<')
+                .next()
+                .unwrap_or(&type_name)
+                .trim()
+                .to_string();
+            return Some(make_symbol(node, "impl", name, Some(detail)));
+        }
+        _ => return None,
+    };
+    let name = field_text(node, "name", source)?;
+    Some(make_symbol(node, kind, name, None))
+}
+
+fn python_symbol(node: Node, source: &str, in_container: bool) -> Option<Symbol> {
+    let kind = match node.kind() {
+        "class_definition" => "class",
+        "function_definition" if in_container => "method",
+        "function_definition" => "def",
+        _ => return None,
+    };
+    let name = field_text(node, "name", source)?;
+    Some(make_symbol(node, kind, name, None))
+}
+
+fn script_symbol(node: Node, source: &str) -> Option<Symbol> {
+    let kind = match node.kind() {
+        "function_declaration" | "generator_function_declaration" => "function",
+        "class_declaration" | "abstract_class_declaration" | "class" => "class",
+        "method_definition" | "method_signature" | "abstract_method_signature" => "method",
+        "interface_declaration" => "interface",
+        "type_alias_declaration" => "type",
+        "enum_declaration" => "enum",
+        "internal_module" | "module" => "namespace",
+        // const foo = () => {} 形式的函数
+        "variable_declarator" => {
+            let value = node.child_by_field_name("value")?;
+            if !matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function" | "generator_function"
+            ) {
+                return None;
+            }
+            "function"
+        }
+        _ => return None,
+    };
+    let name = field_text(node, "name", source)?;
+    Some(make_symbol(node, kind, name, None))
+}
+
+/// HTML只列出结构性元素和带id的元素，普通元素过于琐碎
+fn html_symbol(node: Node, source: &str) -> Option<Symbol> {
+    const STRUCTURAL_TAGS: &[&str] = &[
+        "html", "head", "body", "header", "nav", "main", "section", "article", "aside", "footer",
+        "form", "table", "dialog", "template", "canvas", "h1", "h2", "h3",
+    ];
+
+    if !matches!(node.kind(), "element" | "script_element" | "style_element") {
+        return None;
+    }
+    let start_tag = node.named_child(0)?;
+    let mut cursor = start_tag.walk();
+    let mut tag_name = None;
+    let mut id = None;
+    for child in start_tag.named_children(&mut cursor) {
+        match child.kind() {
+            "tag_name" => tag_name = Some(node_text(child, source).to_lowercase()),
+            "attribute" => {
+                let attribute = node_text(child, source);
+                if let Some((key, value)) = attribute.split_once('=') {
+                    if key.trim().eq_ignore_ascii_case("id") {
+                        id = Some(
+                            value
+                                .trim()
+                                .trim_matches(|c| c == '"' || c == '\'')
+                                .to_string(),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let tag_name = tag_name?;
+    let is_structural = STRUCTURAL_TAGS.contains(&tag_name.as_str())
+        || matches!(tag_name.as_str(), "script" | "style");
+    if id.is_none() && !is_structural {
+        return None;
+    }
+
+    let detail = match &id {
+        Some(id) => format!("<{}#{}>", tag_name, id),
+        None => format!("<{}>", tag_name),
+    };
+    Some(make_symbol(
+        node,
+        "element",
+        id.unwrap_or_else(|| tag_name.clone()),
+        Some(detail),
+    ))
+}
+
+/// 将符号树渲染为缩进的文本大纲
+pub fn render_outline(symbols: &[Symbol]) -> String {
+    fn render(symbols: &[Symbol], depth: usize, lines: &mut Vec<String>) {
+        for symbol in symbols {
+            if lines.len() >= MAX_OUTLINE_SYMBOLS {
+                return;
+            }
+            lines.push(format!(
+                "{}{} [{}-{}]",
+                "  ".repeat(depth),
+                symbol.label(),
+                symbol.start_line,
+                symbol.end_line
+            ));
+            render(&symbol.children, depth + 1, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    render(symbols, 0, &mut lines);
+    if lines.len() >= MAX_OUTLINE_SYMBOLS {
+        lines.push(t!("outline.too_many_symbols", count = MAX_OUTLINE_SYMBOLS));
+    }
+    lines.join("\n")
+}
+
+/// 按名称查找符号，支持 `Type::method` 或 `Class.method` 形式的限定名
+pub fn find_symbols<'a>(symbols: &'a [Symbol], query: &str) -> Vec<&'a Symbol> {
+    fn search<'a>(
+        symbols: &'a [Symbol],
+        segments: &[&str],
+        matched: usize,
+        results: &mut Vec<&'a Symbol>,
+    ) {
+        for symbol in symbols {
+            let mut matched = matched;
+            if symbol.name == segments[matched] {
+                if matched + 1 == segments.len() {
+                    results.push(symbol);
+                    continue;
+                }
+                matched += 1;
+            }
+            search(&symbol.children, segments, matched, results);
+        }
+    }
+
+    let segments: Vec<&str> = query
+        .split("::")
+        .flat_map(|part| part.split('.'))
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let mut results = Vec::new();
+    if !segments.is_empty() {
+        search(symbols, &segments, 0, &mut results);
+    }
+    results
+}
+
+async fn load_source(
+    project_directory: &str,
+    file_path: &str,
+) -> AgentResult<(String, SourceLanguage)> {
+    let final_path = safe_resolve_path(project_directory, file_path)?;
+    let language = SourceLanguage::from_path(&final_path).ok_or_else(|| {
+        AgentError::RuntimeError(t!("outline.unsupported_file", path = file_path))
+    })?;
+    let source = fs::read_to_string(&final_path).await?;
+    Ok((source, language))
+}
+
+pub struct CodeOutlineTool {
+    project_directory: String,
+}
+
+impl CodeOutlineTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for CodeOutlineTool {
+    fn name(&self) -> &str {
+        "code_outline"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.code_outline")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter::required(
+            "path",
+            prompt_text("tools.prompt.source_path"),
+        )]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() != 1 {
+            return Err(AgentError::RuntimeError(t!("outline.outline_args")));
+        }
+
+        let (source, language) = load_source(&self.project_directory, &args[0]).await?;
+        let symbols = outline(&source, language)?;
+        if symbols.is_empty() {
+            return Ok(t!(
+                "outline.no_symbols",
+                path = args[0],
+                language = language.name()
+            ));
+        }
+        Ok(t!(
+            "outline.header",
+            path = args[0],
+            language = language.name(),
+            lines = source.lines().count(),
+            outline = render_outline(&symbols)
+        ))
+    }
+}
+
+pub struct ReadSymbolTool {
+    project_directory: String,
+}
+
+impl ReadSymbolTool {
+    pub fn new(project_directory: String) -> Self {
+        Self { project_directory }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadSymbolTool {
+    fn name(&self) -> &str {
+        "read_symbol"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.read_symbol")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("path", prompt_text("tools.prompt.source_path")),
+            ToolParameter::required("symbol", prompt_text("tools.prompt.symbol_name")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        if args.len() != 2 {
+            return Err(AgentError::RuntimeError(t!("outline.read_symbol_args")));
+        }
+
+        let (source, language) = load_source(&self.project_directory, &args[0]).await?;
+        let symbols = outline(&source, language)?;
+        let matches = find_symbols(&symbols, &args[1]);
+        if matches.is_empty() {
+            return Ok(t!(
+                "outline.symbol_not_found",
+                path = args[0],
+                symbol = args[1]
+            ));
+        }
+
+        let mut sections = Vec::new();
+        for symbol in matches.iter().take(MAX_SYMBOL_MATCHES) {
+            // 从行首开始截取，保留原有缩进
+            let line_start = source[..symbol.start_byte]
+                .rfind('\n')
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            let body = source[line_start..symbol.end_byte]
+                .lines()
+                .enumerate()
+                .map(|(i, line)| format!("{:>5} | {}", symbol.start_line + i, line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            sections.push(format!(
+                "{}:{}-{} {}\n{}",
+                args[0],
+                symbol.start_line,
+                symbol.end_line,
+                symbol.label(),
+                body
+            ));
+        }
+        if matches.len() > MAX_SYMBOL_MATCHES {
+            sections.push(t!(
+                "outline.more_matches",
+                count = matches.len() - MAX_SYMBOL_MATCHES
+            ));
+        }
+        Ok(sections.join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_SOURCE: &str = r#"
+pub struct Counter {
+    value: u32,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self { value: 0 }
+    }
+
+    pub fn increment(&mut self) {
+        self.value += 1;
+    }
+}
+
+impl std::fmt::Display for Counter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+fn main() {}
+"#;
+
+    #[test]
+    fn test_rust_outline() {
+        let symbols = outline(RUST_SOURCE, SourceLanguage::Rust).unwrap();
+        let text = render_outline(&symbols);
+        assert_eq!(
+            text,
+            "struct Counter [2-4]\n\
+             impl Counter [6-14]\n  fn new [7-9]\n  fn increment [11-13]\n\
+             impl std::fmt::Display for Counter [16-20]\n  fn fmt [17-19]\n\
+             fn main [22-22]"
+        );
+    }
+
+    #[test]
+    fn test_find_qualified_symbol() {
+        let symbols = outline(RUST_SOURCE, SourceLanguage::Rust).unwrap();
+        let found = find_symbols(&symbols, "Counter::increment");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].start_line, 11);
+
+        assert_eq!(find_symbols(&symbols, "Counter").len(), 3);
+        assert!(find_symbols(&symbols, "Missing::new").is_empty());
+    }
+
+    #[test]
+    fn test_python_and_typescript_outline() {
+        let python = "class Server:\n    def start(self):\n        pass\n\ndef main():\n    pass\n";
+        let symbols = outline(python, SourceLanguage::Python).unwrap();
+        assert_eq!(
+            render_outline(&symbols),
+            "class Server [1-3]\n  method start [2-3]\ndef main [5-6]"
+        );
+
+        let typescript = "interface Props { id: number }\nexport class App {\n  render(): void {}\n}\nconst helper = () => 1;\n";
+        let symbols = outline(typescript, SourceLanguage::TypeScript).unwrap();
+        assert_eq!(
+            render_outline(&symbols),
+            "interface Props [1-1]\nclass App [2-4]\n  method render [3-3]\nfunction helper [5-5]"
+        );
+    }
+}
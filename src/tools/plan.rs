@@ -0,0 +1,91 @@
+use super::{Tool, ToolParameter};
+use crate::errors::AgentResult;
+use crate::i18n::prompt_text;
+use crate::plan::{PlanTracker, StepStatus};
+use crate::t;
+use async_trait::async_trait;
+
+/// 更新当前计划中步骤的状态，返回更新后的完整计划
+pub struct UpdatePlanTool {
+    plan: PlanTracker,
+}
+
+impl UpdatePlanTool {
+    pub fn new(plan: PlanTracker) -> Self {
+        Self { plan }
+    }
+}
+
+#[async_trait]
+impl Tool for UpdatePlanTool {
+    fn name(&self) -> &str {
+        "update_plan"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.update_plan")
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("step", prompt_text("tools.prompt.plan_step")),
+            ToolParameter::required("status", prompt_text("tools.prompt.plan_status")),
+            ToolParameter::optional("description", prompt_text("tools.prompt.plan_description")),
+        ]
+    }
+
+    /// 参数错误或没有计划时，错误信息作为观察结果返回给模型
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let arg = |index: usize| args.get(index).map(String::as_str).unwrap_or_default();
+        let Ok(step) = arg(0).trim().parse::<usize>() else {
+            return Ok(t!("plan.invalid_step_number", value = arg(0)));
+        };
+        let Some(status) = StepStatus::parse(arg(1)) else {
+            return Ok(t!("plan.invalid_status", value = arg(1)));
+        };
+        let description = Some(arg(2)).filter(|d| !d.trim().is_empty());
+
+        match self
+            .plan
+            .update(|plan| plan.update_step(step, status, description))
+        {
+            Ok(plan) => Ok(t!(
+                "plan.progress",
+                done = plan.completed_count(),
+                total = plan.steps.len(),
+                plan = plan.render()
+            )),
+            Err(e) => Ok(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::Plan;
+
+    #[tokio::test]
+    async fn test_update_plan_reports_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = PlanTracker::new(dir.path());
+        let tool = UpdatePlanTool::new(tracker.clone());
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        // 没有计划时提示模型，而不是中止任务
+        let observation = tool.execute(args(&["1", "completed"])).await.unwrap();
+        assert!(observation.contains(&t!("plan.no_plan")));
+
+        tracker
+            .set(Plan::new("任务", vec!["a".to_string(), "b".to_string()]))
+            .unwrap();
+        let observation = tool.execute(args(&["1", "done"])).await.unwrap();
+        assert!(observation.contains("[x] 1. a\n[ ] 2. b"));
+        assert_eq!(tracker.current().unwrap().completed_count(), 1);
+
+        let observation = tool.execute(args(&["1", "finished"])).await.unwrap();
+        assert!(observation.contains("finished"));
+        let observation = tool.execute(args(&["first", "completed"])).await.unwrap();
+        assert!(observation.contains("first"));
+    }
+}
@@ -0,0 +1,587 @@
+use super::custom::{bind_arguments, is_valid_name, tool_parameters, validate_parameters};
+use super::{safe_resolve_path, CustomParameterConfig, Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, ResourceLimiter, Store, Trap, TypedFunc,
+};
+
+/// 项目级插件配置文件
+pub const PLUGINS_FILE: &str = ".rust-agent/plugins.toml";
+/// 宿主函数所在的导入模块名
+const HOST_MODULE: &str = "rust_agent";
+const DEFAULT_FUEL: u64 = 100_000_000;
+const DEFAULT_MEMORY_LIMIT_MB: usize = 16;
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// 插件可以被授予的能力，每种能力对应一个宿主函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `read_file(path_ptr, path_len) -> i64`：读取项目目录内的文件，
+    /// 返回 `(ptr << 32) | len`，失败时返回 -1
+    ReadFile,
+    /// `output(ptr, len)`：追加工具输出
+    Output,
+}
+
+impl Capability {
+    fn import_name(&self) -> &'static str {
+        match self {
+            Self::ReadFile => "read_file",
+            Self::Output => "output",
+        }
+    }
+
+    fn from_import_name(name: &str) -> Option<Self> {
+        [Self::ReadFile, Self::Output]
+            .into_iter()
+            .find(|c| c.import_name() == name)
+    }
+
+    /// 该能力不会修改文件或其他状态
+    fn is_read_only(&self) -> bool {
+        match self {
+            Self::ReadFile | Self::Output => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginsFile {
+    #[serde(default, rename = "plugin")]
+    plugins: Vec<PluginConfig>,
+}
+
+/// `[[plugin]]` 配置项
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginConfig {
+    pub name: String,
+    pub description: String,
+    /// `.wasm`（或 `.wat`）文件路径，相对于项目目录
+    pub path: String,
+    #[serde(default)]
+    pub parameters: Vec<CustomParameterConfig>,
+    /// 授予的能力，未授予的宿主函数不会提供给插件
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// 每次调用可消耗的fuel（约等于执行的指令数）
+    #[serde(default)]
+    pub fuel: Option<u64>,
+    #[serde(default)]
+    pub memory_limit_mb: Option<usize>,
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+/// 单次调用的资源限制，记录内存增长是否因超过上限被拒绝
+struct PluginLimits {
+    memory_bytes: usize,
+    memory_exceeded: bool,
+}
+
+impl ResourceLimiter for PluginLimits {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.memory_bytes {
+            self.memory_exceeded = true;
+            return Ok(false);
+        }
+        Ok(maximum.is_none_or(|maximum| desired <= maximum))
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(maximum.is_none_or(|maximum| desired <= maximum))
+    }
+
+    fn instances(&self) -> usize {
+        1
+    }
+}
+
+/// 单次调用的宿主状态
+struct HostState {
+    project_directory: String,
+    output: Vec<u8>,
+    output_truncated: bool,
+    limits: PluginLimits,
+}
+
+fn guest_memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => Err(wasmtime::Error::msg(t!(
+            "plugin.missing_export",
+            name = "memory"
+        ))),
+    }
+}
+
+fn read_guest_bytes(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<Vec<u8>> {
+    let memory = guest_memory(caller)?;
+    let start = ptr as u32 as usize;
+    let end = start + len as u32 as usize;
+    memory
+        .data(&caller)
+        .get(start..end)
+        .map(|bytes| bytes.to_vec())
+        .ok_or_else(|| wasmtime::Error::msg(t!("plugin.out_of_bounds")))
+}
+
+fn host_output(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<()> {
+    let bytes = read_guest_bytes(&mut caller, ptr, len)?;
+    let state = caller.data_mut();
+    let remaining = MAX_OUTPUT_BYTES.saturating_sub(state.output.len());
+    if bytes.len() > remaining {
+        state.output_truncated = true;
+    }
+    state
+        .output
+        .extend_from_slice(&bytes[..bytes.len().min(remaining)]);
+    Ok(())
+}
+
+fn host_read_file(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<i64> {
+    let path = String::from_utf8(read_guest_bytes(&mut caller, ptr, len)?)
+        .map_err(|_| wasmtime::Error::msg(t!("plugin.invalid_path")))?;
+
+    // 与内置文件工具一样限制在项目目录内
+    let content = match safe_resolve_path(&caller.data().project_directory, &path)
+        .ok()
+        .and_then(|resolved| std::fs::read(resolved).ok())
+    {
+        Some(content) => content,
+        None => return Ok(-1),
+    };
+
+    let alloc = match caller.get_export("alloc").and_then(|e| e.into_func()) {
+        Some(alloc) => alloc.typed::<i32, i32>(&caller)?,
+        None => {
+            return Err(wasmtime::Error::msg(t!(
+                "plugin.missing_export",
+                name = "alloc"
+            )))
+        }
+    };
+    let content_ptr = alloc.call(&mut caller, content.len() as i32)?;
+    guest_memory(&mut caller)?.write(&mut caller, content_ptr as u32 as usize, &content)?;
+    Ok(((content_ptr as u32 as i64) << 32) | content.len() as i64)
+}
+
+/// 只把授予的能力链接给插件
+fn build_linker(engine: &Engine, capabilities: &[Capability]) -> AgentResult<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    for capability in capabilities {
+        let result = match capability {
+            Capability::Output => linker.func_wrap(HOST_MODULE, "output", host_output),
+            Capability::ReadFile => linker.func_wrap(HOST_MODULE, "read_file", host_read_file),
+        };
+        result.map_err(|e| AgentError::RuntimeError(t!("plugin.link_failed", error = e)))?;
+    }
+    Ok(linker)
+}
+
+/// 插件失败的原因，fuel耗尽或内存增长被上限拒绝时给出对应的提示
+fn describe_trap(error: &wasmtime::Error, fuel: u64, limits: &PluginLimits) -> String {
+    match error.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => t!("plugin.out_of_fuel", fuel = fuel),
+        _ if limits.memory_exceeded => t!(
+            "plugin.memory_limit",
+            error = error,
+            limit = limits.memory_bytes / (1024 * 1024)
+        ),
+        _ => error.to_string(),
+    }
+}
+
+/// 由WebAssembly模块实现的工具
+///
+/// 插件需要导出 `memory`、`alloc(len) -> ptr` 和 `run(ptr, len) -> status`。
+/// 参数以JSON对象（参数名到值）写入插件内存后调用 `run`，返回0表示成功，
+/// 通过 `output` 写出的内容作为观察结果（失败时作为错误信息）。
+#[derive(Clone)]
+pub struct WasmPluginTool {
+    config: PluginConfig,
+    engine: Engine,
+    module: Module,
+    project_directory: String,
+}
+
+impl WasmPluginTool {
+    pub fn new(
+        engine: &Engine,
+        project_directory: &str,
+        config: PluginConfig,
+    ) -> AgentResult<Self> {
+        let invalid = |message: String| {
+            AgentError::ParseError(t!(
+                "plugin.invalid_plugin",
+                name = config.name,
+                error = message
+            ))
+        };
+
+        if !is_valid_name(&config.name) {
+            return Err(invalid(t!("custom.invalid_name")));
+        }
+        validate_parameters(&config.parameters).map_err(invalid)?;
+
+        let path = Path::new(project_directory).join(&config.path);
+        let module = Module::from_file(engine, &path)
+            .map_err(|e| invalid(t!("plugin.load_failed", path = path.display(), error = e)))?;
+
+        // 加载时就检查插件需要的能力，未授予的能力直接拒绝
+        let granted: HashSet<Capability> = config.capabilities.iter().copied().collect();
+        for import in module.imports() {
+            let capability = (import.module() == HOST_MODULE)
+                .then(|| Capability::from_import_name(import.name()))
+                .flatten();
+            match capability {
+                Some(capability) if granted.contains(&capability) => {}
+                Some(_) => {
+                    return Err(invalid(t!(
+                        "plugin.ungranted_capability",
+                        name = import.name()
+                    )));
+                }
+                None => {
+                    return Err(invalid(t!(
+                        "plugin.unsupported_import",
+                        module = import.module(),
+                        name = import.name()
+                    )));
+                }
+            }
+        }
+        for export in ["memory", "alloc", "run"] {
+            if module.get_export(export).is_none() {
+                return Err(invalid(t!("plugin.missing_export", name = export)));
+            }
+        }
+
+        Ok(Self {
+            config,
+            engine: engine.clone(),
+            module,
+            project_directory: project_directory.to_string(),
+        })
+    }
+
+    fn fuel(&self) -> u64 {
+        self.config.fuel.unwrap_or(DEFAULT_FUEL)
+    }
+
+    fn memory_limit_mb(&self) -> usize {
+        self.config
+            .memory_limit_mb
+            .unwrap_or(DEFAULT_MEMORY_LIMIT_MB)
+    }
+
+    /// 在独立的Store中运行一次插件，每次调用都有完整的fuel和内存额度
+    fn run(&self, input: &[u8]) -> AgentResult<String> {
+        let state = HostState {
+            project_directory: self.project_directory.clone(),
+            output: Vec::new(),
+            output_truncated: false,
+            limits: PluginLimits {
+                memory_bytes: self.memory_limit_mb() * 1024 * 1024,
+                memory_exceeded: false,
+            },
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+
+        let linker = build_linker(&self.engine, &self.config.capabilities)?;
+        let status = self.call(&mut store, &linker, input).map_err(|e| {
+            AgentError::ToolExecutionError(t!(
+                "plugin.run_failed",
+                name = self.config.name,
+                error = describe_trap(&e, self.fuel(), &store.data().limits)
+            ))
+        })?;
+
+        let state = store.data();
+        let mut output = String::from_utf8_lossy(&state.output).to_string();
+        if state.output_truncated {
+            output.push('\n');
+            output.push_str(&t!("plugin.truncated", bytes = MAX_OUTPUT_BYTES));
+        }
+        if status != 0 {
+            return Err(AgentError::ToolExecutionError(t!(
+                "plugin.error_status",
+                name = self.config.name,
+                status = status,
+                output = output
+            )));
+        }
+        Ok(output)
+    }
+
+    /// 实例化插件，写入参数并调用 `run`，返回插件的状态码
+    fn call(
+        &self,
+        store: &mut Store<HostState>,
+        linker: &Linker<HostState>,
+        input: &[u8],
+    ) -> wasmtime::Result<i32> {
+        store.set_fuel(self.fuel())?;
+        let instance = linker.instantiate(&mut *store, &self.module)?;
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg(t!("plugin.missing_export", name = "memory")))?;
+        let alloc: TypedFunc<i32, i32> = instance.get_typed_func(&mut *store, "alloc")?;
+        let run: TypedFunc<(i32, i32), i32> = instance.get_typed_func(&mut *store, "run")?;
+
+        let input_ptr = alloc.call(&mut *store, input.len() as i32)?;
+        memory.write(&mut *store, input_ptr as u32 as usize, input)?;
+        run.call(&mut *store, (input_ptr, input.len() as i32))
+    }
+}
+
+#[async_trait]
+impl Tool for WasmPluginTool {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn description(&self) -> &str {
+        &self.config.description
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.config.requires_approval
+    }
+
+    /// 只被授予了只读能力的插件无法修改任何内容
+    fn is_read_only(&self) -> bool {
+        self.config
+            .capabilities
+            .iter()
+            .all(Capability::is_read_only)
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        tool_parameters(&self.config.parameters)
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let input: serde_json::Map<String, serde_json::Value> =
+            bind_arguments(&self.config.name, &self.config.parameters, &args)?
+                .into_iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value?.into())))
+                .collect();
+        let input = serde_json::to_vec(&input)?;
+
+        // 插件执行是同步的CPU计算，放到阻塞线程中避免占用异步运行时
+        let plugin = self.clone();
+        tokio::task::spawn_blocking(move || plugin.run(&input))
+            .await
+            .map_err(|e| AgentError::RuntimeError(t!("plugin.thread_failed", error = e)))?
+    }
+}
+
+/// 读取项目中声明的WebAssembly插件，配置文件不存在时返回空列表
+pub fn load_plugin_tools(project_directory: &str) -> AgentResult<Vec<WasmPluginTool>> {
+    let path = Path::new(project_directory).join(PLUGINS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    let file: PluginsFile = toml::from_str(&content).map_err(|e| {
+        AgentError::ParseError(t!(
+            "plugin.invalid_config",
+            path = path.display(),
+            error = e
+        ))
+    })?;
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)
+        .map_err(|e| AgentError::RuntimeError(t!("plugin.engine_failed", error = e)))?;
+
+    file.plugins
+        .into_iter()
+        .map(|plugin| WasmPluginTool::new(&engine, project_directory, plugin))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 先输出收到的参数，再读取 data.txt 并输出其内容
+    const READER_PLUGIN: &str = r#"
+        (module
+          (import "rust_agent" "read_file" (func $read_file (param i32 i32) (result i64)))
+          (import "rust_agent" "output" (func $output (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 16) "data.txt")
+          (global $heap (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $heap))
+            (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+            (local.get $ptr))
+          (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+            (local $file i64)
+            (call $output (local.get $ptr) (local.get $len))
+            (local.set $file (call $read_file (i32.const 16) (i32.const 8)))
+            (if (i64.lt_s (local.get $file) (i64.const 0))
+              (then (return (i32.const 1))))
+            (call $output
+              (i32.wrap_i64 (i64.shr_u (local.get $file) (i64.const 32)))
+              (i32.wrap_i64 (local.get $file)))
+            (i32.const 0)))
+    "#;
+
+    const LOOP_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "run") (param i32 i32) (result i32)
+            (loop $forever (br $forever))
+            (i32.const 0)))
+    "#;
+
+    /// 越界读取内存，失败与内存上限无关
+    const OUT_OF_BOUNDS_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "run") (param i32 i32) (result i32) (i32.load (i32.const 70000))))
+    "#;
+
+    const LARGE_MEMORY_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 512)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "run") (param i32 i32) (result i32) (i32.const 0)))
+    "#;
+
+    fn setup(project: &tempfile::TempDir, plugins: &[(&str, &str)], config: &str) -> String {
+        let dir = project.path().join(".rust-agent");
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in plugins {
+            std::fs::write(project.path().join(file), source).unwrap();
+        }
+        std::fs::write(dir.join("plugins.toml"), config).unwrap();
+        project.path().display().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_runs_plugin_with_granted_capabilities() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("data.txt"), "file content").unwrap();
+        let dir = setup(
+            &project,
+            &[("reader.wat", READER_PLUGIN)],
+            r#"
+            [[plugin]]
+            name = "reader"
+            description = "读取data.txt"
+            path = "reader.wat"
+            capabilities = ["read_file", "output"]
+            parameters = [{ name = "label", required = false }]
+            "#,
+        );
+
+        let tools = load_plugin_tools(&dir).unwrap();
+        assert!(tools[0].is_read_only());
+        let output = tools[0].execute(vec!["x".to_string()]).await.unwrap();
+        assert_eq!(output, r#"{"label":"x"}file content"#);
+
+        std::fs::remove_file(project.path().join("data.txt")).unwrap();
+        let error = tools[0].execute(vec![]).await.unwrap_err().to_string();
+        assert!(error.contains(&t!(
+            "plugin.error_status",
+            name = "reader",
+            status = 1,
+            output = "{}"
+        )));
+    }
+
+    #[test]
+    fn test_rejects_ungranted_capabilities() {
+        let project = tempfile::tempdir().unwrap();
+        let dir = setup(
+            &project,
+            &[("reader.wat", READER_PLUGIN)],
+            r#"
+            [[plugin]]
+            name = "reader"
+            description = "只授予输出能力"
+            path = "reader.wat"
+            capabilities = ["output"]
+            "#,
+        );
+
+        let error = load_plugin_tools(&dir).err().unwrap().to_string();
+        assert!(error.contains(&t!("plugin.ungranted_capability", name = "read_file")));
+    }
+
+    #[tokio::test]
+    async fn test_enforces_fuel_and_memory_limits() {
+        let project = tempfile::tempdir().unwrap();
+        let dir = setup(
+            &project,
+            &[
+                ("loop.wat", LOOP_PLUGIN),
+                ("large.wat", LARGE_MEMORY_PLUGIN),
+                ("bounds.wat", OUT_OF_BOUNDS_PLUGIN),
+            ],
+            r#"
+            [[plugin]]
+            name = "spin"
+            description = "死循环"
+            path = "loop.wat"
+            fuel = 10000
+
+            [[plugin]]
+            name = "large"
+            description = "初始内存32MB"
+            path = "large.wat"
+            memory_limit_mb = 16
+
+            [[plugin]]
+            name = "bounds"
+            description = "越界读取"
+            path = "bounds.wat"
+            "#,
+        );
+
+        let tools = load_plugin_tools(&dir).unwrap();
+        let error = tools[0].execute(vec![]).await.unwrap_err().to_string();
+        assert!(
+            error.contains(&t!("plugin.out_of_fuel", fuel = 10000)),
+            "{}",
+            error
+        );
+
+        let error = tools[1].execute(vec![]).await.unwrap_err().to_string();
+        let limit = t!("plugin.memory_limit", error = "", limit = 16);
+        assert!(error.contains(&limit), "{}", error);
+
+        let error = tools[2].execute(vec![]).await.unwrap_err().to_string();
+        assert!(!error.contains(&limit), "{}", error);
+    }
+}
@@ -0,0 +1,586 @@
+//! 本地全文检索：按符号或行把项目文件切分成片段，建立 BM25 索引，不依赖外部服务。
+//! 索引保存在项目目录中，每次查询前只重新索引修改过的文件
+
+use super::outline::{outline, SourceLanguage, Symbol};
+use super::{Tool, ToolParameter};
+use crate::errors::{AgentError, AgentResult};
+use crate::i18n::prompt_text;
+use crate::memory::push_bigrams;
+use crate::t;
+use async_trait::async_trait;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// 索引文件，相对于项目目录
+pub const INDEX_FILE: &str = ".rust-agent/index.json";
+/// 索引格式版本，切分或分词方式改变时递增，旧索引会被重建
+const INDEX_VERSION: u32 = 1;
+
+/// 不属于任何符号的行按这个行数切分
+const CHUNK_LINES: usize = 40;
+/// 不超过这个行数的符号作为一个片段，更长的符号按其中的子符号或行切分
+const MAX_SYMBOL_LINES: usize = 80;
+/// 超过这个大小的文件不建立索引
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+/// 最多索引的文件数
+const MAX_INDEXED_FILES: usize = 20_000;
+const DEFAULT_RESULTS: usize = 5;
+const MAX_RESULTS: usize = 20;
+
+/// BM25 参数
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// 文件中连续的若干行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Chunk {
+    start_line: usize,
+    end_line: usize,
+    /// 片段所属的符号，如 `ReActAgent::run`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    /// 词语及其出现次数，包括文件路径和符号名中的词语
+    terms: HashMap<String, u32>,
+    length: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    /// 修改时间（毫秒）和大小，任意一个变化时重新索引
+    modified: u64,
+    size: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexData {
+    version: u32,
+    /// 相对于项目目录的路径
+    files: BTreeMap<String, IndexedFile>,
+}
+
+/// 一条检索结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub symbol: Option<String>,
+    pub score: f64,
+}
+
+/// 一次增量更新的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IndexUpdate {
+    /// 新建或重新索引的文件数
+    pub indexed: usize,
+    pub removed: usize,
+}
+
+impl IndexUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.indexed == 0 && self.removed == 0
+    }
+}
+
+pub struct SearchIndex {
+    project_dir: PathBuf,
+    data: IndexData,
+}
+
+impl SearchIndex {
+    /// 读取项目中保存的索引，文件不存在、无法解析或版本不同时从空索引开始
+    pub fn load(project_dir: impl AsRef<Path>) -> Self {
+        let project_dir = project_dir.as_ref().to_path_buf();
+        let data = fs::read_to_string(project_dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<IndexData>(&content).ok())
+            .filter(|data| data.version == INDEX_VERSION)
+            .unwrap_or_else(|| IndexData {
+                version: INDEX_VERSION,
+                files: BTreeMap::new(),
+            });
+        Self { project_dir, data }
+    }
+
+    /// 已索引的文件数
+    pub fn file_count(&self) -> usize {
+        self.data.files.len()
+    }
+
+    /// 扫描项目（遵守 .gitignore），重新索引新增或修改过的文件，移除已删除的文件
+    pub fn update(&mut self) -> IndexUpdate {
+        let mut update = IndexUpdate::default();
+        let mut seen = HashSet::new();
+        let walker = WalkBuilder::new(&self.project_dir)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walker.filter_map(|e| e.ok()) {
+            if seen.len() >= MAX_INDEXED_FILES {
+                break;
+            }
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&self.project_dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default();
+
+            let unchanged = self
+                .data
+                .files
+                .get(&relative)
+                .is_some_and(|file| file.modified == modified && file.size == metadata.len());
+            if !unchanged {
+                // 无法按UTF-8读取的文件（如二进制文件）不建立索引
+                let Ok(content) = fs::read_to_string(entry.path()) else {
+                    continue;
+                };
+                if content.contains('\0') {
+                    continue;
+                }
+                let chunks = index_file(&relative, &content);
+                self.data.files.insert(
+                    relative.clone(),
+                    IndexedFile {
+                        modified,
+                        size: metadata.len(),
+                        chunks,
+                    },
+                );
+                update.indexed += 1;
+            }
+            seen.insert(relative);
+        }
+
+        let before = self.data.files.len();
+        self.data.files.retain(|path, _| seen.contains(path));
+        update.removed = before - self.data.files.len();
+        update
+    }
+
+    pub fn save(&self) -> AgentResult<()> {
+        let path = self.project_dir.join(INDEX_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(&self.data)?)?;
+        Ok(())
+    }
+
+    /// 按 BM25 分数返回与查询最相关的片段
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query: HashSet<String> = tokenize(query).into_iter().collect();
+        let chunks: Vec<(&str, &Chunk)> = self
+            .data
+            .files
+            .iter()
+            .flat_map(|(path, file)| file.chunks.iter().map(move |chunk| (path.as_str(), chunk)))
+            .collect();
+        if query.is_empty() || chunks.is_empty() {
+            return Vec::new();
+        }
+
+        let total = chunks.len() as f64;
+        let average_length = chunks.iter().map(|(_, c)| c.length as f64).sum::<f64>() / total;
+        let idf: HashMap<&str, f64> = query
+            .iter()
+            .map(|term| {
+                let frequency = chunks
+                    .iter()
+                    .filter(|(_, chunk)| chunk.terms.contains_key(term))
+                    .count() as f64;
+                let idf = (1.0 + (total - frequency + 0.5) / (frequency + 0.5)).ln();
+                (term.as_str(), idf)
+            })
+            .collect();
+
+        let mut hits: Vec<SearchHit> = chunks
+            .iter()
+            .filter_map(|(path, chunk)| {
+                let normalized = 1.0 - B + B * chunk.length as f64 / average_length.max(1.0);
+                let score: f64 = idf
+                    .iter()
+                    .filter_map(|(term, idf)| {
+                        let tf = *chunk.terms.get(*term)? as f64;
+                        Some(idf * tf * (K1 + 1.0) / (tf + K1 * normalized))
+                    })
+                    .sum();
+                (score > 0.0).then(|| SearchHit {
+                    path: path.to_string(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    symbol: chunk.symbol.clone(),
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+                .then(a.start_line.cmp(&b.start_line))
+        });
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// 切分文件并统计每个片段的词语
+fn index_file(path: &str, content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let path_terms = tokenize(path);
+    chunk_ranges(path, content, lines.len())
+        .into_iter()
+        .filter_map(|(start_line, end_line, symbol)| {
+            let mut terms: HashMap<String, u32> = HashMap::new();
+            let text = lines[start_line - 1..end_line].join("\n");
+            let symbol_terms = symbol.as_deref().map(tokenize).unwrap_or_default();
+            let body_terms = tokenize(&text);
+            if body_terms.is_empty() {
+                return None;
+            }
+            for term in path_terms.iter().chain(&symbol_terms).chain(&body_terms) {
+                *terms.entry(term.clone()).or_default() += 1;
+            }
+            let length = terms.values().sum();
+            Some(Chunk {
+                start_line,
+                end_line,
+                symbol,
+                terms,
+                length,
+            })
+        })
+        .collect()
+}
+
+/// 片段的行范围（从 1 开始，包含两端）：支持解析的源码按符号切分，其余的行按固定行数切分
+fn chunk_ranges(
+    path: &str,
+    content: &str,
+    line_count: usize,
+) -> Vec<(usize, usize, Option<String>)> {
+    let mut symbols = Vec::new();
+    if let Some(language) = SourceLanguage::from_path(Path::new(path)) {
+        if let Ok(outline) = outline(content, language) {
+            collect_symbol_ranges(&outline, None, &mut symbols);
+        }
+    }
+    symbols.sort_by_key(|(start, _, _)| *start);
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut ranges = Vec::new();
+    let mut next_line = 1;
+    for (mut start, end, symbol) in symbols {
+        // 符号前紧挨着的文档注释和属性属于这个符号
+        while start > next_line && is_symbol_prefix(lines[start - 2]) {
+            start -= 1;
+        }
+        // 嵌套或重叠的符号只保留未被覆盖的部分
+        let start = start.max(next_line);
+        let end = end.min(line_count);
+        if start > end {
+            continue;
+        }
+        split_lines(next_line, start - 1, None, &mut ranges);
+        split_lines(start, end, Some(symbol), &mut ranges);
+        next_line = end + 1;
+    }
+    split_lines(next_line, line_count, None, &mut ranges);
+    ranges
+}
+
+fn is_symbol_prefix(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "/*", "*", "#", "@"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+fn collect_symbol_ranges(
+    symbols: &[Symbol],
+    parent: Option<&str>,
+    ranges: &mut Vec<(usize, usize, String)>,
+) {
+    for symbol in symbols {
+        let name = match parent {
+            Some(parent) => format!("{}::{}", parent, symbol.name),
+            None => symbol.name.clone(),
+        };
+        if symbol.end_line - symbol.start_line >= MAX_SYMBOL_LINES && !symbol.children.is_empty() {
+            collect_symbol_ranges(&symbol.children, Some(&name), ranges);
+        } else {
+            ranges.push((symbol.start_line, symbol.end_line, name));
+        }
+    }
+}
+
+/// 把 `start..=end` 行按 `CHUNK_LINES` 切分；不超过 `MAX_SYMBOL_LINES` 的符号不切分
+fn split_lines(
+    start: usize,
+    end: usize,
+    symbol: Option<String>,
+    ranges: &mut Vec<(usize, usize, Option<String>)>,
+) {
+    if start > end {
+        return;
+    }
+    if symbol.is_some() && end - start < MAX_SYMBOL_LINES {
+        ranges.push((start, end, symbol));
+        return;
+    }
+    let mut chunk_start = start;
+    while chunk_start <= end {
+        let chunk_end = (chunk_start + CHUNK_LINES - 1).min(end);
+        ranges.push((chunk_start, chunk_end, symbol.clone()));
+        chunk_start = chunk_end + 1;
+    }
+}
+
+/// 代码的分词：标识符按下划线和驼峰拆开并转为小写，中文等其他文字按相邻两个字；忽略单个字母
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut others: Vec<char> = Vec::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+        } else {
+            push_identifier(&mut tokens, &std::mem::take(&mut word));
+        }
+        if !c.is_ascii() && c.is_alphanumeric() {
+            others.push(c);
+        } else {
+            push_bigrams(&mut tokens, &std::mem::take(&mut others));
+        }
+    }
+    tokens
+}
+
+/// 按驼峰拆分标识符，如 `HTTPRequestTool` 拆为 http、request、tool
+fn push_identifier(tokens: &mut Vec<String>, word: &str) {
+    let chars: Vec<char> = word.chars().collect();
+    let mut part = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(index + 1);
+        let boundary = c.is_ascii_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_ascii_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_ascii_uppercase() && next.is_some_and(|n| n.is_ascii_lowercase()))
+            });
+        if boundary {
+            push_part(tokens, &std::mem::take(&mut part));
+        }
+        part.push(c.to_ascii_lowercase());
+    }
+    push_part(tokens, &part);
+}
+
+fn push_part(tokens: &mut Vec<String>, part: &str) {
+    if part.len() > 1 {
+        tokens.push(part.to_string());
+    }
+}
+
+/// 根据自然语言描述查找项目中最相关的代码片段
+pub struct FindRelevantTool {
+    project_directory: String,
+    /// 第一次查询时加载
+    index: Arc<Mutex<Option<SearchIndex>>>,
+}
+
+impl FindRelevantTool {
+    pub fn new(project_directory: String) -> Self {
+        Self {
+            project_directory,
+            index: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 更新并保存索引后查询，返回结果及对应的文件内容
+    fn search(&self, query: &str, limit: usize) -> AgentResult<Vec<(SearchHit, String)>> {
+        let mut guard = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        let index = guard.get_or_insert_with(|| SearchIndex::load(&self.project_directory));
+        if !index.update().is_empty() {
+            index.save()?;
+        }
+        Ok(index
+            .search(query, limit)
+            .into_iter()
+            .map(|hit| {
+                let content =
+                    fs::read_to_string(Path::new(&self.project_directory).join(&hit.path))
+                        .unwrap_or_default()
+                        .lines()
+                        .skip(hit.start_line - 1)
+                        .take(hit.end_line + 1 - hit.start_line)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                (hit, content)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Tool for FindRelevantTool {
+    fn name(&self) -> &str {
+        "find_relevant"
+    }
+
+    fn description(&self) -> &str {
+        prompt_text("tools.prompt.find_relevant")
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter::required("query", prompt_text("tools.prompt.relevant_query")),
+            ToolParameter::optional("limit", prompt_text("tools.prompt.relevant_limit")),
+        ]
+    }
+
+    async fn execute(&self, args: Vec<String>) -> AgentResult<String> {
+        let query = args.first().cloned().unwrap_or_default();
+        let limit = match args.get(1).map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            None => DEFAULT_RESULTS,
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) if limit > 0 => limit.min(MAX_RESULTS),
+                _ => return Ok(t!("retrieval.invalid_limit", value = limit)),
+            },
+        };
+
+        // 首次建立索引需要读取整个项目，放到阻塞线程中执行
+        let tool = Self {
+            project_directory: self.project_directory.clone(),
+            index: Arc::clone(&self.index),
+        };
+        let hits = tokio::task::spawn_blocking(move || tool.search(&query, limit))
+            .await
+            .map_err(|e| AgentError::ToolExecutionError(e.to_string()))??;
+
+        if hits.is_empty() {
+            return Ok(t!("retrieval.no_results"));
+        }
+        Ok(hits
+            .iter()
+            .map(|(hit, content)| {
+                let symbol = hit
+                    .symbol
+                    .as_ref()
+                    .map(|symbol| format!(" ({})", symbol))
+                    .unwrap_or_default();
+                format!(
+                    "{}:{}-{}{} score={:.2}\n{}",
+                    hit.path, hit.start_line, hit.end_line, symbol, hit.score, content
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_splits_identifiers() {
+        assert_eq!(
+            tokenize("HTTPRequestTool::parse_action(x) 解析动作"),
+            vec!["http", "request", "tool", "parse", "action", "解析", "析动", "动作"]
+        );
+    }
+
+    #[test]
+    fn test_rust_files_are_chunked_by_symbol() {
+        let body = (0..100)
+            .map(|i| format!("    let v{} = {};", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let content = format!(
+            "use std::fs;\n\nfn small() {{\n    load_config();\n}}\n\nimpl Agent {{\n    fn long() {{\n{}\n    }}\n}}\n",
+            body
+        );
+        let ranges = chunk_ranges("src/lib.rs", &content, content.lines().count());
+        assert_eq!(ranges[0], (1, 2, None));
+        assert_eq!(ranges[1], (3, 5, Some("small".to_string())));
+        assert_eq!(ranges[2], (6, 7, None));
+        // 过长的 impl 按其中的方法切分，过长的方法再按行切分
+        assert_eq!(ranges[3], (8, 47, Some("Agent::long".to_string())));
+        assert_eq!(ranges.last().unwrap().2, None);
+        assert_eq!(ranges.last().unwrap().1, content.lines().count());
+    }
+
+    #[test]
+    fn test_incremental_update_and_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/config.rs"),
+            "/// Load layered configuration files\nfn load_config() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/parser.rs"), "fn parse_action() {}\n").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/config.rs"), "fn load_config() {}\n").unwrap();
+
+        let mut index = SearchIndex::load(root);
+        assert_eq!(
+            index.update(),
+            IndexUpdate {
+                indexed: 2,
+                removed: 0
+            }
+        );
+        index.save().unwrap();
+
+        let hits = index.search("where is the configuration loaded", 5);
+        assert_eq!(hits[0].path, "src/config.rs");
+        assert_eq!(hits[0].symbol.as_deref(), Some("load_config"));
+        assert!(hits.iter().all(|hit| !hit.path.starts_with("target/")));
+
+        // 重新加载后只索引变化的文件
+        let mut index = SearchIndex::load(root);
+        assert_eq!(index.file_count(), 2);
+        assert!(index.update().is_empty());
+        fs::remove_file(root.join("src/parser.rs")).unwrap();
+        fs::write(root.join("src/agent.rs"), "fn run_agent_loop() {}\n").unwrap();
+        assert_eq!(
+            index.update(),
+            IndexUpdate {
+                indexed: 1,
+                removed: 1
+            }
+        );
+        assert!(index.search("parse action", 5).is_empty());
+        assert_eq!(index.search("agent loop", 5)[0].path, "src/agent.rs");
+    }
+}
@@ -0,0 +1,465 @@
+//! 运行轨迹：每个任务的系统提示词、每次请求新增的消息、模型的原始输出和解析结果、工具参数和观察结果、
+//! 耗时、重试和最终结果，按发生顺序逐行以JSON写入 `.rust-agent/trajectories/`，用于排查失败的运行
+
+use crate::errors::{AgentError, AgentResult};
+use crate::t;
+use async_openai::types::ChatCompletionRequestMessage;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 轨迹文件的保存目录，相对于项目目录
+pub const TRAJECTORIES_DIR: &str = ".rust-agent/trajectories";
+
+/// 不显示完整内容时，观察结果和消息最多显示的行数
+const PREVIEW_LINES: usize = 10;
+
+/// 本进程内轨迹文件的编号，避免同一秒开始的任务使用同一个文件
+static NEXT_TRAJECTORY_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// 模型给出的最终答案
+    Answer(String),
+    /// 用户拒绝了需要确认的操作
+    Cancelled,
+    /// 任务因错误中止
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TrajectoryEvent {
+    Start {
+        task: String,
+        model: String,
+        /// Agent的嵌套层数，子Agent的运行也会记录
+        depth: usize,
+        date: String,
+    },
+    SystemPrompt {
+        content: String,
+    },
+    /// 第 `turn` 次请求模型，`messages` 为上次请求之后新增的消息，`total_messages` 不含系统提示词
+    Request {
+        turn: usize,
+        messages: Vec<ChatCompletionRequestMessage>,
+        total_messages: usize,
+    },
+    Response {
+        turn: usize,
+        raw: String,
+        thought: Option<String>,
+        actions: Vec<String>,
+        final_answer: Option<String>,
+        duration_ms: u64,
+    },
+    /// 输出中没有完整的动作，要求模型重新输出
+    Retry {
+        turn: usize,
+        attempt: usize,
+        error: String,
+    },
+    /// 执行一个动作；`arguments` 为对应到工具参数后的值，无法对应时为 None，`failed` 表示工具返回了错误并中止任务
+    Tool {
+        action: String,
+        tool: String,
+        arguments: Option<Vec<String>>,
+        observation: String,
+        failed: bool,
+        duration_ms: u64,
+    },
+    Finish {
+        outcome: Outcome,
+        duration_ms: u64,
+    },
+}
+
+/// 轨迹文件中的一行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryEntry {
+    /// 距任务开始的毫秒数
+    pub elapsed_ms: u64,
+    #[serde(flatten)]
+    pub event: TrajectoryEvent,
+}
+
+/// 把一个任务的事件写入轨迹文件，每个事件立即写入，任务中断时已记录的部分依然保留
+#[derive(Debug)]
+pub struct TrajectoryRecorder {
+    path: PathBuf,
+    started: Instant,
+    /// 写入失败后为 None，不再记录
+    file: Mutex<Option<File>>,
+}
+
+impl TrajectoryRecorder {
+    /// 在项目的轨迹目录中创建新的轨迹文件
+    pub fn create(project_dir: impl AsRef<Path>) -> AgentResult<Self> {
+        let directory = project_dir.as_ref().join(TRAJECTORIES_DIR);
+        fs::create_dir_all(&directory)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        loop {
+            let id = NEXT_TRAJECTORY_ID.fetch_add(1, Ordering::Relaxed);
+            let path = directory.join(format!("{}-{}.jsonl", timestamp, id));
+            // 其他进程可能已使用了同名文件
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    return Ok(Self {
+                        path,
+                        started: Instant::now(),
+                        file: Mutex::new(Some(file)),
+                    })
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 距任务开始的时间
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// 追加一个事件；写入失败时给出警告，之后的事件不再记录
+    pub fn record(&self, event: TrajectoryEvent) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(writer) = file.as_mut() else {
+            return;
+        };
+        let entry = TrajectoryEntry {
+            elapsed_ms: millis(self.elapsed()),
+            event,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(AgentError::from)
+            .and_then(|line| Ok(writer.write_all(format!("{}\n", line).as_bytes())?));
+        if let Err(e) = result {
+            eprintln!("\n\n{}", t!("trajectory.write_failed", error = e));
+            *file = None;
+        }
+    }
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+/// 从轨迹文件读取的一次运行
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    pub entries: Vec<TrajectoryEntry>,
+}
+
+impl Trajectory {
+    pub fn load(path: &Path) -> AgentResult<Self> {
+        let content = fs::read_to_string(path)?;
+        let entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    AgentError::ParseError(t!(
+                        "trajectory.invalid_line",
+                        line = index + 1,
+                        error = e
+                    ))
+                })
+            })
+            .collect::<AgentResult<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /// 项目中的轨迹文件，新的在前
+    pub fn list(project_dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(project_dir.join(TRAJECTORIES_DIR)) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        // 文件名以开始时间和编号开头，按数值排序
+        paths.sort_by_key(|path| {
+            let stem = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let mut parts = stem.splitn(2, '-').map(|p| p.parse::<u64>().unwrap_or(0));
+            (parts.next(), parts.next())
+        });
+        paths.reverse();
+        paths
+    }
+
+    /// 按路径、文件名或不带扩展名的文件名查找轨迹文件，`latest` 表示最近的一个
+    pub fn find(project_dir: &Path, name: &str) -> Option<PathBuf> {
+        if name == "latest" {
+            return Self::list(project_dir).into_iter().next();
+        }
+        let directory = project_dir.join(TRAJECTORIES_DIR);
+        [
+            PathBuf::from(name),
+            directory.join(name),
+            directory.join(format!("{}.jsonl", name)),
+        ]
+        .into_iter()
+        .find(|path| path.is_file())
+    }
+
+    pub fn task(&self) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match &entry.event {
+            TrajectoryEvent::Start { task, .. } => Some(task.as_str()),
+            _ => None,
+        })
+    }
+
+    /// 运行结果，任务未正常结束（如进程被中断）时为 None
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.entries.iter().find_map(|entry| match &entry.event {
+            TrajectoryEvent::Finish { outcome, .. } => Some(outcome),
+            _ => None,
+        })
+    }
+
+    /// 可读的运行过程；`full` 为 false 时省略系统提示词，较长的消息和观察结果只显示开头
+    pub fn render(&self, full: bool) -> String {
+        let mut lines = Vec::new();
+        for entry in &self.entries {
+            match &entry.event {
+                TrajectoryEvent::Start {
+                    task,
+                    model,
+                    depth,
+                    date,
+                } => {
+                    lines.push(t!("trajectory.start", task = task));
+                    lines.push(t!(
+                        "trajectory.start_detail",
+                        model = model,
+                        date = date,
+                        depth = depth
+                    ));
+                }
+                TrajectoryEvent::SystemPrompt { content } => {
+                    lines.push(t!(
+                        "trajectory.system_prompt",
+                        length = content.chars().count()
+                    ));
+                    if full {
+                        push_indented(&mut lines, content, true);
+                    }
+                }
+                TrajectoryEvent::Request {
+                    turn,
+                    messages,
+                    total_messages,
+                } => {
+                    lines.push(t!(
+                        "trajectory.request",
+                        turn = turn,
+                        count = messages.len(),
+                        total = total_messages
+                    ));
+                    for message in messages {
+                        let value = serde_json::to_value(message).unwrap_or_default();
+                        let role = value["role"].as_str().unwrap_or("?");
+                        let content = match &value["content"] {
+                            serde_json::Value::String(text) => text.clone(),
+                            other => other.to_string(),
+                        };
+                        push_indented(&mut lines, &format!("[{}] {}", role, content), full);
+                    }
+                }
+                TrajectoryEvent::Response {
+                    turn,
+                    raw,
+                    thought,
+                    actions,
+                    final_answer,
+                    duration_ms,
+                } => {
+                    lines.push(t!(
+                        "trajectory.response",
+                        turn = turn,
+                        seconds = seconds(*duration_ms)
+                    ));
+                    if let Some(thought) = thought {
+                        push_indented(&mut lines, &format!("💭 {}", thought), full);
+                    }
+                    for action in actions {
+                        push_indented(&mut lines, &format!("🔧 {}", action), full);
+                    }
+                    if let Some(answer) = final_answer {
+                        push_indented(&mut lines, &format!("✅ {}", answer), full);
+                    }
+                    if thought.is_none() && actions.is_empty() && final_answer.is_none() {
+                        push_indented(&mut lines, raw, full);
+                    }
+                }
+                TrajectoryEvent::Retry { attempt, error, .. } => {
+                    lines.push(t!("trajectory.retry", attempt = attempt, error = error))
+                }
+                TrajectoryEvent::Tool {
+                    action,
+                    observation,
+                    failed,
+                    duration_ms,
+                    ..
+                } => {
+                    let key = if *failed {
+                        "trajectory.tool_failed"
+                    } else {
+                        "trajectory.tool"
+                    };
+                    lines.push(t!(key, action = action, seconds = seconds(*duration_ms)));
+                    push_indented(&mut lines, observation, full);
+                }
+                TrajectoryEvent::Finish {
+                    outcome,
+                    duration_ms,
+                } => {
+                    let seconds = seconds(*duration_ms);
+                    match outcome {
+                        Outcome::Answer(answer) => {
+                            lines.push(t!("trajectory.answered", seconds = seconds));
+                            push_indented(&mut lines, answer, true);
+                        }
+                        Outcome::Cancelled => {
+                            lines.push(t!("trajectory.cancelled", seconds = seconds))
+                        }
+                        Outcome::Failed(error) => {
+                            lines.push(t!("trajectory.failed", seconds = seconds, error = error))
+                        }
+                    }
+                }
+            }
+        }
+        if self.outcome().is_none() {
+            lines.push(t!("trajectory.unfinished"));
+        }
+        lines.join("\n")
+    }
+}
+
+fn seconds(duration_ms: u64) -> String {
+    format!("{:.1}", duration_ms as f64 / 1000.0)
+}
+
+/// 缩进后加入输出，`full` 为 false 时只保留前几行
+pub(crate) fn push_indented(lines: &mut Vec<String>, text: &str, full: bool) {
+    let text = text.trim_end();
+    let total = text.lines().count();
+    let shown = if full {
+        total
+    } else {
+        total.min(PREVIEW_LINES)
+    };
+    lines.extend(text.lines().take(shown).map(|line| format!("   {}", line)));
+    if shown < total {
+        lines.push(format!(
+            "   {}",
+            t!("trajectory.more_lines", count = total - shown)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_load_and_render() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = TrajectoryRecorder::create(dir.path()).unwrap();
+        let second = TrajectoryRecorder::create(dir.path()).unwrap();
+        assert_ne!(recorder.path(), second.path());
+
+        recorder.record(TrajectoryEvent::Start {
+            task: "读取配置".to_string(),
+            model: "test-model".to_string(),
+            depth: 0,
+            date: "2026-01-01".to_string(),
+        });
+        recorder.record(TrajectoryEvent::Response {
+            turn: 1,
+            raw: "<action>read_file(\"a.toml\")</action>".to_string(),
+            thought: Some("先读文件".to_string()),
+            actions: vec!["read_file(\"a.toml\")".to_string()],
+            final_answer: None,
+            duration_ms: 1500,
+        });
+        let observation = (1..=15)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        recorder.record(TrajectoryEvent::Tool {
+            action: "read_file(a.toml)".to_string(),
+            tool: "read_file".to_string(),
+            arguments: Some(vec!["a.toml".to_string()]),
+            observation: observation.clone(),
+            failed: false,
+            duration_ms: 3,
+        });
+
+        // 没有结束事件的轨迹是被中断的运行
+        let trajectory = Trajectory::load(recorder.path()).unwrap();
+        assert_eq!(trajectory.entries.len(), 3);
+        assert_eq!(trajectory.task(), Some("读取配置"));
+        assert!(trajectory.outcome().is_none());
+        assert!(trajectory
+            .render(false)
+            .ends_with(&t!("trajectory.unfinished")));
+
+        recorder.record(TrajectoryEvent::Finish {
+            outcome: Outcome::Answer("完成".to_string()),
+            duration_ms: 2000,
+        });
+        let trajectory = Trajectory::load(recorder.path()).unwrap();
+        assert_eq!(
+            trajectory.outcome(),
+            Some(&Outcome::Answer("完成".to_string()))
+        );
+        let summary = trajectory.render(false);
+        assert!(summary.contains("💭 先读文件"));
+        assert!(summary.contains("line 10\n"));
+        assert!(!summary.contains("line 11"));
+        assert!(summary.contains(&t!("trajectory.more_lines", count = 5)));
+        assert!(trajectory.render(true).contains("line 15"));
+
+        assert_eq!(
+            Trajectory::find(dir.path(), "latest").as_deref(),
+            Some(second.path())
+        );
+        let name = recorder.path().file_stem().unwrap().to_str().unwrap();
+        assert_eq!(
+            Trajectory::find(dir.path(), name).as_deref(),
+            Some(recorder.path())
+        );
+        assert!(Trajectory::find(dir.path(), "missing").is_none());
+    }
+
+    #[test]
+    fn test_invalid_lines_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.jsonl");
+        fs::write(&path, "{\"elapsed_ms\":0,\"event\":\"finish\"}\n").unwrap();
+        let error = Trajectory::load(&path).unwrap_err().to_string();
+        assert!(error.contains('1'));
+    }
+}
@@ -35,17 +35,8 @@ pub enum AgentStep {
     FinalAnswer(FinalAnswer),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AgentState {
     pub messages: Vec<async_openai::types::ChatCompletionRequestMessage>,
     pub current_step: Option<AgentStep>,
 }
-
-impl Default for AgentState {
-    fn default() -> Self {
-        Self {
-            messages: Vec::new(),
-            current_step: None,
-        }
-    }
-}